@@ -1,10 +1,47 @@
-use backend::app_image_installer::install_app_image;
+use backend::app_image_installer::{
+    install_app_image_with_progress, install_app_images, InstallOptions,
+};
+use backend::app_image_self_updater::{self_update_app_image, SelfUpdateResult};
 use backend::app_image_uninstaller::uninstall_app_image;
 use backend::app_image_updater::app_image_update;
+use backend::app_image_verifier::verify_app_image;
+use backend::app_image_versions::{prune_app_image, rollback_app_image};
 use clap::Parser;
-use log::{error, info};
+use common_utils::app_images_helpers::{read_all_app, read_app_info};
+use common_utils::backend_event::{ActionResult, BatchFileStatus, ErrorCode};
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::{Error, ErrorKind};
+use common_utils::install_layout::DEFAULT_KEPT_VERSIONS;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, Registry, RegistryEntry};
+use common_utils::signature::{default_trusted_keys_dir, SignaturePolicy};
+use log::info;
+use output::{Output, OutputFormat};
+use progress::{CancelRequests, InstallReporter};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod output;
+mod progress;
+
+/// Documents the exit codes, see `ErrorCode`
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  unexpected failure
+  2  missing or invalid arguments
+  3  AppImage file or installed app not found
+  4  install failed, or some AppImages of a batch install failed
+  5  uninstall failed
+  6  update failed
+  7  registry could not be read or written
+  8  verify found missing or modified files
+  9  cancelled
+  10 app already installed in the scope
+
+With --output json, progress, warning, error and result events are written
+to stdout, one JSON object per line. Logs are written to stderr.
+Writing \"cancel\" on stdin then cancels a running install.";
 
 #[derive(clap::ValueEnum, Clone, Default, Debug, Serialize)]
 #[serde(rename_all = "kebab-case")]
@@ -13,14 +50,22 @@ enum Action {
     Install,
     Uninstall,
     Update,
+    UpdateApp,
+    Rollback,
+    Prune,
+    RebuildRegistry,
+    List,
+    Info,
+    Verify,
 }
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 struct Args {
-    /// AppImage file path to install
+    /// AppImage file path to install. Repeat it to install several AppImages in a batch,
+    /// which goes on past the AppImages that fail to install
     #[arg(short, long)]
-    file_path: Option<String>,
+    file_path: Vec<String>,
 
     /// Installation directory
     /// The directory where the AppImage will be installed
@@ -35,20 +80,60 @@ struct Args {
         value_enum,
         requires_if("install", "file_path"),
         requires_if("install", "install_dir"),
-        requires_if("uninstall", "uninstall_app_name"),
         requires_if("update", "new_install_dir"),
         requires_if("update", "old_install_dir")
     )]
     action: Action,
 
+    /// Id of the app to uninstall, update, roll back, prune, show or verify
+    #[arg(long)]
+    app_id: Option<String>,
+
+    /// Verify all the installed apps
+    #[arg(long)]
+    all: bool,
+
     /// App name to uninstall
     #[arg(short, long)]
     uninstall_app_name: Option<String>,
 
+    /// App name to update to its latest release
+    #[arg(long)]
+    update_app_name: Option<String>,
+
     /// No sandbox flag
     #[arg(short, long)]
     no_sandbox: Option<bool>,
 
+    /// What to do with unsigned AppImages or AppImages signed by an untrusted key:
+    /// refuse, warn or allow
+    #[arg(long)]
+    signature_policy: Option<SignaturePolicy>,
+
+    /// What to do when the app is already installed in the scope:
+    /// refuse, replace or keep-both
+    #[arg(long)]
+    on_duplicate: Option<DuplicatePolicy>,
+
+    /// Who the app is installed for: system (requires admin rights) or user.
+    /// list, info and verify look in both scopes when it is not given
+    #[arg(long)]
+    scope: Option<InstallScope>,
+
+    /// The number of versions of an app kept on disk by install, update-app and prune,
+    /// the current one included
+    #[arg(long, default_value_t = DEFAULT_KEPT_VERSIONS)]
+    keep: usize,
+
+    /// The version rollback switches to, by version number or directory name.
+    /// The previous version when not given
+    #[arg(long)]
+    to: Option<String>,
+
+    /// Directory containing the public keys of trusted publishers
+    #[arg(long)]
+    trusted_keys_dir: Option<String>,
+
     /// New app images directory path
     /// The new path to the directory where the AppImages are stored
     #[arg(long)]
@@ -58,70 +143,387 @@ struct Args {
     /// The old path to the directory where the AppImages are stored
     #[arg(long)]
     old_install_dir: Option<String>,
+
+    /// Output format: text logs, or JSON events on stdout
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Debug)
-        .init();
+/// A failed action, with the class of the failure
+struct Failure {
+    code: ErrorCode,
+    error: Error,
+}
 
-    info!("Starting AppHub backend");
+impl Failure {
+    fn new(code: ErrorCode, error: impl Into<Error>) -> Self {
+        Failure {
+            code,
+            error: error.into(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
+    let output = Output::new(args.output.unwrap_or_default());
+    output.init_logger();
+
+    info!("Starting AppHub backend");
+
+    let result = run(args, &output).and_then(|result| {
+        // the reports are written even when the verification or some installs fail
+        let failure = verify_failure(&result).or_else(|| batch_failure(&result));
+        output.result(result);
+        failure.map_or(Ok(()), Err)
+    });
+
+    match result {
+        Ok(()) => {
+            info!("AppHub backend finished successfully");
+            ExitCode::SUCCESS
+        }
+        Err(failure) => {
+            output.error(failure.code, &failure.error);
+            ExitCode::from(failure.code.exit_code())
+        }
+    }
+}
+
+/// The failure of a verification that found issues
+fn verify_failure(result: &ActionResult) -> Option<Failure> {
+    let ActionResult::Verified { reports } = result else {
+        return None;
+    };
+    let failed = reports.iter().filter(|report| !report.is_ok()).count();
+    if failed == 0 {
+        return None;
+    }
+    Some(Failure::new(
+        ErrorCode::VerifyFailed,
+        format!("{} of {} apps failed verification", failed, reports.len()),
+    ))
+}
+
+/// The failure of a batch install where some AppImages were not installed
+fn batch_failure(result: &ActionResult) -> Option<Failure> {
+    let ActionResult::BatchInstalled { files } = result else {
+        return None;
+    };
+    let failed: Vec<_> = files
+        .iter()
+        .filter_map(|file| match &file.status {
+            BatchFileStatus::Failed { error } => Some(error),
+            _ => None,
+        })
+        .collect();
+    if failed.is_empty() {
+        return None;
+    }
+    let code = if failed.iter().any(|error| error.is_cancelled()) {
+        ErrorCode::Cancelled
+    } else {
+        ErrorCode::InstallFailed
+    };
+    Some(Failure::new(
+        code,
+        format!("{} of {} AppImages failed to install", failed.len(), files.len()),
+    ))
+}
+
+fn run(args: Args, output: &Output) -> Result<ActionResult, Failure> {
+    let scope = args.scope.unwrap_or_default();
+    // the scopes searched by the queries
+    let scopes = match args.scope {
+        Some(scope) => vec![scope],
+        None => InstallScope::ALL.to_vec(),
+    };
+    // the actions that keep versions on disk always keep the current one
+    if args.keep == 0 && matches!(args.action, Action::Install | Action::UpdateApp | Action::Prune) {
+        return Err(Failure::new(
+            ErrorCode::InvalidArguments,
+            "keep must be at least 1, the current version is always kept",
+        ));
+    }
 
     match args.action {
         Action::Install => {
             info!("Received install action");
             // read required arguments
-            let file_path = args.file_path.as_ref().ok_or("file_path is required")?;
-            let installation_dir = args.install_dir.as_ref().ok_or("install_dir is required")?;
-            let no_sandbox = args.no_sandbox.unwrap_or(false);
+            let file_path = args
+                .file_path
+                .first()
+                .ok_or(Failure::new(ErrorCode::InvalidArguments, "file_path is required"))?;
+            let installation_dir = args
+                .install_dir
+                .as_ref()
+                .ok_or(Failure::new(ErrorCode::InvalidArguments, "install_dir is required"))?;
+            let options = InstallOptions {
+                no_sandbox: args.no_sandbox.unwrap_or(false),
+                signature_policy: args.signature_policy.unwrap_or_default(),
+                trusted_keys_dir: args
+                    .trusted_keys_dir
+                    .map(PathBuf::from)
+                    .or_else(default_trusted_keys_dir),
+                scope,
+                on_duplicate: args.on_duplicate.unwrap_or_default(),
+                keep_versions: args.keep,
+            };
+            let cancel_requests = if output.accepts_cancel_requests() {
+                CancelRequests::watch_stdin()
+            } else {
+                CancelRequests::default()
+            };
+            let reporter = InstallReporter {
+                output,
+                cancel_requests: &cancel_requests,
+            };
+
+            if args.file_path.len() > 1 {
+                // a single run, so that system installs ask for the password once
+                output.progress(&format!("Installing {} AppImages", args.file_path.len()));
+                let files = install_app_images(
+                    args.file_path.iter().map(PathBuf::from).collect(),
+                    PathBuf::from(installation_dir),
+                    &options,
+                    &reporter,
+                );
+                return Ok(ActionResult::BatchInstalled { files });
+            }
+
+            if !Path::new(file_path).is_file() {
+                return Err(Failure::new(
+                    ErrorCode::NotFound,
+                    Error::new(ErrorKind::NotFound, format!("AppImage not found: {}", file_path)),
+                ));
+            }
 
             // install the AppImage
-            if let Err(e) = install_app_image(
+            output.progress(&format!("Installing AppImage: {}", file_path));
+            let app = install_app_image_with_progress(
                 PathBuf::from(file_path.clone()),
                 PathBuf::from(installation_dir.clone()),
-                no_sandbox,
-            ) {
-                error!("Failed to install AppImage: {}", e);
-                return Err("Failed to install AppImage".into());
-            }
-            info!("Installing AppImage: {}", file_path);
+                &options,
+                &reporter,
+            )
+            .map_err(|e| {
+                let file_name = Path::new(file_path).file_name().unwrap_or_default();
+                let e = e.for_app(file_name.to_string_lossy());
+                match e.kind {
+                    ErrorKind::Cancelled => Failure::new(ErrorCode::Cancelled, e),
+                    ErrorKind::AlreadyInstalled => Failure::new(ErrorCode::AlreadyInstalled, e),
+                    _ => Failure::new(ErrorCode::InstallFailed, e),
+                }
+            })?;
+
+            Ok(ActionResult::Installed {
+                id: app.id,
+                name: app.name,
+                app_image_path: app.app_image_path,
+                scope: app.scope,
+            })
         }
         Action::Uninstall => {
             // read required arguments
-            let app_name = args
-                .uninstall_app_name
-                .as_ref()
-                .ok_or("uninstall_app_name is required")?;
+            let app = app_selector(&args.app_id, &args.uninstall_app_name).ok_or(
+                Failure::new(
+                    ErrorCode::InvalidArguments,
+                    "app_id or uninstall_app_name is required",
+                ),
+            )?;
+            let id = find_app_id(&app, &[scope])?;
 
             // uninstall the AppImage
-            if let Err(e) = uninstall_app_image(app_name.clone()) {
-                error!("Failed to uninstall AppImage: {}", e);
-                return Err("Failed to uninstall AppImage".into());
-            }
-            info!("Uninstalling AppImage with app name: {}", app_name);
+            output.progress(&format!("Uninstalling AppImage: {}", app));
+            uninstall_app_image(app, scope)
+                .map_err(|e| Failure::new(ErrorCode::UninstallFailed, e))?;
+
+            Ok(ActionResult::Uninstalled { id })
         }
         Action::Update => {
             // read required arguments
-            let new_install_dir = args
-                .new_install_dir
-                .as_ref()
-                .ok_or("new_install_dir is required")?;
-            let old_install_dir = args
-                .old_install_dir
-                .as_ref()
-                .ok_or("old_install_dir is required")?;
+            let new_install_dir = args.new_install_dir.as_ref().ok_or(Failure::new(
+                ErrorCode::InvalidArguments,
+                "new_install_dir is required",
+            ))?;
+            let old_install_dir = args.old_install_dir.as_ref().ok_or(Failure::new(
+                ErrorCode::InvalidArguments,
+                "old_install_dir is required",
+            ))?;
 
-            info!(
+            output.progress(&format!(
                 "Updating desktop files with new install dir: {}",
                 new_install_dir
-            );
+            ));
+
+            app_image_update(old_install_dir.clone(), new_install_dir.clone())
+                .map_err(|e| Failure::new(ErrorCode::UpdateFailed, e))?;
+
+            Ok(ActionResult::Moved {
+                install_dir: PathBuf::from(new_install_dir),
+            })
+        }
+        Action::UpdateApp => {
+            // read required arguments
+            let app = app_selector(&args.app_id, &args.update_app_name).ok_or(Failure::new(
+                ErrorCode::InvalidArguments,
+                "app_id or update_app_name is required",
+            ))?;
+            let id = find_app_id(&app, &[scope])?;
+
+            // update the AppImage using its embedded update information
+            output.progress(&format!("Updating AppImage: {}", app));
+            let options = InstallOptions {
+                no_sandbox: args.no_sandbox.unwrap_or(false),
+                signature_policy: args.signature_policy.unwrap_or_default(),
+                trusted_keys_dir: args
+                    .trusted_keys_dir
+                    .map(PathBuf::from)
+                    .or_else(default_trusted_keys_dir),
+                scope,
+                keep_versions: args.keep,
+                ..InstallOptions::default()
+            };
+            match self_update_app_image(app, &options) {
+                Ok(SelfUpdateResult::UpToDate) => Ok(ActionResult::UpToDate { id }),
+                Ok(SelfUpdateResult::Updated {
+                    app_image_path,
+                    reused_bytes,
+                    downloaded_bytes,
+                }) => Ok(ActionResult::Updated {
+                    id,
+                    app_image_path,
+                    reused_bytes,
+                    downloaded_bytes,
+                }),
+                Err(e) => Err(Failure::new(ErrorCode::UpdateFailed, e)),
+            }
+        }
+        Action::Rollback => {
+            let app = args
+                .app_id
+                .clone()
+                .map(AppSelector::Id)
+                .ok_or(Failure::new(ErrorCode::InvalidArguments, "app_id is required"))?;
+            find_app_id(&app, &[scope])?;
+
+            output.progress(&format!("Rolling back {}", app));
+            let app = rollback_app_image(app, scope, args.to.as_deref()).map_err(|e| {
+                match e.kind {
+                    ErrorKind::NotFound => Failure::new(ErrorCode::NotFound, e),
+                    _ => Failure::new(ErrorCode::UpdateFailed, e),
+                }
+            })?;
+
+            Ok(ActionResult::RolledBack {
+                id: app.id,
+                version: app.version,
+                app_image_path: app
+                    .versions
+                    .first()
+                    .map(|version| version.app_image_path.clone())
+                    .unwrap_or(app.app_image_path),
+            })
+        }
+        Action::Prune => {
+            let app = args
+                .app_id
+                .clone()
+                .map(AppSelector::Id)
+                .ok_or(Failure::new(ErrorCode::InvalidArguments, "app_id is required"))?;
+            let id = find_app_id(&app, &[scope])?;
+
+            output.progress(&format!("Removing the versions of {} beyond {}", app, args.keep));
+            let removed = prune_app_image(app, scope, args.keep)
+                .map_err(|e| Failure::new(ErrorCode::UpdateFailed, e))?;
+
+            Ok(ActionResult::Pruned { id, removed })
+        }
+        Action::RebuildRegistry => {
+            output.progress("Rebuilding the registry from the desktop files");
+
+            let registry = scope
+                .applications_dir()
+                .and_then(|applications_dir| {
+                    Registry::rebuild_from_desktop_files(
+                        &Registry::location(scope)?,
+                        &applications_dir,
+                        scope,
+                    )
+                })
+                .and_then(|registry| registry.save().map(|_| registry))
+                .map_err(|e| Failure::new(ErrorCode::RegistryFailed, e))?;
+
+            Ok(ActionResult::RegistryRebuilt {
+                apps: registry.apps().len(),
+            })
+        }
+        Action::List => {
+            let apps = read_all_app()
+                .map_err(|e| Failure::new(ErrorCode::RegistryFailed, e))?
+                .into_iter()
+                .filter(|app| scopes.contains(&app.scope))
+                .collect();
+            Ok(ActionResult::Listed { apps })
+        }
+        Action::Info => {
+            let app = args
+                .app_id
+                .clone()
+                .map(AppSelector::Id)
+                .ok_or(Failure::new(ErrorCode::InvalidArguments, "app_id is required"))?;
+            let app = find_app(&app, &scopes)?;
+
+            Ok(ActionResult::Info {
+                app: Box::new(read_app_info(app)),
+            })
+        }
+        Action::Verify => {
+            let mut apps = Vec::new();
+            if args.all {
+                for scope in &scopes {
+                    let registry = Registry::load_or_recover(*scope)
+                        .map_err(|e| Failure::new(ErrorCode::RegistryFailed, e))?;
+                    apps.extend(registry.apps().iter().cloned());
+                }
+            } else {
+                let app = args.app_id.clone().map(AppSelector::Id).ok_or(Failure::new(
+                    ErrorCode::InvalidArguments,
+                    "app_id or all is required",
+                ))?;
+                apps.push(find_app(&app, &scopes)?);
+            }
 
-            app_image_update(old_install_dir.clone(), new_install_dir.clone())?;
+            output.progress(&format!("Verifying {} apps", apps.len()));
+            let reports = apps.iter().map(verify_app_image).collect();
+            Ok(ActionResult::Verified { reports })
         }
     }
+}
 
-    info!("AppHub backend finished successfully");
+/// An installed app, failing when it is not in the registries of the scopes
+fn find_app(app: &AppSelector, scopes: &[InstallScope]) -> Result<RegistryEntry, Failure> {
+    Registry::find_in_scopes(app, scopes)
+        .map_err(|e| Failure::new(ErrorCode::RegistryFailed, e))?
+        .ok_or_else(|| {
+            Failure::new(
+                ErrorCode::NotFound,
+                Error::new(ErrorKind::NotFound, format!("App not found: {}", app))
+                    .for_app(app.to_string()),
+            )
+        })
+}
 
-    Ok(())
+/// The id of an installed app, failing when it is not in the registry
+fn find_app_id(app: &AppSelector, scopes: &[InstallScope]) -> Result<String, Failure> {
+    find_app(app, scopes).map(|app| app.id)
+}
+
+/// The app designated by its id, or by its name for older callers
+fn app_selector(app_id: &Option<String>, app_name: &Option<String>) -> Option<AppSelector> {
+    match (app_id, app_name) {
+        (Some(id), _) => Some(AppSelector::Id(id.clone())),
+        (None, Some(name)) => Some(AppSelector::Name(name.clone())),
+        (None, None) => None,
+    }
 }
@@ -0,0 +1,172 @@
+use common_utils::backend_event::{
+    ActionResult, BackendEvent, BatchFile, BatchFileStatus, ErrorCode, InstallPhase,
+};
+use common_utils::error::Error;
+use log::{debug, error, info, Level, Log, Metadata, Record};
+use serde::Serialize;
+
+/// How the backend reports what it does
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Log lines on stderr only
+    #[default]
+    Text,
+    /// One JSON event per line on stdout, log lines on stderr
+    Json,
+}
+
+/// Writes the events of the backend in the chosen format
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    format: OutputFormat,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Output { format }
+    }
+
+    /// Install the logger, filtered by `RUST_LOG` and at the info level by default. In JSON
+    /// mode the warnings are also reported as events.
+    pub fn init_logger(&self) {
+        let inner =
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+                .build();
+        let max_level = inner.filter();
+        let logger = EventLogger {
+            inner,
+            output: *self,
+        };
+        if log::set_boxed_logger(Box::new(logger)).is_ok() {
+            // the warnings are reported as events whatever the level of the logs
+            log::set_max_level(max_level.max(log::LevelFilter::Warn));
+        }
+    }
+
+    pub fn progress(&self, message: &str) {
+        info!("{}", message);
+        self.emit(&BackendEvent::Progress {
+            message: message.to_string(),
+        });
+    }
+
+    pub fn install_phase(&self, phase: InstallPhase) {
+        match phase {
+            // one event per copied chunk, too many for the logs
+            InstallPhase::Copying { .. } => debug!("{:?}", phase),
+            _ => info!("{:?}", phase),
+        }
+        self.emit(&BackendEvent::InstallPhase(phase));
+    }
+
+    pub fn batch_file(&self, file: &BatchFile) {
+        match &file.status {
+            BatchFileStatus::Installing => info!("Installing AppImage: {:?}", file.file_path),
+            BatchFileStatus::Installed { id, .. } => {
+                info!("Installed {:?} as {}", file.file_path, id)
+            }
+            BatchFileStatus::Failed { error } => {
+                error!("Failed to install {:?}: {}", file.file_path, error)
+            }
+        }
+        self.emit(&BackendEvent::BatchFile(file.clone()));
+    }
+
+    /// Whether the action can be cancelled by writing on stdin, as the GUI does
+    pub fn accepts_cancel_requests(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    pub fn error(&self, code: ErrorCode, error: &Error) {
+        error!("{} ({})", error, code);
+        self.emit(&BackendEvent::Error {
+            code,
+            error: error.clone(),
+        });
+    }
+
+    pub fn result(&self, result: ActionResult) {
+        info!("Result: {:?}", result);
+        if self.format == OutputFormat::Text {
+            print_text(&result);
+        }
+        self.emit(&BackendEvent::Result { result });
+    }
+
+    fn emit(&self, event: &BackendEvent) {
+        if self.format == OutputFormat::Json {
+            println!("{}", event.to_json_line());
+        }
+    }
+}
+
+/// Print the results of the queries on stdout, one line per item
+fn print_text(result: &ActionResult) {
+    match result {
+        ActionResult::Listed { apps } => {
+            for app in apps {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    app.id,
+                    app.version.as_deref().unwrap_or("-"),
+                    app.scope,
+                    app.size,
+                    app.app_path
+                );
+            }
+        }
+        ActionResult::Info { app } => {
+            println!("{}", serde_json::to_string_pretty(app).unwrap_or_default());
+        }
+        ActionResult::BatchInstalled { files } => {
+            for file in files {
+                match &file.status {
+                    BatchFileStatus::Installed { id, .. } => {
+                        println!("{}\tinstalled\t{}", file.file_path.display(), id)
+                    }
+                    BatchFileStatus::Failed { error } => {
+                        println!("{}\tfailed\t{}", file.file_path.display(), error)
+                    }
+                    BatchFileStatus::Installing => {}
+                }
+            }
+        }
+        ActionResult::Verified { reports } => {
+            for report in reports {
+                if report.is_ok() {
+                    println!("{}\tok", report.id);
+                }
+                for issue in &report.issues {
+                    println!("{}\t{}", report.id, issue);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Forwards the records to env_logger, and the warnings to the output as events
+struct EventLogger {
+    inner: env_logger::Logger,
+    output: Output,
+}
+
+impl Log for EventLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+        if record.level() == Level::Warn {
+            self.output.emit(&BackendEvent::Warning {
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
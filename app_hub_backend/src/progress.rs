@@ -0,0 +1,58 @@
+use crate::output::Output;
+use backend::app_image_installer::{BatchInstallProgress, InstallProgress};
+use common_utils::backend_event::{BatchFile, InstallPhase, CANCEL_REQUEST};
+use log::info;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Whether the GUI asked to cancel the running action, by writing
+/// [`CANCEL_REQUEST`] on stdin
+#[derive(Clone, Default)]
+pub struct CancelRequests {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelRequests {
+    /// Read the requests from stdin in the background
+    pub fn watch_stdin() -> Self {
+        let requests = CancelRequests::default();
+        let cancelled = requests.cancelled.clone();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim() == CANCEL_REQUEST {
+                    info!("Cancel requested");
+                    cancelled.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+        requests
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Reports the phases of an install and the AppImages of a batch as events, and stops it when it is cancelled
+pub struct InstallReporter<'a> {
+    pub output: &'a Output,
+    pub cancel_requests: &'a CancelRequests,
+}
+
+impl InstallProgress for InstallReporter<'_> {
+    fn phase(&self, phase: InstallPhase) {
+        self.output.install_phase(phase);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_requests.is_cancelled()
+    }
+}
+
+impl BatchInstallProgress for InstallReporter<'_> {
+    fn file(&self, file: &BatchFile) {
+        self.output.batch_file(file);
+    }
+}
@@ -1,43 +1,299 @@
-use common_utils::app_images_helpers::{app_image_extract_squashroot, choose_icon};
+use common_utils::app_id::derive_app_id;
+use common_utils::app_image_reader::AppImageReader;
+use common_utils::backend_event::{BatchFile, BatchFileStatus, InstallPhase};
+use common_utils::app_images_helpers::{collect_theme_icons, refresh_icon_cache};
+use common_utils::desktop_entry_validator::validate_desktop_entry;
 use common_utils::desktop_file_builder::DesktopFileBuilder;
-use common_utils::desktop_file_helpers::find_desktop_file_location;
-use common_utils::file_system_helpers::{
-    add_executable_permission, find_desktop_file_in_dir, get_file_name,
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::{Error, ErrorKind, Result};
+use common_utils::file_system_helpers::{add_executable_permission, file_sha256, get_file_name};
+use common_utils::install_layout::{
+    app_dir, launcher_file_name, launcher_path, launcher_target, version_dir_name,
+    DEFAULT_KEPT_VERSIONS,
 };
-use log::info;
-use std::fs::File;
-use std::io::Write;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{InstalledVersion, Registry, RegistryEntry};
+use common_utils::signature::{verify_app_image_signature, SignaturePolicy};
+use crate::app_image_uninstaller::remove_app_files;
+use crate::install_transaction::{InstallStep, InstallTransaction};
+use log::{error, info, warn};
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Follows an install: told about each phase, and asked whether to stop.
+/// A cancelled install fails with an error of kind [`ErrorKind::Cancelled`].
+pub trait InstallProgress {
+    fn phase(&self, phase: InstallPhase);
+
+    /// Whether the install should stop. Checked between the phases and while copying,
+    /// until the files are moved into place. A cancelled install is undone.
+    fn is_cancelled(&self) -> bool;
+}
+
+/// Ignores the phases and never cancels
+pub struct NoProgress;
+
+impl InstallProgress for NoProgress {
+    fn phase(&self, _phase: InstallPhase) {}
+
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// How AppImages are installed
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Run the app without its sandbox
+    pub no_sandbox: bool,
+    pub signature_policy: SignaturePolicy,
+    /// The directory of the trusted keys, instead of the default one
+    pub trusted_keys_dir: Option<PathBuf>,
+    pub scope: InstallScope,
+    /// What to do when the app is already installed
+    pub on_duplicate: DuplicatePolicy,
+    /// How many versions of the app are kept, the installed one included
+    pub keep_versions: usize,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        InstallOptions {
+            no_sandbox: false,
+            signature_policy: SignaturePolicy::default(),
+            trusted_keys_dir: None,
+            scope: InstallScope::default(),
+            on_duplicate: DuplicatePolicy::default(),
+            keep_versions: DEFAULT_KEPT_VERSIONS,
+        }
+    }
+}
+
+/// Install an AppImage, returning its registry entry
 pub fn install_app_image(
     file_path: PathBuf,
     installation_dir: PathBuf,
-    no_sandbox: bool,
-) -> Result<(), String> {
+    options: &InstallOptions,
+) -> Result<RegistryEntry> {
+    install_app_image_with_progress(file_path, installation_dir, options, &NoProgress)
+}
+
+/// Install an AppImage, reporting its phases and stopping when it is cancelled
+pub fn install_app_image_with_progress(
+    file_path: PathBuf,
+    installation_dir: PathBuf,
+    options: &InstallOptions,
+    progress: &dyn InstallProgress,
+) -> Result<RegistryEntry> {
+    run_install(
+        file_path,
+        installation_dir,
+        options,
+        InstallHooks {
+            progress,
+            #[cfg(any(test, feature = "test-fixtures"))]
+            fail_at: None,
+            updated_app: None,
+        },
+    )
+}
+
+/// Follows a batch install: told when each AppImage starts and finishes installing,
+/// besides the phases of the running install
+pub trait BatchInstallProgress: InstallProgress {
+    fn file(&self, file: &BatchFile);
+}
+
+/// Install several AppImages with the same options, one after the other. A failed
+/// install is undone and the batch goes on with the next AppImage; once the batch is
+/// cancelled, the AppImages left are not installed. Returns the outcome of each AppImage.
+pub fn install_app_images(
+    file_paths: Vec<PathBuf>,
+    installation_dir: PathBuf,
+    options: &InstallOptions,
+    progress: &impl BatchInstallProgress,
+) -> Vec<BatchFile> {
+    info!("Installing a batch of {} AppImages", file_paths.len());
+
+    let mut files = Vec::with_capacity(file_paths.len());
+    for (index, file_path) in file_paths.into_iter().enumerate() {
+        let status = if progress.is_cancelled() {
+            Err(Error::new(ErrorKind::Cancelled, "Installation cancelled"))
+        } else if !file_path.is_file() {
+            Err(Error::new(
+                ErrorKind::NotFound,
+                format!("AppImage not found: {}", file_path.display()),
+            ))
+        } else {
+            progress.file(&BatchFile {
+                index,
+                file_path: file_path.clone(),
+                status: BatchFileStatus::Installing,
+            });
+            install_app_image_with_progress(
+                file_path.clone(),
+                installation_dir.clone(),
+                options,
+                progress,
+            )
+        };
+
+        let status = match status {
+            Ok(app) => BatchFileStatus::Installed {
+                id: app.id,
+                name: app.name,
+                app_image_path: app.app_image_path,
+            },
+            Err(error) => {
+                error!("Failed to install {:?}: {}", file_path, error);
+                let file_name = file_path.file_name().unwrap_or(file_path.as_os_str());
+                BatchFileStatus::Failed {
+                    error: error.for_app(file_name.to_string_lossy()),
+                }
+            }
+        };
+        let file = BatchFile {
+            index,
+            file_path,
+            status,
+        };
+        progress.file(&file);
+        files.push(file);
+    }
+    files
+}
+
+/// Install an AppImage, making the given step of the install transaction fail.
+/// Used to check that a failed install leaves nothing behind.
+#[cfg(any(test, feature = "test-fixtures"))]
+pub fn install_app_image_with_fail_point(
+    file_path: PathBuf,
+    installation_dir: PathBuf,
+    options: &InstallOptions,
+    fail_at: Option<InstallStep>,
+) -> Result<RegistryEntry> {
+    run_install(
+        file_path,
+        installation_dir,
+        options,
+        InstallHooks {
+            progress: &NoProgress,
+            fail_at,
+            updated_app: None,
+        },
+    )
+}
+
+/// Install the update of an installed app as its new version, with the same checks as
+/// an install. The installed version is replaced even when the update derives another id.
+pub(crate) fn install_update(
+    file_path: PathBuf,
+    installation_dir: PathBuf,
+    options: &InstallOptions,
+    installed: &RegistryEntry,
+) -> Result<RegistryEntry> {
+    let options = InstallOptions {
+        on_duplicate: DuplicatePolicy::Replace,
+        ..options.clone()
+    };
+    run_install(
+        file_path,
+        installation_dir,
+        &options,
+        InstallHooks {
+            progress: &NoProgress,
+            #[cfg(any(test, feature = "test-fixtures"))]
+            fail_at: None,
+            updated_app: Some(&installed.id),
+        },
+    )
+}
+
+/// What follows an install besides its arguments
+struct InstallHooks<'a> {
+    progress: &'a dyn InstallProgress,
+    #[cfg(any(test, feature = "test-fixtures"))]
+    fail_at: Option<InstallStep>,
+    /// The id of the app an update is installed for, instead of the derived one
+    updated_app: Option<&'a str>,
+}
+
+impl InstallHooks<'_> {
+    /// Report the next phase, unless the install was cancelled
+    fn enter(&self, phase: InstallPhase) -> Result<()> {
+        self.check_cancelled()?;
+        self.progress.phase(phase);
+        Ok(())
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.progress.is_cancelled() {
+            info!("Install cancelled");
+            return Err(Error::new(ErrorKind::Cancelled, "Installation cancelled"));
+        }
+        Ok(())
+    }
+}
+
+fn run_install(
+    file_path: PathBuf,
+    installation_dir: PathBuf,
+    options: &InstallOptions,
+    hooks: InstallHooks,
+) -> Result<RegistryEntry> {
+    let scope = options.scope;
     info!("##### REQUESTED TO INSTALL APP ####");
     info!("# File path: {:?}", &file_path);
-    info!("# No sandbox: {:?}", &no_sandbox);
+    info!("# No sandbox: {:?}", &options.no_sandbox);
+    info!("# Signature policy: {}", &options.signature_policy);
+    info!("# Scope: {}", &scope);
+    info!("# On duplicate: {}", &options.on_duplicate);
+    info!("# Kept versions: {}", &options.keep_versions);
     info!("#################################");
 
-    // Add executable permission to the AppImage
-    add_executable_permission(&file_path);
+    let original_file_name = get_file_name(&file_path)?;
 
-    let installation_file_name = get_file_name(&file_path)?;
+    // every file is staged first and moved into place at the end, a failure
+    // at any step undoes the previous ones
+    let mut transaction = InstallTransaction::new(&installation_dir)?;
+    #[cfg(any(test, feature = "test-fixtures"))]
+    if let Some(step) = hooks.fail_at {
+        transaction = transaction.fail_at(step);
+    }
 
-    // extract squashrootfs from AppImage
-    let squashroot_path = app_image_extract_squashroot(&file_path)?;
+    // stage the AppImage, reporting the copied bytes. The source may be writable by
+    // the user, so everything below only reads the staged copy.
+    let staged_app_image = transaction.stage_copy_with_progress(
+        InstallStep::StageAppImage,
+        &file_path,
+        &original_file_name,
+        |done_bytes, total_bytes| {
+            hooks.check_cancelled()?;
+            hooks.progress.phase(InstallPhase::Copying {
+                done_bytes,
+                total_bytes,
+            });
+            Ok(())
+        },
+    )?;
 
-    // check installation dir exist
-    if !installation_dir.exists() {
-        std::fs::create_dir_all(&installation_dir)
-            .map_err(|_| "Failed to create installation directory")?;
-    }
+    // hash the AppImage and verify the embedded signature before anything is placed
+    hooks.enter(InstallPhase::Hashing)?;
+    let sha256 = file_sha256(&staged_app_image)?;
+    let signature_status =
+        verify_app_image_signature(&staged_app_image, options.trusted_keys_dir.as_deref())?;
+    info!("Signature status: {:?}", signature_status);
+    options.signature_policy.enforce(&signature_status)?;
+
+    // read the AppImage payload without executing it
+    hooks.enter(InstallPhase::Inspecting)?;
+    let mut reader = AppImageReader::open(&staged_app_image)?;
 
     // parse AppImage desktop file
-    let desktop_file_path = match find_desktop_file_in_dir(&squashroot_path) {
-        Ok(path) => {
-            info!("Desktop file found at: {:?}", path);
-            path
+    let desktop_file_content = match reader.read_desktop_file() {
+        Ok(content) => {
+            info!("Desktop file found at: {:?}", reader.find_desktop_file());
+            content
         }
         Err(err) => {
             return Err(err);
@@ -45,74 +301,208 @@ pub fn install_app_image(
     };
 
     let mut desktop_builder =
-        match DesktopFileBuilder::from_desktop_entry_path(&desktop_file_path, false) {
+        match DesktopFileBuilder::from_desktop_entry_content(&desktop_file_content, false) {
             Ok(db) => db,
             Err(err) => {
-                return Err(err.to_string());
+                return Err(err);
             }
         };
 
-    // copy icons to icons directory
-    info!("Installing icons...");
-    let icon_path = match choose_icon(&squashroot_path, &installation_dir) {
-        Ok(path) => path,
+    // keep the embedded update information, if any
+    let update_info = match reader.update_info() {
+        Ok(Some(update_info)) => {
+            info!("Update information found: {}", update_info);
+            desktop_builder.set_update_information(update_info.to_string());
+            Some(update_info)
+        }
+        Ok(None) => {
+            info!("No update information embedded in the AppImage");
+            None
+        }
         Err(err) => {
-            return Err(err.to_string());
+            error!("Failed to read update information: {}", err);
+            None
         }
     };
 
-    // set icon path
-    desktop_builder.set_icon(icon_path.to_string_lossy().to_string());
+    let mut app_id = match hooks.updated_app {
+        Some(app_id) => app_id.to_string(),
+        None => derive_app_id(&mut reader)?,
+    };
+    info!("App id: {}", app_id);
+
+    // an app with the same id is refused, replaced, or kept next to the new one
+    let mut registry = Registry::load_or_recover(scope)?;
+    let mut replaced = None;
+    if let Some(installed) = registry.find_by_id(&app_id) {
+        info!("App already installed: {} {:?}", installed.name, installed.version);
+        match options.on_duplicate {
+            DuplicatePolicy::Refuse => {
+                let installed_name = match &installed.version {
+                    Some(version) => format!("{} {}", installed.name, version),
+                    None => installed.name.clone(),
+                };
+                return Err(Error::new(
+                    ErrorKind::AlreadyInstalled,
+                    format!("{} is already installed", installed_name),
+                )
+                .for_app(app_id));
+            }
+            DuplicatePolicy::Replace => replaced = Some(installed.clone()),
+            DuplicatePolicy::KeepBoth => {
+                let version = desktop_builder.app_image_version();
+                app_id = registry.side_by_side_id(&app_id, version.as_deref());
+                // the translated names too, so that both copies can be told apart
+                let suffix = version.unwrap_or_else(|| app_id.clone());
+                desktop_builder.add_name_suffix(&format!(" ({})", suffix));
+                info!("Installing next to the installed app as: {}", app_id);
+            }
+        }
+    }
 
-    // set builder properties
-    desktop_builder.set_exec(format!(
-        "{}{}",
-        installation_dir.to_string_lossy(),
-        installation_file_name
-    ));
+    // the version gets its own directory, the desktop entry runs a link to it
+    let app_image_version = desktop_builder.app_image_version();
+    let app_dir = app_dir(&installation_dir, &app_id);
+    let installation_path = app_dir
+        .join(version_dir_name(app_image_version.as_deref(), &sha256))
+        .join(&original_file_name);
+    let launcher = launcher_path(&installation_dir, &app_id);
 
-    desktop_builder.set_path(installation_dir.to_string_lossy().to_string());
+    add_executable_permission(&staged_app_image);
+    // staged under another name, the AppImage may be named like the launcher
+    let staged_launcher = transaction.stage_symlink(
+        InstallStep::StageAppImage,
+        &format!("{}.launcher", launcher_file_name(&app_id)),
+        &launcher_target(&launcher, &installation_path),
+    )?;
 
-    if no_sandbox {
-        desktop_builder.set_no_sandbox(true);
+    // stage the icons, named after the app id in the hicolor theme of the scope
+    hooks.enter(InstallPhase::Icons)?;
+    info!("Installing icons...");
+    let icon_app_name = desktop_builder.name().unwrap_or_else(|| app_id.clone());
+    let icons = collect_theme_icons(&mut reader, desktop_builder.icon().as_deref(), &icon_app_name);
+    let icon_theme_dir = scope.icon_theme_dir()?;
+    let mut staged_icons = Vec::new();
+    for icon in &icons {
+        let staged_icon = transaction.stage_write(
+            InstallStep::StageIcon,
+            &format!("icon-{}.{}", icon.size_dir, icon.extension),
+            &icon.content,
+        )?;
+        staged_icons.push((staged_icon, icon_theme_dir.join(icon.theme_path(&app_id))));
     }
 
-    // write desktop file to /usr/share/applications
-    let desktop_files_system_location = find_desktop_file_location()?;
+    // the desktop entry uses the themed icon name
+    desktop_builder.set_icon(app_id.clone());
+
+    // point the commands at the installed AppImage, keeping their arguments
+    desktop_builder.target_app_image(&app_dir, &launcher_file_name(&app_id), options.no_sandbox);
+
+    // write desktop file to the applications dir of the scope, named after the app id
+    hooks.enter(InstallPhase::DesktopEntry)?;
+    let applications_dir = scope.applications_dir()?;
     let app_name = match desktop_builder.name() {
         None => {
-            return Err("Failed to get app name".to_string());
+            return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Failed to get app name"));
         }
         Some(name) => name,
     };
-    let desktop_entry_path = format!(
-        "{}/{}.desktop",
-        desktop_files_system_location.to_string_lossy().to_string(),
-        app_name
-    );
-
-    let desktop_file_content = match desktop_builder.generate_content_string() {
-        Ok(content) => content,
-        Err(e) => return Err(e.to_string()),
-    };
+    let desktop_entry_file_name = format!("{}.desktop", app_id);
+    let desktop_entry_path = applications_dir.join(&desktop_entry_file_name);
+
+    let categories = desktop_builder.categories();
+
+    let desktop_file_content = desktop_builder.generate_content_string()?;
 
+    // refuse desktop entries the desktop environments would reject or misread
+    let validation = validate_desktop_entry(&desktop_file_content);
+    for warning in validation.warnings() {
+        warn!("Desktop entry {}", warning);
+    }
+    if validation.has_errors() {
+        let errors: Vec<String> = validation.errors().map(|error| error.to_string()).collect();
+        return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Invalid desktop entry")
+            .caused_by(errors.join("; ")));
+    }
+
+    let staged_desktop_entry = transaction.stage_write(
+        InstallStep::StageDesktopFile,
+        &desktop_entry_file_name,
+        desktop_file_content.as_bytes(),
+    )?;
+
+    // Move the staged files into place
+    hooks.enter(InstallPhase::Committing)?;
+    info!("Installing AppImage to: {:?}", installation_path);
+    transaction.place(InstallStep::CommitAppImage, &staged_app_image, &installation_path)?;
+    transaction.place(InstallStep::CommitAppImage, &staged_launcher, &launcher)?;
+    for (staged_icon, icon_path) in &staged_icons {
+        transaction.place(InstallStep::CommitIcon, staged_icon, icon_path)?;
+    }
     info!("Writing .desktop file to: {:?}", desktop_entry_path);
-    let mut file =
-        File::create(desktop_entry_path).map_err(|e| "Failed to create .desktop file")?;
-    file.write_all(desktop_file_content.as_bytes())
-        .map_err(|_| "Failed to write .desktop file")?;
-
-    // Copy the AppImage to the installation directory
-    let installation_path = format!(
-        "{}/{}",
-        installation_dir.to_string_lossy(),
-        installation_file_name
-    );
-    std::fs::copy(&file_path, &installation_path)
-        .map_err(|_| "Failed to copy AppImage to installation dir")?;
-
-    // remove squashrootfs directory
-    std::fs::remove_dir_all(&squashroot_path).map_err(|_| "Failed to remove squashroot dir")?;
-
-    Ok(())
+    transaction.place(
+        InstallStep::CommitDesktopFile,
+        &staged_desktop_entry,
+        &desktop_entry_path,
+    )?;
+
+    // Record the installed app in the registry, with the versions kept before it
+    let icon_paths: Vec<PathBuf> = staged_icons.into_iter().map(|(_, icon_path)| icon_path).collect();
+    let version = InstalledVersion {
+        version: app_image_version.clone(),
+        original_file_name: original_file_name.clone(),
+        sha256: sha256.clone(),
+        size: std::fs::metadata(&installation_path)
+            .map_err(|e| Error::io("Failed to read AppImage metadata", e))?
+            .len(),
+        installed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        app_image_path: installation_path,
+    };
+    let mut versions = vec![version.clone()];
+    if let Some(replaced) = &replaced {
+        versions.extend(
+            replaced
+                .versions
+                .iter()
+                .filter(|kept| kept.app_image_path != version.app_image_path)
+                .cloned(),
+        );
+    }
+    let mut entry = RegistryEntry {
+        id: app_id,
+        name: app_name,
+        original_file_name,
+        sha256,
+        size: version.size,
+        installed_at: version.installed_at,
+        version: app_image_version,
+        categories,
+        update_info,
+        app_image_path: launcher,
+        icon_paths,
+        desktop_file_path: desktop_entry_path,
+        scope,
+        versions,
+    };
+    let pruned = entry.prune_versions(options.keep_versions);
+    if !pruned.is_empty() {
+        info!("Removing {} older versions", pruned.len());
+    }
+    registry.upsert(entry.clone());
+    transaction.save_registry(&registry)?;
+
+    transaction.commit();
+
+    // the files of the replaced version this one does not reuse, with the pruned versions
+    if let Some(replaced) = &replaced {
+        if let Err(err) = remove_app_files(replaced, Some(&entry)) {
+            error!("Failed to remove the files of the replaced version: {}", err);
+        }
+    }
+    refresh_icon_cache(&icon_theme_dir);
+
+    Ok(entry)
 }
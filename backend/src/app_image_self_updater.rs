@@ -0,0 +1,294 @@
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::error::{Error, ErrorKind, Result};
+use common_utils::exec_command::ExecArg;
+use common_utils::registry::{AppSelector, Registry, RegistryEntry};
+use common_utils::update_info::{read_update_info, UpdateInfo};
+use common_utils::zsync::{file_sha1_hex, ZsyncControl};
+use crate::app_image_installer::{install_update, InstallOptions};
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+/// The largest response read into memory, the zsync control files and the GitHub
+/// release descriptions are far smaller
+const MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The result of a self update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfUpdateResult {
+    /// The installed AppImage already matches the latest release
+    UpToDate,
+    /// The AppImage was replaced by the latest release
+    Updated {
+        app_image_path: PathBuf,
+        /// Bytes copied from the installed AppImage
+        reused_bytes: u64,
+        /// Bytes downloaded from the update server
+        downloaded_bytes: u64,
+    },
+}
+
+/// Update an installed AppImage using the update information embedded in it.
+/// The `.zsync` control file is downloaded first, the blocks already present in the
+/// installed file are reused and only the missing ranges are downloaded.
+/// The update is then installed as the new version of the app with the given options,
+/// checked like any install; a failed update leaves the installed version in place.
+pub fn self_update_app_image(app: AppSelector, options: &InstallOptions) -> Result<SelfUpdateResult> {
+    info!("Self updating AppImage: {} ({})", app, options.scope);
+
+    let installed = Registry::load_or_recover(options.scope)?
+        .find(&app)
+        .cloned()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("App not found: {}", app)))?;
+    let installed_path = installed.app_image_path.clone();
+
+    let update_info = read_update_info(&installed_path)?
+        .ok_or_else(|| Error::other(format!("{} does not provide update information", app)))?;
+    info!("Update information: {}", update_info);
+
+    let zsync_url = resolve_zsync_url(&update_info)?;
+    info!("Downloading zsync control file from: {}", zsync_url);
+    let control = ZsyncControl::parse(&download(zsync_url.as_str())?)?;
+    let file_url = zsync_url
+        .join(&control.urls[0])
+        .map_err(|e| Error::new(ErrorKind::Network, "Invalid AppImage URL in zsync file").caused_by(e))?;
+
+    if control.sha1.as_deref() == Some(file_sha1_hex(&installed_path)?.as_str()) {
+        info!("{} is already up to date", app);
+        return Ok(SelfUpdateResult::UpToDate);
+    }
+
+    // apps installed before the versions were kept have their AppImage
+    // directly in the installation directory
+    let app_dir = installed_path
+        .parent()
+        .ok_or_else(|| Error::other("Failed to get installation directory"))?;
+    let installation_dir = if installed.versions.is_empty() {
+        app_dir
+    } else {
+        app_dir
+            .parent()
+            .ok_or_else(|| Error::other("Failed to get installation directory"))?
+    };
+    let new_file_name = control
+        .filename
+        .as_deref()
+        .and_then(|filename| Path::new(filename).file_name())
+        .unwrap_or(OsStr::new(&installed.original_file_name));
+
+    // the update is written to a directory removed once it is installed, under the
+    // name of the new release
+    let download_dir = tempfile::Builder::new()
+        .prefix(".apphub-update-")
+        .tempdir_in(installation_dir)
+        .map_err(|e| Error::io("Failed to create download directory", e))?;
+    let download_path = download_dir.path().join(new_file_name);
+    let (reused_bytes, downloaded_bytes) =
+        match assemble(&control, &installed_path, file_url.as_str(), &download_path) {
+            Ok(result) => result,
+            Err(err) => {
+                warn!("Delta update failed ({}), downloading the whole AppImage", err);
+                (0, download_to(&control, file_url.as_str(), &download_path)?)
+            }
+        };
+    info!(
+        "Reused {} bytes, downloaded {} bytes",
+        reused_bytes, downloaded_bytes
+    );
+
+    // the app keeps running without its sandbox when it was installed so
+    let options = InstallOptions {
+        no_sandbox: options.no_sandbox || runs_without_sandbox(&installed),
+        ..options.clone()
+    };
+    let updated = install_update(
+        download_path,
+        installation_dir.to_path_buf(),
+        &options,
+        &installed,
+    )?;
+
+    Ok(SelfUpdateResult::Updated {
+        app_image_path: updated.versions[0].app_image_path.clone(),
+        reused_bytes,
+        downloaded_bytes,
+    })
+}
+
+/// Whether the desktop entry of an installed app runs it without its sandbox
+fn runs_without_sandbox(app: &RegistryEntry) -> bool {
+    DesktopFileBuilder::from_desktop_entry_path(&app.desktop_file_path, true)
+        .and_then(|desktop_entry| desktop_entry.exec_command())
+        .is_ok_and(|exec_command| {
+            exec_command
+                .args
+                .contains(&ExecArg::Literal("--no-sandbox".to_string()))
+        })
+}
+
+/// Build the new AppImage at `output_path` from the blocks of the installed one and the
+/// missing ranges. Returns the number of reused and downloaded bytes.
+fn assemble(
+    control: &ZsyncControl,
+    installed_path: &Path,
+    file_url: &str,
+    output_path: &Path,
+) -> Result<(u64, u64)> {
+    let read_error = |e| Error::io("Failed to read installed AppImage", e);
+    let write_error = |e| Error::io("Failed to write AppImage", e);
+    let block_size = control.block_size as u64;
+
+    let mut local = BufReader::new(File::open(installed_path).map_err(read_error)?);
+    let matches = control.match_blocks(&mut local).map_err(read_error)?;
+
+    let mut output = File::create(output_path).map_err(write_error)?;
+    output.set_len(control.length).map_err(write_error)?;
+    let mut reused_bytes = 0;
+    for (index, offset) in matches.iter().enumerate() {
+        if let Some(offset) = offset {
+            let start = index as u64 * block_size;
+            let length = block_size.min(control.length - start);
+            local.seek(SeekFrom::Start(*offset)).map_err(read_error)?;
+            output.seek(SeekFrom::Start(start)).map_err(write_error)?;
+            // past the end of the installed AppImage, the block is zero padded
+            reused_bytes += io::copy(&mut (&mut local).take(length), &mut output).map_err(write_error)?;
+        }
+    }
+
+    let mut downloaded_bytes = 0;
+    for (start, end) in control.missing_ranges(&matches) {
+        output.seek(SeekFrom::Start(start)).map_err(write_error)?;
+        downloaded_bytes += download_range(file_url, start, end, &mut output)?;
+    }
+
+    output.sync_all().map_err(write_error)?;
+    verify(control, output_path)?;
+    Ok((reused_bytes, downloaded_bytes))
+}
+
+/// Download the whole AppImage to `output_path`, returning the number of downloaded bytes
+fn download_to(control: &ZsyncControl, file_url: &str, output_path: &Path) -> Result<u64> {
+    let write_error = |e| Error::io("Failed to write AppImage", e);
+    let response = ureq::get(file_url)
+        .call()
+        .map_err(|e| Error::new(ErrorKind::Network, format!("Failed to download {}", file_url)).caused_by(e))?;
+
+    // a larger file is cut one byte past the expected size, and refused by the check
+    let mut output = File::create(output_path).map_err(write_error)?;
+    let downloaded_bytes = io::copy(
+        &mut response.into_reader().take(control.length + 1),
+        &mut output,
+    )
+    .map_err(|e| Error::new(ErrorKind::Network, "Failed to read response").caused_by(e))?;
+    output.sync_all().map_err(write_error)?;
+
+    verify(control, output_path)?;
+    Ok(downloaded_bytes)
+}
+
+fn verify(control: &ZsyncControl, path: &Path) -> Result<()> {
+    let size = fs::metadata(path)
+        .map_err(|e| Error::io("Failed to read AppImage metadata", e))?
+        .len();
+    if size != control.length {
+        return Err(Error::new(ErrorKind::Network, "The downloaded AppImage has an unexpected size"));
+    }
+    match &control.sha1 {
+        Some(sha1) if *sha1 != file_sha1_hex(path)? => {
+            Err(Error::new(ErrorKind::Network, "The downloaded AppImage checksum does not match"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Find the URL of the `.zsync` control file of the latest release
+fn resolve_zsync_url(update_info: &UpdateInfo) -> Result<Url> {
+    let url = match update_info {
+        UpdateInfo::Zsync { url } => url.clone(),
+        UpdateInfo::GhReleasesZsync {
+            owner,
+            repo,
+            release,
+            filename,
+        } => {
+            let release_path = if release == "latest" {
+                "latest".to_string()
+            } else {
+                format!("tags/{}", release)
+            };
+            let release_url = format!(
+                "{}/repos/{}/{}/releases/{}",
+                GITHUB_API_URL, owner, repo, release_path
+            );
+            let release: serde_json::Value = serde_json::from_slice(&download(&release_url)?)
+                .map_err(|e| Error::new(ErrorKind::Network, "Invalid GitHub release").caused_by(e))?;
+            let pattern = glob::Pattern::new(filename)
+                .map_err(|e| Error::new(ErrorKind::Network, "Invalid zsync file name pattern").caused_by(e))?;
+
+            release["assets"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|asset| {
+                    asset["name"]
+                        .as_str()
+                        .map(|name| pattern.matches(name))
+                        .unwrap_or(false)
+                })
+                .and_then(|asset| asset["browser_download_url"].as_str())
+                .ok_or_else(|| Error::new(ErrorKind::Network, format!("No release asset matches {}", filename)))?
+                .to_string()
+        }
+        UpdateInfo::PlingV1Zsync { .. } => {
+            return Err(Error::other("Updates from Pling are not supported"));
+        }
+    };
+
+    Url::parse(&url).map_err(|e| Error::new(ErrorKind::Network, format!("Invalid zsync URL {}", url)).caused_by(e))
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| Error::new(ErrorKind::Network, format!("Failed to download {}", url)).caused_by(e))?;
+    read_body(response)
+}
+
+/// Download the bytes in [start, end) of the given URL to `output`, returning their number
+fn download_range(url: &str, start: u64, end: u64, output: &mut impl Write) -> Result<u64> {
+    let response = ureq::get(url)
+        .set("Range", &format!("bytes={}-{}", start, end - 1))
+        .call()
+        .map_err(|e| Error::new(ErrorKind::Network, format!("Failed to download {}", url)).caused_by(e))?;
+
+    let is_partial = response.status() == 206;
+    let read_error = |e| Error::new(ErrorKind::Network, "Failed to read response").caused_by(e);
+    let mut body = response.into_reader();
+    if !is_partial {
+        // the server ignored the range and sends the whole file
+        io::copy(&mut (&mut body).take(start), &mut io::sink()).map_err(read_error)?;
+    }
+    let downloaded_bytes = io::copy(&mut body.take(end - start), output).map_err(read_error)?;
+
+    if downloaded_bytes != end - start {
+        return Err(Error::new(ErrorKind::Network, format!("Unexpected range size downloaded from {}", url)));
+    }
+    Ok(downloaded_bytes)
+}
+
+fn read_body(response: ureq::Response) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| Error::new(ErrorKind::Network, "Failed to read response").caused_by(e))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(Error::new(ErrorKind::Network, format!("Response larger than {} bytes", MAX_BODY_SIZE)));
+    }
+    Ok(body)
+}
@@ -1,44 +1,83 @@
-use common_utils::app_images_helpers::remove_icon;
-use common_utils::desktop_file_helpers::{delete_desktop_file_by_name, find_desktop_entry};
+use common_utils::app_images_helpers::{refresh_icon_cache, remove_icon};
+use common_utils::error::{Error, ErrorKind, Result};
 use common_utils::file_system_helpers::rm_file;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, Registry, RegistryEntry};
+use crate::app_image_versions::remove_version_files;
 use log::{error, info};
+use std::fs;
 use std::path::PathBuf;
 
-pub fn uninstall_app_image(app_name: String) -> Result<(), String> {
-    info!("Uninstalling AppImage with app name: {}", app_name);
+pub fn uninstall_app_image(app: AppSelector, scope: InstallScope) -> Result<()> {
+    info!("Uninstalling AppImage: {} ({})", app, scope);
 
-    let desktop_entry = match find_desktop_entry(app_name.clone()) {
-        Ok(path) => path,
-        Err(err) => {
-            return Err(err);
-        }
-    };
+    let mut registry = Registry::load_or_recover(scope)?;
+    let entry = registry
+        .find(&app)
+        .cloned()
+        .ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("App not found: {}", app)).for_app(app.to_string())
+        })?;
+
+    remove_app_files(&entry, None).map_err(|err| err.for_app(entry.name.clone()))?;
+    match scope.icon_theme_dir() {
+        Ok(icon_theme_dir) => refresh_icon_cache(&icon_theme_dir),
+        Err(err) => error!("Failed to find the icon theme: {}", err),
+    }
+
+    // Remove the app from the registry
+    registry.remove(&entry.id);
+    registry.save().map_err(|err| err.for_app(entry.name))?;
 
-    let exec = &desktop_entry
-        .exec
-        .replace("--no-sandbox", "")
-        .trim()
-        .to_string();
+    Ok(())
+}
+
+/// Remove the AppImage with its kept versions, the desktop entry and the icons of an
+/// installed app, except the files `kept` uses too. Failing to remove an icon is only logged.
+pub fn remove_app_files(entry: &RegistryEntry, kept: Option<&RegistryEntry>) -> Result<()> {
+    let is_kept = |path: &PathBuf| {
+        kept.is_some_and(|kept| {
+            kept.app_image_path == *path
+                || kept.versions.iter().any(|version| version.app_image_path == *path)
+                || kept.desktop_file_path == *path
+                || kept.icon_paths.contains(path)
+        })
+    };
 
     // Remove the AppImage
-    if let Err(err) = rm_file(exec) {
-        error!(
-            "Failed to remove app image file at {}: {}",
-            desktop_entry.exec, err
-        );
-        return Err("Failed to remove AppImage".into());
+    if !is_kept(&entry.app_image_path) {
+        if let Err(err) = rm_file(&entry.app_image_path.to_string_lossy().to_string()) {
+            error!(
+                "Failed to remove app image file at {:?}: {}",
+                entry.app_image_path, err
+            );
+            return Err(err.context("Failed to remove AppImage"));
+        }
     }
 
-    // Remove the desktop entry
-    if let Err(err) = delete_desktop_file_by_name(&app_name) {
-        error!("Failed to remove desktop entry: {}", err);
-        return Err("Failed to remove desktop entry".into());
+    // Remove the kept versions, then the directory of the app once it is empty
+    for version in entry.versions.iter().filter(|version| !is_kept(&version.app_image_path)) {
+        remove_version_files(version)?;
+    }
+    if !entry.versions.is_empty() {
+        if let Some(app_dir) = entry.app_image_path.parent() {
+            let _ = fs::remove_dir(app_dir);
+        }
     }
 
-    // Remove icons
-    if let Err(err) = remove_icon(&PathBuf::from(&desktop_entry.icon)) {
-        error!("Failed to remove icons: {}", err);
+    // Remove the desktop entry
+    if !is_kept(&entry.desktop_file_path) {
+        if let Err(err) = fs::remove_file(&entry.desktop_file_path) {
+            error!("Failed to remove desktop entry: {}", err);
+            return Err(Error::io("Failed to remove desktop entry", err));
+        }
     }
 
+    // Remove the icons of every size
+    for icon_path in entry.icon_paths.iter().filter(|icon_path| !is_kept(icon_path)) {
+        if let Err(err) = remove_icon(icon_path) {
+            error!("Failed to remove icons: {}", err);
+        }
+    }
     Ok(())
 }
@@ -1,17 +1,20 @@
 use common_utils::desktop_file_builder::DesktopFileBuilder;
 use common_utils::desktop_file_helpers::find_desktop_entries_by_exec_contains;
+use common_utils::error::{Error, Result};
 use common_utils::file_system_helpers::copy_dir_all;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{Registry, RegistryEntry};
 use log::{error, info, warn};
 use std::fs::{remove_dir_all, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 /// Update the image of an app
 pub fn app_image_update(
     old_install_dir: String,
     new_install_dir: String,
-) -> Result<(), String> {
+) -> Result<()> {
     if old_install_dir.eq(&new_install_dir) {
         return Err("Old and new install directories are the same".into());
     }
@@ -22,20 +25,20 @@ pub fn app_image_update(
     );
 
     if let Err(e) = copy_dir_all(&old_install_dir, &new_install_dir) {
-        return Err(format!("Failed to copy app images: {}", e));
+        return Err(Error::io("Failed to copy app images", e));
     }
 
     info!("App images moved successfully");
 
     if let Err(e) = remove_dir_all(&old_install_dir) {
-        return Err(format!("Failed to delete old install directory: {}", e));
+        return Err(Error::io("Failed to delete old install directory", e));
     }
 
     info!("Old install directory deleted successfully");
 
     let desktop_entries = match find_desktop_entries_by_exec_contains(&old_install_dir) {
         Ok(entries) => entries,
-        Err(e) => return Err(format!("Failed to find desktop entries: {}", e)),
+        Err(e) => return Err(e.context("Failed to find desktop entries")),
     };
 
     for desktop_entry in desktop_entries {
@@ -65,12 +68,9 @@ pub fn app_image_update(
             continue;
         }
 
-        let new_exec = desktop_file_builder
-            .exec()
-            .unwrap()
-            .replace(&old_install_dir, &new_install_dir);
-        info!("New exec: {}", new_exec);
-        desktop_file_builder.set_exec(new_exec);
+        desktop_file_builder
+            .map_programs(|program| program.replace(&old_install_dir, &new_install_dir));
+        info!("New exec: {:?}", desktop_file_builder.exec());
 
         // Update path fields
         if desktop_file_builder.path().is_some() {
@@ -103,6 +103,34 @@ pub fn app_image_update(
         }
     }
 
+    // Point the registry of each scope at the new install dir
+    for scope in InstallScope::ALL {
+        let mut registry = Registry::load_or_recover(scope)?;
+        let moved_apps: Vec<RegistryEntry> = registry
+            .apps()
+            .iter()
+            .filter(|app| app.app_image_path.starts_with(&old_install_dir))
+            .cloned()
+            .collect();
+        if moved_apps.is_empty() {
+            continue;
+        }
+        for mut app in moved_apps {
+            app.app_image_path = move_path(&app.app_image_path, &old_install_dir, &new_install_dir);
+            for version in &mut app.versions {
+                version.app_image_path =
+                    move_path(&version.app_image_path, &old_install_dir, &new_install_dir);
+            }
+            app.icon_paths = app
+                .icon_paths
+                .iter()
+                .map(|icon_path| move_path(icon_path, &old_install_dir, &new_install_dir))
+                .collect();
+            registry.upsert(app);
+        }
+        registry.save()?;
+    }
+
     // Add executable permissions to all app images
     if let Err(e) = Command::new("chmod")
         .arg("-R")
@@ -112,11 +140,19 @@ pub fn app_image_update(
         .stderr(Stdio::piped())
         .spawn()
     {
-        return Err(format!(
-            "Failed to add executable permissions to app images: {}",
-            e
+        return Err(Error::io(
+            "Failed to add executable permissions to app images",
+            e,
         ));
     }
 
     Ok(())
 }
+
+/// Replace the old install dir prefix of a path with the new one
+fn move_path(path: &Path, old_install_dir: &str, new_install_dir: &str) -> PathBuf {
+    match path.strip_prefix(old_install_dir) {
+        Ok(relative_path) => Path::new(new_install_dir).join(relative_path),
+        Err(_) => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,68 @@
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::file_system_helpers::file_sha256;
+use common_utils::models::verify_report::{VerifyIssue, VerifyReport};
+use common_utils::registry::RegistryEntry;
+use log::info;
+
+/// Check that the AppImage, the icons and the desktop entry of an installed app
+/// exist and match its registry entry
+pub fn verify_app_image(app: &RegistryEntry) -> VerifyReport {
+    info!("Verifying {} ({})", app.id, app.scope);
+    let mut issues = Vec::new();
+
+    if app.app_image_path.is_file() {
+        match file_sha256(&app.app_image_path) {
+            Ok(sha256) if sha256 != app.sha256 => issues.push(VerifyIssue::AppImageModified {
+                path: app.app_image_path.clone(),
+                expected_sha256: app.sha256.clone(),
+                actual_sha256: sha256,
+            }),
+            Ok(_) => {}
+            Err(_) => issues.push(VerifyIssue::AppImageMissing {
+                path: app.app_image_path.clone(),
+            }),
+        }
+    } else {
+        issues.push(VerifyIssue::AppImageMissing {
+            path: app.app_image_path.clone(),
+        });
+    }
+
+    for icon_path in &app.icon_paths {
+        if !icon_path.is_file() {
+            issues.push(VerifyIssue::IconMissing {
+                path: icon_path.clone(),
+            });
+        }
+    }
+
+    if !app.desktop_file_path.is_file() {
+        issues.push(VerifyIssue::DesktopEntryMissing {
+            path: app.desktop_file_path.clone(),
+        });
+    } else {
+        match DesktopFileBuilder::from_desktop_entry_path(&app.desktop_file_path, true) {
+            Ok(desktop_entry) => {
+                let expected = app.app_image_path.to_string_lossy().to_string();
+                let program = desktop_entry.exec_command().ok().map(|command| command.program);
+                if program.as_deref() != Some(expected.as_str()) {
+                    issues.push(VerifyIssue::ExecMismatch {
+                        expected,
+                        actual: program,
+                    });
+                }
+            }
+            Err(reason) => issues.push(VerifyIssue::DesktopEntryInvalid {
+                path: app.desktop_file_path.clone(),
+                reason: reason.to_string(),
+            }),
+        }
+    }
+
+    VerifyReport {
+        id: app.id.clone(),
+        name: app.name.clone(),
+        scope: app.scope,
+        issues,
+    }
+}
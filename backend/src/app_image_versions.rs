@@ -0,0 +1,125 @@
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::error::{Error, ErrorKind, Result};
+use common_utils::install_layout::link_launcher;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, InstalledVersion, Registry, RegistryEntry};
+use log::{error, info, warn};
+use std::fs;
+
+/// Make another kept version of an app the current one: the version with the given
+/// number or directory name, or the previous one
+pub fn rollback_app_image(
+    app: AppSelector,
+    scope: InstallScope,
+    to: Option<&str>,
+) -> Result<RegistryEntry> {
+    info!("Rolling back {} ({}) to {}", app, scope, to.unwrap_or("the previous version"));
+
+    let mut registry = Registry::load_or_recover(scope)?;
+    let mut entry = find_app(&registry, &app)?;
+    let index = match to {
+        Some(to) => entry
+            .versions
+            .iter()
+            .position(|version| version.matches(to))
+            .ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, format!("Version {} of {} is not kept", to, entry.name))
+                    .for_app(entry.name.clone())
+            })?,
+        None if entry.versions.len() > 1 => 1,
+        None => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No previous version of {} is kept", entry.name),
+            )
+            .for_app(entry.name.clone()));
+        }
+    };
+    if index == 0 {
+        info!("{} is already the current version", version_label(&entry.versions[0]));
+        return Ok(entry);
+    }
+
+    let previous = entry.versions[0].app_image_path.clone();
+    link_launcher(&entry.app_image_path, &entry.versions[index].app_image_path)?;
+    entry.set_current_version(index);
+    registry.upsert(entry.clone());
+    if let Err(err) = registry.save() {
+        // point the launcher back at the version the registry still records
+        if let Err(err) = link_launcher(&entry.app_image_path, &previous) {
+            error!("Failed to restore the launcher of {}: {}", entry.name, err);
+        }
+        return Err(err.for_app(entry.name));
+    }
+
+    if let Err(err) = update_desktop_entry_version(&entry) {
+        warn!("Failed to update the version in the desktop entry: {}", err);
+    }
+    info!("{} now runs {}", entry.name, version_label(&entry.versions[0]));
+    Ok(entry)
+}
+
+/// Remove the kept versions of an app but the current one and the `keep - 1` most
+/// recently installed others, returning the removed versions
+pub fn prune_app_image(
+    app: AppSelector,
+    scope: InstallScope,
+    keep: usize,
+) -> Result<Vec<InstalledVersion>> {
+    info!("Pruning {} ({}), keeping {} versions", app, scope, keep);
+
+    let mut registry = Registry::load_or_recover(scope)?;
+    let mut entry = find_app(&registry, &app)?;
+    let pruned = entry.prune_versions(keep);
+    if pruned.is_empty() {
+        info!("No version of {} to remove", entry.name);
+        return Ok(pruned);
+    }
+
+    registry.upsert(entry.clone());
+    registry.save().map_err(|err| err.for_app(entry.name.clone()))?;
+    for version in &pruned {
+        remove_version_files(version).map_err(|err| err.for_app(entry.name.clone()))?;
+    }
+    Ok(pruned)
+}
+
+/// Remove the AppImage of a kept version, with its directory once it is empty
+pub fn remove_version_files(version: &InstalledVersion) -> Result<()> {
+    info!("Removing version {}: {:?}", version_label(version), version.app_image_path);
+    match fs::remove_file(&version.app_image_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("{:?} was already removed", version.app_image_path);
+        }
+        Err(e) => return Err(Error::io("Failed to remove AppImage", e)),
+    }
+    if let Some(version_dir) = version.app_image_path.parent() {
+        let _ = fs::remove_dir(version_dir);
+    }
+    Ok(())
+}
+
+fn find_app(registry: &Registry, app: &AppSelector) -> Result<RegistryEntry> {
+    registry.find(app).cloned().ok_or_else(|| {
+        Error::new(ErrorKind::NotFound, format!("App not found: {}", app)).for_app(app.to_string())
+    })
+}
+
+/// The version number of a version, or its directory name when it has none
+fn version_label(version: &InstalledVersion) -> String {
+    version.version.clone().unwrap_or_else(|| version.dir_name())
+}
+
+/// Record the version number of the current version in the desktop entry
+fn update_desktop_entry_version(entry: &RegistryEntry) -> Result<()> {
+    let Some(version) = &entry.version else {
+        return Ok(());
+    };
+    let mut desktop_file_builder =
+        DesktopFileBuilder::from_desktop_entry_path(&entry.desktop_file_path, true)?;
+    desktop_file_builder.set_app_image_version(version.clone());
+    let content = desktop_file_builder.generate_content_string()?;
+    fs::write(&entry.desktop_file_path, content)
+        .map_err(|e| Error::io("Failed to write desktop entry", e))
+}
@@ -0,0 +1,315 @@
+use common_utils::error::{Error, Result};
+use common_utils::registry::Registry;
+use log::{debug, error, info, warn};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// The size of the chunks copied between two progress reports
+const COPY_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The steps of an install, in the order they are performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStep {
+    StageAppImage,
+    StageIcon,
+    StageDesktopFile,
+    CommitAppImage,
+    CommitIcon,
+    CommitDesktopFile,
+    SaveRegistry,
+}
+
+impl InstallStep {
+    #[cfg(any(test, feature = "test-fixtures"))]
+    pub const ALL: [InstallStep; 7] = [
+        InstallStep::StageAppImage,
+        InstallStep::StageIcon,
+        InstallStep::StageDesktopFile,
+        InstallStep::CommitAppImage,
+        InstallStep::CommitIcon,
+        InstallStep::CommitDesktopFile,
+        InstallStep::SaveRegistry,
+    ];
+}
+
+/// A side effect of the transaction, undone on rollback
+#[derive(Debug)]
+enum Change {
+    /// A directory that did not exist before the install
+    CreatedDir(PathBuf),
+    /// A file moved to its destination, with the backup of the file it replaced
+    PlacedFile {
+        destination: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    /// The registry was saved, with the content it had before
+    SavedRegistry {
+        path: PathBuf,
+        previous: Option<Vec<u8>>,
+    },
+}
+
+/// An install that either completes or leaves the system as it was.
+/// Files are first written to a staging directory inside the installation directory,
+/// then moved to their destination with renames. Unless [`InstallTransaction::commit`]
+/// is called, every completed change is undone when the transaction is dropped.
+pub struct InstallTransaction {
+    staging_dir: Option<TempDir>,
+    changes: Vec<Change>,
+    #[cfg(any(test, feature = "test-fixtures"))]
+    fail_at: Option<InstallStep>,
+    committed: bool,
+}
+
+impl InstallTransaction {
+    /// Start a transaction staging its files in the given installation directory
+    pub fn new(installation_dir: &Path) -> Result<Self> {
+        let mut transaction = InstallTransaction {
+            staging_dir: None,
+            changes: Vec::new(),
+            #[cfg(any(test, feature = "test-fixtures"))]
+            fail_at: None,
+            committed: false,
+        };
+        transaction.create_dir_all(installation_dir)?;
+
+        let staging_dir = tempfile::Builder::new()
+            .prefix(".apphub-install-")
+            .tempdir_in(installation_dir)
+            .map_err(|e| Error::io("Failed to create staging directory", e))?;
+        info!("Staging install in {:?}", staging_dir.path());
+        transaction.staging_dir = Some(staging_dir);
+
+        Ok(transaction)
+    }
+
+    /// Make the given step fail, to exercise the rollback
+    #[cfg(any(test, feature = "test-fixtures"))]
+    pub fn fail_at(mut self, step: InstallStep) -> Self {
+        self.fail_at = Some(step);
+        self
+    }
+
+    /// Copy a file into the staging directory
+    pub fn stage_copy(
+        &mut self,
+        step: InstallStep,
+        source: &Path,
+        file_name: &str,
+    ) -> Result<PathBuf> {
+        self.stage_copy_with_progress(step, source, file_name, |_, _| Ok(()))
+    }
+
+    /// Copy a file into the staging directory, calling `on_progress` with the copied and
+    /// total bytes after each chunk. An error returned by `on_progress` stops the copy.
+    pub fn stage_copy_with_progress(
+        &mut self,
+        step: InstallStep,
+        source: &Path,
+        file_name: &str,
+        mut on_progress: impl FnMut(u64, u64) -> Result<()>,
+    ) -> Result<PathBuf> {
+        self.check(step)?;
+        let staged_path = self.staging_path(file_name)?;
+        let copy_error = |e: io::Error| Error::io(format!("Failed to stage {}", file_name), e);
+
+        let mut reader = File::open(source).map_err(copy_error)?;
+        let total_bytes = reader.metadata().map_err(copy_error)?.len();
+        let mut writer = File::create(&staged_path).map_err(copy_error)?;
+        let mut buffer = vec![0; COPY_CHUNK_SIZE];
+        let mut done_bytes = 0;
+        on_progress(done_bytes, total_bytes)?;
+        loop {
+            let read = reader.read(&mut buffer).map_err(copy_error)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).map_err(copy_error)?;
+            done_bytes += read as u64;
+            on_progress(done_bytes, total_bytes)?;
+        }
+        writer.flush().map_err(copy_error)?;
+        Ok(staged_path)
+    }
+
+    /// Write a file into the staging directory
+    pub fn stage_write(
+        &mut self,
+        step: InstallStep,
+        file_name: &str,
+        content: &[u8],
+    ) -> Result<PathBuf> {
+        self.check(step)?;
+        let staged_path = self.staging_path(file_name)?;
+        fs::write(&staged_path, content)
+            .map_err(|e| Error::io(format!("Failed to stage {}", file_name), e))?;
+        Ok(staged_path)
+    }
+
+    /// Create a symbolic link to `target` in the staging directory
+    pub fn stage_symlink(
+        &mut self,
+        step: InstallStep,
+        file_name: &str,
+        target: &Path,
+    ) -> Result<PathBuf> {
+        self.check(step)?;
+        let staged_path = self.staging_path(file_name)?;
+        symlink(target, &staged_path)
+            .map_err(|e| Error::io(format!("Failed to stage {}", file_name), e))?;
+        Ok(staged_path)
+    }
+
+    /// Move a staged file to its destination. A file already there is kept aside
+    /// until the transaction is committed.
+    pub fn place(
+        &mut self,
+        step: InstallStep,
+        staged_path: &Path,
+        destination: &Path,
+    ) -> Result<()> {
+        self.check(step)?;
+        if let Some(parent) = destination.parent() {
+            self.create_dir_all(parent)?;
+        }
+
+        let backup = if destination.symlink_metadata().is_ok() {
+            let backup = sibling_path(destination, ".apphub-backup");
+            fs::rename(destination, &backup)
+                .map_err(|e| Error::io(format!("Failed to back up {:?}", destination), e))?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        if let Err(err) = move_file(staged_path, destination) {
+            if let Some(backup) = &backup {
+                let _ = fs::rename(backup, destination);
+            }
+            return Err(err);
+        }
+
+        info!("Placed {:?}", destination);
+        self.changes.push(Change::PlacedFile {
+            destination: destination.to_path_buf(),
+            backup,
+        });
+        Ok(())
+    }
+
+    /// Save the registry, keeping its previous content for the rollback
+    pub fn save_registry(&mut self, registry: &Registry) -> Result<()> {
+        self.check(InstallStep::SaveRegistry)?;
+        let path = registry.path().to_path_buf();
+        let previous = fs::read(&path).ok();
+        registry.save()?;
+        self.changes.push(Change::SavedRegistry { path, previous });
+        Ok(())
+    }
+
+    /// Keep all the changes and remove the backups and the staging directory
+    pub fn commit(mut self) {
+        for change in &self.changes {
+            if let Change::PlacedFile {
+                backup: Some(backup),
+                ..
+            } = change
+            {
+                if let Err(e) = fs::remove_file(backup) {
+                    warn!("Failed to remove backup {:?}: {}", backup, e);
+                }
+            }
+        }
+        self.committed = true;
+        info!("Install committed");
+    }
+
+    /// Undo the completed changes, the most recent first
+    fn rollback(&mut self) {
+        warn!("Rolling back install");
+        while let Some(change) = self.changes.pop() {
+            let result = match &change {
+                Change::CreatedDir(path) => fs::remove_dir(path),
+                Change::PlacedFile {
+                    destination,
+                    backup,
+                } => fs::remove_file(destination).and_then(|_| match backup {
+                    Some(backup) => fs::rename(backup, destination),
+                    None => Ok(()),
+                }),
+                Change::SavedRegistry { path, previous } => match previous {
+                    Some(previous) => fs::write(path, previous),
+                    None => fs::remove_file(path),
+                },
+            };
+            if let Err(e) = result {
+                error!("Failed to undo {:?}: {}", change, e);
+            }
+        }
+    }
+
+    fn check(&self, step: InstallStep) -> Result<()> {
+        debug!("Install step: {:?}", step);
+        #[cfg(any(test, feature = "test-fixtures"))]
+        if self.fail_at == Some(step) {
+            return Err(Error::other(format!("Injected failure at {:?}", step)));
+        }
+        Ok(())
+    }
+
+    fn staging_path(&self, file_name: &str) -> Result<PathBuf> {
+        let staging_dir = self
+            .staging_dir
+            .as_ref()
+            .ok_or_else(|| Error::other("Missing staging directory"))?;
+        Ok(staging_dir.path().join(file_name))
+    }
+
+    /// Create a directory and its missing parents, recording each of them
+    fn create_dir_all(&mut self, path: &Path) -> Result<()> {
+        let missing: Vec<&Path> = path.ancestors().take_while(|dir| !dir.exists()).collect();
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(dir).map_err(|e| Error::io(format!("Failed to create {:?}", dir), e))?;
+            self.changes.push(Change::CreatedDir(dir.to_path_buf()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for InstallTransaction {
+    fn drop(&mut self) {
+        // the staging directory is removed before the directories created for it
+        drop(self.staging_dir.take());
+        if !self.committed {
+            self.rollback();
+        }
+    }
+}
+
+/// Move a file with a rename. Across file systems the file is copied next to the
+/// destination first, so that the destination still appears atomically.
+fn move_file(source: &Path, destination: &Path) -> Result<()> {
+    if fs::rename(source, destination).is_ok() {
+        return Ok(());
+    }
+
+    let temp_path = sibling_path(destination, ".apphub-tmp");
+    fs::copy(source, &temp_path)
+        .and_then(|_| fs::rename(&temp_path, destination))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            Error::io(format!("Failed to move {:?} to {:?}", source, destination), e)
+        })?;
+    let _ = fs::remove_file(source);
+    Ok(())
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
@@ -1,3 +1,7 @@
 pub mod app_image_updater;
 pub mod app_image_installer;
-pub mod app_image_uninstaller;
\ No newline at end of file
+pub mod app_image_self_updater;
+pub mod app_image_uninstaller;
+pub mod install_transaction;
+pub mod app_image_verifier;
+pub mod app_image_versions;
@@ -0,0 +1,102 @@
+mod common;
+
+use backend::install_transaction::InstallStep;
+use common_utils::error::Error;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common::TestEnv;
+use common_utils::registry::{Registry, RegistryEntry};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The test AppImage, to install to a new test directory
+fn setup() -> (TestEnv, PathBuf) {
+    let env = common::setup();
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    (env, source)
+}
+
+fn install(
+    env: &TestEnv,
+    source: &Path,
+    fail_at: Option<InstallStep>,
+) -> Result<RegistryEntry, Error> {
+    env.install_failing_at(source, &common::install_options(DuplicatePolicy::Replace), fail_at)
+}
+
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut file_names: Vec<_> = dir_entries(dir)
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    file_names.sort();
+    file_names
+}
+
+// the environment is shared by the whole process, so the steps run in a single test
+#[test]
+fn test_install_rolls_back_on_failure() {
+    for step in InstallStep::ALL {
+        let (env, source) = setup();
+        let icon_theme_dir = env.path().join("icons/hicolor");
+
+        let result = install(&env, &source, Some(step));
+
+        assert!(result.is_err(), "the install should fail at {:?}", step);
+        assert!(
+            !env.installation_dir.exists(),
+            "{:?} left {:?}",
+            step,
+            dir_entries(&env.installation_dir)
+        );
+        assert!(dir_entries(&env.applications_dir).is_empty(), "{:?}", step);
+        assert!(!icon_theme_dir.exists(), "{:?}", step);
+        assert!(!env.registry_path.exists(), "{:?}", step);
+        assert!(source.exists());
+    }
+
+    let (env, source) = setup();
+    let icon_theme_dir = env.path().join("icons/hicolor");
+    install(&env, &source, None).unwrap();
+
+    let installed_path = env.installation_dir.join("test/test.AppImage");
+    assert_eq!(fs::read(&installed_path).unwrap(), fs::read(&source).unwrap());
+    let registry = Registry::load_from(&env.registry_path).unwrap();
+    let app = registry.find_by_id("test").unwrap();
+    assert_eq!(app.app_image_path, installed_path);
+    assert_eq!(app.desktop_file_path, env.applications_dir.join("test.desktop"));
+    // the 64x64 icon of the AppImage is resized to the other standard sizes
+    let icon_paths: Vec<_> = ["256x256", "128x128", "64x64", "32x32"]
+        .iter()
+        .map(|size| icon_theme_dir.join(size).join("apps/test.png"))
+        .collect();
+    assert_eq!(app.icon_paths, icon_paths);
+    assert!(app.icon_paths.iter().all(|icon_path| icon_path.exists()));
+
+    // a failed reinstall keeps the previous install
+    let registry_content = fs::read(&env.registry_path).unwrap();
+    let desktop_file_path = env.applications_dir.join("test.desktop");
+    let desktop_file_content = fs::read(&desktop_file_path).unwrap();
+
+    for step in InstallStep::ALL {
+        assert!(install(&env, &source, Some(step)).is_err());
+
+        assert_eq!(fs::read(&env.registry_path).unwrap(), registry_content);
+        assert_eq!(fs::read(&desktop_file_path).unwrap(), desktop_file_content);
+        assert_eq!(file_names(&env.installation_dir), ["test"], "{:?}", step);
+        assert_eq!(
+            file_names(&env.installation_dir.join("test")),
+            [app.versions[0].dir_name(), "test.AppImage".to_string()],
+            "{:?}",
+            step
+        );
+        assert_eq!(fs::read(&installed_path).unwrap(), fs::read(&source).unwrap());
+        assert!(app.icon_paths.iter().all(|icon_path| icon_path.exists()), "{:?}", step);
+    }
+}
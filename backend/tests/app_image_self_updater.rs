@@ -0,0 +1,202 @@
+mod common;
+
+use backend::app_image_installer::InstallOptions;
+use backend::app_image_self_updater::{self_update_app_image, SelfUpdateResult};
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::ErrorKind;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, Registry};
+use common_utils::signature::SignaturePolicy;
+use common_utils::test_fixtures::{pseudo_random_data, zsync_control_file, AppImageFixture};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{fs, thread};
+use tiny_http::{Header, Response, Server};
+
+/// A local HTTP server serving files with support for range requests
+struct UpdateServer {
+    port: u16,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    served_bytes: Arc<AtomicU64>,
+}
+
+impl UpdateServer {
+    fn start() -> Self {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        let files: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+        let served_bytes = Arc::new(AtomicU64::new(0));
+
+        let (thread_files, thread_served_bytes) = (files.clone(), served_bytes.clone());
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let file = thread_files.lock().unwrap().get(request.url()).cloned();
+                let Some(file) = file else {
+                    let _ = request.respond(Response::empty(404));
+                    continue;
+                };
+
+                let range = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Range"))
+                    .and_then(|header| {
+                        let (start, end) = header.value.as_str().strip_prefix("bytes=")?.split_once('-')?;
+                        Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+                    });
+
+                let response = match range {
+                    Some((start, end)) => {
+                        let body = file[start..=end].to_vec();
+                        let content_range = format!("bytes {}-{}/{}", start, end, file.len());
+                        thread_served_bytes.fetch_add(body.len() as u64, Ordering::SeqCst);
+                        Response::from_data(body)
+                            .with_status_code(206)
+                            .with_header(Header::from_bytes("Content-Range", content_range).unwrap())
+                    }
+                    None => {
+                        thread_served_bytes.fetch_add(file.len() as u64, Ordering::SeqCst);
+                        Response::from_data(file)
+                    }
+                };
+                let _ = request.respond(response);
+            }
+        });
+
+        UpdateServer {
+            port,
+            files,
+            served_bytes,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    fn serve(&self, path: &str, content: Vec<u8>) {
+        self.files.lock().unwrap().insert(path.to_string(), content);
+    }
+}
+
+fn app_image(update_url: &str, data: &[u8]) -> Vec<u8> {
+    let mut update_info = format!("zsync|{}", update_url).into_bytes();
+    update_info.resize(1024, 0);
+
+    let mut fixture = AppImageFixture::with_app("test");
+    fixture
+        .file("usr/lib/data.bin", data)
+        .section(".upd_info", &update_info);
+    fixture.build()
+}
+
+fn write_desktop_entry(applications_dir: &Path, exec: &Path) {
+    let mut builder = DesktopFileBuilder::new();
+    builder
+        .set_type("Application".to_string())
+        .set_name("test".to_string())
+        .set_exec(exec.to_string_lossy().to_string())
+        .set_icon("test".to_string());
+    fs::write(
+        applications_dir.join("test.desktop"),
+        builder.generate_content_string().unwrap(),
+    )
+    .unwrap();
+}
+
+// the environment is shared by the whole process, so the updates run in a single test
+#[test]
+fn test_self_update_downloads_only_changed_ranges() {
+    let env = common::setup();
+    let (applications_dir, installation_dir) = (&env.applications_dir, &env.installation_dir);
+    fs::create_dir_all(installation_dir).unwrap();
+
+    let server = UpdateServer::start();
+    let zsync_url = server.url("/test.AppImage.zsync");
+    let release = |data: &[u8], file_name: &str| {
+        let app_image = app_image(&zsync_url, data);
+        server.serve(
+            "/test.AppImage.zsync",
+            zsync_control_file(&app_image, 2048, file_name, file_name),
+        );
+        server.serve(&format!("/{}", file_name), app_image.clone());
+        app_image
+    };
+    let update = |signature_policy| {
+        let options = InstallOptions {
+            signature_policy,
+            ..common::install_options(DuplicatePolicy::Replace)
+        };
+        self_update_app_image(AppSelector::Id("test".to_string()), &options)
+    };
+
+    // the new release changes a small part of the payload
+    let old_data = pseudo_random_data(300_000, 1);
+    let mut new_data = old_data.clone();
+    new_data[150_000..151_000].copy_from_slice(&pseudo_random_data(1000, 2));
+
+    // installed before the versions were kept
+    let installed_path = installation_dir.join("test-1.0.AppImage");
+    fs::write(&installed_path, app_image(&zsync_url, &old_data)).unwrap();
+    write_desktop_entry(applications_dir, &installed_path);
+
+    let new_app_image = release(&new_data, "test-2.0.AppImage");
+    let result = update(SignaturePolicy::Allow).unwrap();
+
+    let launcher = installation_dir.join("test/test.AppImage");
+    let new_path = match result {
+        SelfUpdateResult::Updated {
+            app_image_path,
+            reused_bytes,
+            downloaded_bytes,
+        } => {
+            assert_eq!(reused_bytes + downloaded_bytes, new_app_image.len() as u64);
+            assert!(downloaded_bytes < new_app_image.len() as u64 / 2);
+            app_image_path
+        }
+        SelfUpdateResult::UpToDate => panic!("the AppImage should have been updated"),
+    };
+    assert_eq!(new_path.file_name().unwrap(), "test-2.0.AppImage");
+    assert_eq!(fs::read(&new_path).unwrap(), new_app_image);
+    assert_eq!(fs::read(&launcher).unwrap(), new_app_image);
+    assert!(!installed_path.exists());
+    assert!(server.served_bytes.load(Ordering::SeqCst) < new_app_image.len() as u64 / 2);
+
+    let desktop_entry =
+        DesktopFileBuilder::from_desktop_entry_path(&applications_dir.join("test.desktop"), true)
+            .unwrap();
+    assert_eq!(desktop_entry.exec(), Some(launcher.to_string_lossy().to_string()));
+    assert_eq!(
+        desktop_entry.update_information(),
+        Some(format!("zsync|{}", zsync_url))
+    );
+
+    let registry = Registry::load(InstallScope::System).unwrap();
+    let app = registry.find_by_id("test").unwrap();
+    assert_eq!(app.app_image_path, launcher);
+    assert_eq!(app.size, new_app_image.len() as u64);
+
+    // a second run finds nothing to update
+    assert_eq!(update(SignaturePolicy::Allow).unwrap(), SelfUpdateResult::UpToDate);
+
+    // an update is checked like an install, a refused one leaves the installed version
+    let mut newer_data = new_data.clone();
+    newer_data[200_000..201_000].copy_from_slice(&pseudo_random_data(1000, 3));
+    let newer_app_image = release(&newer_data, "test-3.0.AppImage");
+    let error = update(SignaturePolicy::Refuse).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Signature);
+    assert_eq!(fs::read(&launcher).unwrap(), new_app_image);
+    assert_eq!(Registry::load(InstallScope::System).unwrap().find_by_id("test"), Some(app));
+    assert_eq!(fs::read_dir(installation_dir).unwrap().count(), 1);
+
+    // the updated version is kept to roll back to it
+    update(SignaturePolicy::Allow).unwrap();
+    assert_eq!(fs::read(&launcher).unwrap(), newer_app_image);
+    let registry = Registry::load(InstallScope::System).unwrap();
+    let versions = &registry.find_by_id("test").unwrap().versions;
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].app_image_path, new_path);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use backend::app_image_verifier::verify_app_image;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::models::verify_report::VerifyIssue;
+use std::fs;
+
+#[test]
+fn test_verify_installed_app() {
+    let env = common::setup();
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    let app = env
+        .install(&source, &common::install_options(DuplicatePolicy::Refuse))
+        .unwrap();
+
+    assert!(verify_app_image(&app).is_ok());
+
+    fs::write(&app.app_image_path, b"tampered").unwrap();
+    fs::remove_file(&app.icon_paths[0]).unwrap();
+    let desktop_entry = fs::read_to_string(&app.desktop_file_path).unwrap();
+    fs::write(
+        &app.desktop_file_path,
+        desktop_entry.replace(&app.app_image_path.to_string_lossy().to_string(), "/usr/bin/other"),
+    )
+    .unwrap();
+
+    let report = verify_app_image(&app);
+    assert_eq!(report.issues.len(), 3);
+    assert!(matches!(report.issues[0], VerifyIssue::AppImageModified { .. }));
+    assert_eq!(
+        report.issues[1],
+        VerifyIssue::IconMissing {
+            path: app.icon_paths[0].clone()
+        }
+    );
+    assert_eq!(
+        report.issues[2],
+        VerifyIssue::ExecMismatch {
+            expected: app.app_image_path.to_string_lossy().to_string(),
+            actual: Some("/usr/bin/other".to_string()),
+        }
+    );
+
+    fs::remove_file(&app.app_image_path).unwrap();
+    fs::remove_file(&app.desktop_file_path).unwrap();
+    let report = verify_app_image(&app);
+    assert!(report.issues.contains(&VerifyIssue::AppImageMissing {
+        path: app.app_image_path.clone()
+    }));
+    assert!(report.issues.contains(&VerifyIssue::DesktopEntryMissing {
+        path: app.desktop_file_path.clone()
+    }));
+}
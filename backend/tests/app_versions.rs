@@ -0,0 +1,92 @@
+mod common;
+
+use backend::app_image_installer::InstallOptions;
+use backend::app_image_uninstaller::uninstall_app_image;
+use backend::app_image_versions::{prune_app_image, rollback_app_image};
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::ErrorKind;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, Registry, RegistryEntry};
+use std::fs;
+
+fn versions(app: &RegistryEntry) -> Vec<&str> {
+    app.versions
+        .iter()
+        .map(|version| version.version.as_deref().unwrap_or_default())
+        .collect()
+}
+
+fn desktop_entry_version(app: &RegistryEntry) -> Option<String> {
+    DesktopFileBuilder::from_desktop_entry_path(&app.desktop_file_path, true)
+        .unwrap()
+        .app_image_version()
+}
+
+// the environment is shared by the whole process, so the actions run in a single test
+#[test]
+fn test_keep_roll_back_and_prune_versions() {
+    let env = common::setup();
+    let installation_dir = &env.installation_dir;
+    let app = || AppSelector::Id("test".to_string());
+
+    // the versions beyond the three most recent ones are removed
+    let mut installed = None;
+    for version in ["1.0", "2.0", "3.0", "4.0"] {
+        let source = env.write_version(&format!("Test-{}-x86_64.AppImage", version), version);
+        let options = InstallOptions {
+            keep_versions: 3,
+            ..common::install_options(DuplicatePolicy::Replace)
+        };
+        installed = Some(env.install(&source, &options).unwrap());
+        let version_path = installation_dir
+            .join("test")
+            .join(version)
+            .join(source.file_name().unwrap());
+        assert_eq!(fs::read(&version_path).unwrap(), fs::read(&source).unwrap());
+    }
+    let installed = installed.unwrap();
+    let launcher = installation_dir.join("test/test.AppImage");
+    assert_eq!(installed.app_image_path, launcher);
+    assert_eq!(versions(&installed), ["4.0", "3.0", "2.0"]);
+    assert!(!installation_dir.join("test/1.0").exists());
+    assert_eq!(fs::read(&launcher).unwrap(), fs::read(&installed.versions[0].app_image_path).unwrap());
+
+    // the previous version by default, or the given one
+    let rolled_back = rollback_app_image(app(), InstallScope::System, None).unwrap();
+    assert_eq!(rolled_back.version.as_deref(), Some("3.0"));
+    assert_eq!(versions(&rolled_back), ["3.0", "4.0", "2.0"]);
+    assert_eq!(fs::read(&launcher).unwrap(), fs::read(&rolled_back.versions[0].app_image_path).unwrap());
+    assert_eq!(desktop_entry_version(&rolled_back).as_deref(), Some("3.0"));
+
+    let rolled_back = rollback_app_image(app(), InstallScope::System, Some("2.0")).unwrap();
+    assert_eq!(rolled_back.sha256, rolled_back.versions[0].sha256);
+    assert_eq!(Registry::load(InstallScope::System).unwrap().find_by_id("test"), Some(&rolled_back));
+    let error = rollback_app_image(app(), InstallScope::System, Some("1.0")).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::NotFound);
+
+    // the registry is recovered with the kept versions
+    let recovered = Registry::rebuild_from_desktop_files(
+        &env.registry_path,
+        &env.applications_dir,
+        InstallScope::System,
+    )
+    .unwrap();
+    let recovered = recovered.find_by_id("test").unwrap();
+    assert_eq!(recovered.version.as_deref(), Some("2.0"));
+    assert_eq!(recovered.versions.len(), 3);
+
+    // pruning keeps the current version and the most recently installed others
+    let pruned = prune_app_image(app(), InstallScope::System, 2).unwrap();
+    assert_eq!(pruned.len(), 1);
+    assert!(!pruned[0].app_image_path.exists());
+    assert!(!pruned[0].app_image_path.parent().unwrap().exists());
+    let registry = Registry::load(InstallScope::System).unwrap();
+    let pruned_app = registry.find_by_id("test").unwrap();
+    assert_eq!(pruned_app.versions.len(), 2);
+    assert_eq!(pruned_app.version.as_deref(), Some("2.0"));
+    assert!(pruned_app.versions.iter().all(|version| version.app_image_path.exists()));
+
+    uninstall_app_image(app(), InstallScope::System).unwrap();
+    assert!(!installation_dir.join("test").exists());
+}
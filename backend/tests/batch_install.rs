@@ -0,0 +1,136 @@
+mod common;
+
+use backend::app_image_installer::{
+    install_app_images, BatchInstallProgress, InstallProgress,
+};
+use common_utils::backend_event::{BatchFile, BatchFileStatus, InstallPhase};
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::ErrorKind;
+use common_utils::registry::Registry;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// Records the reported AppImages, and cancels the batch once the given number of
+/// AppImages is installed
+struct RecordingProgress {
+    files: RefCell<Vec<BatchFile>>,
+    cancel_after: Option<usize>,
+}
+
+impl RecordingProgress {
+    fn new(cancel_after: Option<usize>) -> Self {
+        RecordingProgress {
+            files: RefCell::new(Vec::new()),
+            cancel_after,
+        }
+    }
+}
+
+impl InstallProgress for RecordingProgress {
+    fn phase(&self, _phase: InstallPhase) {}
+
+    fn is_cancelled(&self) -> bool {
+        let installed = self
+            .files
+            .borrow()
+            .iter()
+            .filter(|file| matches!(file.status, BatchFileStatus::Installed { .. }))
+            .count();
+        self.cancel_after
+            .is_some_and(|cancel_after| installed >= cancel_after)
+    }
+}
+
+impl BatchInstallProgress for RecordingProgress {
+    fn file(&self, file: &BatchFile) {
+        self.files.borrow_mut().push(file.clone());
+    }
+}
+
+fn install(
+    file_paths: &[PathBuf],
+    installation_dir: &Path,
+    progress: &RecordingProgress,
+) -> Vec<BatchFile> {
+    install_app_images(
+        file_paths.to_vec(),
+        installation_dir.to_path_buf(),
+        &common::install_options(DuplicatePolicy::Refuse),
+        progress,
+    )
+}
+
+fn failure_kind(file: &BatchFile) -> Option<ErrorKind> {
+    match &file.status {
+        BatchFileStatus::Failed { error } => Some(error.kind),
+        _ => None,
+    }
+}
+
+// the environment is shared by the whole process, so both batches run in a single test
+#[test]
+fn test_batch_install_goes_on_past_failures() {
+    let env = common::setup();
+    let (registry_path, installation_dir) = (&env.registry_path, &env.installation_dir);
+    let app_image = |name: &str| env.write_app_image(&format!("{}.AppImage", name), name);
+    let one = app_image("one");
+    let two = app_image("two");
+    let file_paths = [
+        one.clone(),
+        env.path().join("missing.AppImage"),
+        // refused, "one" was installed by the batch
+        one.clone(),
+        two,
+    ];
+
+    let progress = RecordingProgress::new(None);
+    let files = install(&file_paths, installation_dir, &progress);
+
+    assert_eq!(files.len(), 4);
+    assert!(files
+        .iter()
+        .enumerate()
+        .all(|(index, file)| file.index == index));
+    assert!(matches!(&files[0].status, BatchFileStatus::Installed { id, .. } if id == "one"));
+    assert_eq!(failure_kind(&files[1]), Some(ErrorKind::NotFound));
+    assert_eq!(failure_kind(&files[2]), Some(ErrorKind::AlreadyInstalled));
+    assert!(matches!(&files[3].status, BatchFileStatus::Installed { id, .. } if id == "two"));
+    // each install is reported when it starts, the missing file never starts
+    let reported: Vec<_> = progress
+        .files
+        .borrow()
+        .iter()
+        .map(|file| {
+            (
+                file.index,
+                matches!(file.status, BatchFileStatus::Installing),
+            )
+        })
+        .collect();
+    assert_eq!(
+        reported,
+        [
+            (0, true),
+            (0, false),
+            (1, false),
+            (2, true),
+            (2, false),
+            (3, true),
+            (3, false)
+        ]
+    );
+    let registry = Registry::load_from(registry_path).unwrap();
+    assert_eq!(registry.apps().len(), 2);
+
+    // the AppImages left once the batch is cancelled are not installed
+    let three = app_image("three");
+    let four = app_image("four");
+    let progress = RecordingProgress::new(Some(1));
+    let files = install(&[three, four], installation_dir, &progress);
+
+    assert!(matches!(files[0].status, BatchFileStatus::Installed { .. }));
+    assert_eq!(failure_kind(&files[1]), Some(ErrorKind::Cancelled));
+    let registry = Registry::load_from(registry_path).unwrap();
+    assert!(registry.find_by_id("three").is_some());
+    assert!(registry.find_by_id("four").is_none());
+}
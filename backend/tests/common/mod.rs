@@ -0,0 +1,106 @@
+//! The setup shared by the integration tests. The environment is shared by the whole
+//! process, so each test file runs a single test.
+#![allow(dead_code)]
+
+use backend::app_image_installer::{
+    install_app_image, install_app_image_with_fail_point, InstallOptions,
+};
+use backend::install_transaction::InstallStep;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::Result;
+use common_utils::registry::{RegistryEntry, REGISTRY_PATH_ENV};
+use common_utils::signature::SignaturePolicy;
+use common_utils::test_fixtures::AppImageFixture;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// A temporary directory holding the installed apps of both scopes
+pub struct TestEnv {
+    pub dir: tempfile::TempDir,
+    pub applications_dir: PathBuf,
+    pub installation_dir: PathBuf,
+    pub registry_path: PathBuf,
+    /// The XDG data directory, where the apps of the user scope are installed
+    pub data_dir: PathBuf,
+}
+
+/// Point the system and user scopes at a new temporary directory
+pub fn setup() -> TestEnv {
+    let dir = tempfile::tempdir().unwrap();
+    let applications_dir = dir.path().join("applications");
+    fs::create_dir_all(&applications_dir).unwrap();
+    let registry_path = dir.path().join("registry.json");
+    let data_dir = dir.path().join("data");
+    env::set_var("KDE_INSTALL_APPDIR", &applications_dir);
+    env::set_var(REGISTRY_PATH_ENV, &registry_path);
+    env::set_var("XDG_DATA_HOME", &data_dir);
+
+    TestEnv {
+        installation_dir: dir.path().join("AppImages"),
+        applications_dir,
+        registry_path,
+        data_dir,
+        dir,
+    }
+}
+
+/// The options of the test installs, which allow the unsigned fixtures
+pub fn install_options(on_duplicate: DuplicatePolicy) -> InstallOptions {
+    InstallOptions {
+        signature_policy: SignaturePolicy::Allow,
+        on_duplicate,
+        ..InstallOptions::default()
+    }
+}
+
+impl TestEnv {
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Write an AppImage of the given app to the test directory
+    pub fn write_app_image(&self, file_name: &str, app: &str) -> PathBuf {
+        let path = self.path().join(file_name);
+        AppImageFixture::with_app(app).write_to(&path);
+        path
+    }
+
+    /// Write the given version of the "test" app to the test directory. The versions
+    /// differ in their content, and their names are translated.
+    pub fn write_version(&self, file_name: &str, version: &str) -> PathBuf {
+        let path = self.path().join(file_name);
+        let mut fixture = AppImageFixture::with_app("test");
+        fixture
+            .file(
+                "test.desktop",
+                format!(
+                    "[Desktop Entry]\nType=Application\nName=Test\nName[fr]=Essai\nExec=AppRun\nIcon=test\nCategories=Utility;\nX-AppImage-Version={}\n",
+                    version
+                )
+                .as_bytes(),
+            )
+            .file("usr/share/version", version.as_bytes());
+        fixture.write_to(&path);
+        path
+    }
+
+    /// Install an AppImage to the installation directory
+    pub fn install(&self, source: &Path, options: &InstallOptions) -> Result<RegistryEntry> {
+        install_app_image(source.to_path_buf(), self.installation_dir.clone(), options)
+    }
+
+    /// Install an AppImage, making the given step of the install transaction fail
+    pub fn install_failing_at(
+        &self,
+        source: &Path,
+        options: &InstallOptions,
+        fail_at: Option<InstallStep>,
+    ) -> Result<RegistryEntry> {
+        install_app_image_with_fail_point(
+            source.to_path_buf(),
+            self.installation_dir.clone(),
+            options,
+            fail_at,
+        )
+    }
+}
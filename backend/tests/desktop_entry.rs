@@ -0,0 +1,117 @@
+mod common;
+
+use backend::app_image_installer::InstallOptions;
+use common_utils::desktop_file_builder::DesktopFileBuilder;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::exec_command::ExecArg;
+use common_utils::test_fixtures::{png_icon, AppImageFixture};
+use std::fs;
+
+const DESKTOP_ENTRY: &str = "[Desktop Entry]
+Type=Application
+Name=Test
+Name[de]=Test DE
+GenericName=Text Editor
+Keywords=text;editor;
+MimeType=text/plain;text/markdown;
+StartupWMClass=test-app
+TryExec=test
+Exec=test %F
+Icon=test
+Categories=Utility;
+Actions=new-window;
+
+[Desktop Action new-window]
+Name=New Window
+Name[de]=Neues Fenster
+Exec=test --new-window
+";
+
+const SVG_ICON: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"16\" height=\"16\"><rect width=\"16\" height=\"16\" fill=\"#3584e4\"/></svg>";
+
+#[test]
+fn test_install_validates_and_keeps_the_embedded_desktop_entry() {
+    let mut env = common::setup();
+
+    // an invalid desktop entry is refused before anything is installed
+    let source = env.path().join("Test-x86_64.AppImage");
+    let mut fixture = AppImageFixture::with_app("test");
+    fixture.file("test.desktop", DESKTOP_ENTRY.replace("%F", "%F %x").as_bytes());
+    fixture.write_to(&source);
+    let error = env
+        .install(&source, &common::install_options(DuplicatePolicy::Refuse))
+        .unwrap_err();
+    assert!(error.to_string().contains("Invalid field code %x"), "{}", error);
+    assert_eq!(fs::read_dir(&env.applications_dir).unwrap().count(), 0);
+
+    let mut fixture = AppImageFixture::with_app("test");
+    fixture
+        .file("test.desktop", DESKTOP_ENTRY.as_bytes())
+        .file("usr/share/icons/hicolor/256x256/apps/test.png", &png_icon(256))
+        .file("usr/share/icons/hicolor/scalable/apps/test.svg", SVG_ICON.as_bytes())
+        .file("usr/share/screenshot.png", &png_icon(1024));
+    fixture.write_to(&source);
+
+    // an install dir with spaces needs the Exec commands to be quoted
+    env.installation_dir = env.path().join("My AppImages");
+    let options = InstallOptions {
+        no_sandbox: true,
+        ..common::install_options(DuplicatePolicy::Refuse)
+    };
+    let app = env.install(&source, &options).unwrap();
+
+    // the shipped sizes are kept, the missing standard sizes are rendered from the SVG icon,
+    // and the desktop entry uses the themed name
+    let icon_theme_dir = env.path().join("icons/hicolor");
+    assert_eq!(
+        app.icon_paths,
+        vec![
+            icon_theme_dir.join("256x256/apps/test.png"),
+            icon_theme_dir.join("128x128/apps/test.png"),
+            icon_theme_dir.join("64x64/apps/test.png"),
+            icon_theme_dir.join("32x32/apps/test.png"),
+            icon_theme_dir.join("scalable/apps/test.svg"),
+        ]
+    );
+    assert!(app.icon_paths.iter().all(|icon_path| icon_path.exists()));
+    assert_eq!(fs::read(&app.icon_paths[0]).unwrap(), png_icon(256));
+    let rendered = image::open(&app.icon_paths[1]).unwrap();
+    assert_eq!((rendered.width(), rendered.height()), (128, 128));
+
+    let installed = fs::read_to_string(&app.desktop_file_path).unwrap();
+    let app_image_path = app.app_image_path.to_string_lossy().to_string();
+    let desktop_entry = DesktopFileBuilder::from_desktop_entry_content(&installed, true).unwrap();
+
+    let exec_command = desktop_entry.exec_command().unwrap();
+    assert_eq!(exec_command.program, app_image_path);
+    assert_eq!(
+        exec_command.args,
+        vec![
+            ExecArg::Literal("--no-sandbox".to_string()),
+            ExecArg::FieldCode('F')
+        ]
+    );
+    assert!(installed.contains(&format!("Exec=\"{}\" --no-sandbox %F\n", app_image_path)));
+    assert_eq!(desktop_entry.try_exec(), Some(app_image_path.clone()));
+    assert_eq!(desktop_entry.generic_name(), Some("Text Editor".to_string()));
+    assert_eq!(desktop_entry.localized_names()["de"], "Test DE");
+    assert_eq!(
+        desktop_entry.mime_types(),
+        Some(vec!["text/plain".to_string(), "text/markdown".to_string()])
+    );
+    assert_eq!(desktop_entry.startup_wm_class(), Some("test-app".to_string()));
+    assert_eq!(
+        desktop_entry.keywords(),
+        Some(vec!["text".to_string(), "editor".to_string()])
+    );
+
+    let actions = desktop_entry.actions();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].name, Some("New Window".to_string()));
+    assert_eq!(
+        actions[0].exec,
+        Some(format!("\"{}\" --new-window --no-sandbox", app_image_path))
+    );
+    assert!(installed.contains("Name[de]=Neues Fenster\n"));
+    assert_eq!(desktop_entry.icon(), Some("test".to_string()));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::ErrorKind;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::Registry;
+use std::fs;
+use std::path::Path;
+
+// the environment is shared by the whole process, so the policies run in a single test
+#[test]
+fn test_install_of_an_installed_app() {
+    let env = common::setup();
+    let installation_dir = &env.installation_dir;
+    let install = |source: &Path, on_duplicate: DuplicatePolicy| {
+        env.install(source, &common::install_options(on_duplicate))
+    };
+
+    let first = env.write_version("Test-1.0-x86_64.AppImage", "1.0");
+    install(&first, DuplicatePolicy::Refuse).unwrap();
+
+    // refused by default, leaving the installed version untouched
+    let second = env.write_version("Test-2.0-x86_64.AppImage", "2.0");
+    let error = install(&second, DuplicatePolicy::Refuse).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::AlreadyInstalled);
+    assert!(error.to_string().contains("Test 1.0"), "{}", error);
+    assert!(installation_dir.join("test/1.0/Test-1.0-x86_64.AppImage").exists());
+    assert!(!installation_dir.join("test/2.0").exists());
+
+    // an upgrade replaces the installed version, which is kept to roll back to it
+    let upgraded = install(&second, DuplicatePolicy::Replace).unwrap();
+    assert_eq!(upgraded.id, "test");
+    assert_eq!(upgraded.version.as_deref(), Some("2.0"));
+    assert_eq!(upgraded.versions.len(), 2);
+    assert!(installation_dir.join("test/1.0/Test-1.0-x86_64.AppImage").exists());
+    assert!(upgraded.app_image_path.exists());
+    assert!(upgraded.desktop_file_path.exists());
+    assert!(upgraded.icon_paths.iter().all(|icon_path| icon_path.exists()));
+    assert_eq!(Registry::load(InstallScope::System).unwrap().apps().len(), 1);
+
+    // keeping both installs the older version under its own id and name
+    let kept = install(&first, DuplicatePolicy::KeepBoth).unwrap();
+    assert_eq!(kept.id, "test-1.0");
+    assert_eq!(kept.name, "Test (1.0)");
+    let kept_desktop_entry = fs::read_to_string(&kept.desktop_file_path).unwrap();
+    assert!(kept_desktop_entry.contains("Name[fr]=Essai (1.0)\n"), "{}", kept_desktop_entry);
+    assert!(kept.app_image_path.exists());
+    assert!(upgraded.app_image_path.exists());
+
+    let registry = Registry::load(InstallScope::System).unwrap();
+    assert_eq!(registry.apps().len(), 2);
+    let mut desktop_files: Vec<_> = fs::read_dir(&env.applications_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    desktop_files.sort();
+    assert_eq!(desktop_files, vec!["test-1.0.desktop", "test.desktop"]);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use backend::app_image_installer::{install_app_image_with_progress, InstallProgress};
+use common_utils::backend_event::InstallPhase;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use std::cell::RefCell;
+use std::fs;
+
+/// Records the phases, and cancels the install once the given phase is reached
+struct RecordingProgress {
+    phases: RefCell<Vec<InstallPhase>>,
+    cancel_at: Option<fn(&InstallPhase) -> bool>,
+}
+
+impl InstallProgress for RecordingProgress {
+    fn phase(&self, phase: InstallPhase) {
+        self.phases.borrow_mut().push(phase);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        match (self.cancel_at, self.phases.borrow().last()) {
+            (Some(cancel_at), Some(phase)) => cancel_at(phase),
+            _ => false,
+        }
+    }
+}
+
+// the environment is shared by the whole process, so both installs run in a single test
+#[test]
+fn test_install_reports_phases_and_can_be_cancelled() {
+    let env = common::setup();
+    let (registry_path, installation_dir) = (&env.registry_path, &env.installation_dir);
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    let size = fs::metadata(&source).unwrap().len();
+
+    let install = |progress: &RecordingProgress| {
+        install_app_image_with_progress(
+            source.clone(),
+            installation_dir.clone(),
+            &common::install_options(DuplicatePolicy::Refuse),
+            progress,
+        )
+    };
+
+    // a cancelled copy is undone
+    let progress = RecordingProgress {
+        phases: RefCell::new(Vec::new()),
+        cancel_at: Some(|phase| matches!(phase, InstallPhase::Copying { .. })),
+    };
+    assert!(install(&progress).unwrap_err().is_cancelled());
+    assert!(!installation_dir.exists());
+    assert!(!registry_path.exists());
+
+    let progress = RecordingProgress {
+        phases: RefCell::new(Vec::new()),
+        cancel_at: None,
+    };
+    install(&progress).unwrap();
+    assert_eq!(
+        progress.phases.into_inner(),
+        vec![
+            InstallPhase::Copying {
+                done_bytes: 0,
+                total_bytes: size
+            },
+            InstallPhase::Copying {
+                done_bytes: size,
+                total_bytes: size
+            },
+            InstallPhase::Hashing,
+            InstallPhase::Inspecting,
+            InstallPhase::Icons,
+            InstallPhase::DesktopEntry,
+            InstallPhase::Committing,
+        ]
+    );
+}
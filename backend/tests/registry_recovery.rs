@@ -0,0 +1,29 @@
+mod common;
+
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::Registry;
+use std::fs;
+
+#[test]
+fn test_recovered_registry_is_saved_once() {
+    let env = common::setup();
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    let app = env
+        .install(&source, &common::install_options(DuplicatePolicy::Refuse))
+        .unwrap();
+    fs::remove_file(&env.registry_path).unwrap();
+
+    // the list reads the desktop files without hashing the AppImages or saving anything
+    let listed = Registry::load_or_list(InstallScope::System).unwrap();
+    let listed = listed.find_by_id("test").unwrap();
+    assert_eq!(listed.app_image_path, app.app_image_path);
+    assert!(listed.sha256.is_empty());
+    assert!(!env.registry_path.exists());
+
+    // the recovered registry is saved, the next loads read it
+    let recovered = Registry::load_or_recover(InstallScope::System).unwrap();
+    assert_eq!(recovered.find_by_id("test").unwrap().sha256, app.sha256);
+    assert_eq!(Registry::load_from(&env.registry_path).unwrap().apps(), recovered.apps());
+    assert_eq!(Registry::load_or_list(InstallScope::System).unwrap().apps(), recovered.apps());
+}
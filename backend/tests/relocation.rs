@@ -0,0 +1,36 @@
+mod common;
+
+use backend::app_image_installer::{install_app_image, InstallOptions};
+use backend::app_image_updater::app_image_update;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::Registry;
+
+#[test]
+fn test_relocation_updates_the_registry_of_each_scope() {
+    let env = common::setup();
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    let options = InstallOptions {
+        scope: InstallScope::User,
+        ..common::install_options(DuplicatePolicy::Refuse)
+    };
+    install_app_image(source, env.installation_dir.clone(), &options).unwrap();
+
+    let new_install_dir = env.path().join("Moved AppImages");
+    app_image_update(
+        env.installation_dir.to_string_lossy().to_string(),
+        new_install_dir.to_string_lossy().to_string(),
+    )
+    .unwrap();
+
+    let registry = Registry::load(InstallScope::User).unwrap();
+    let app = registry.find_by_id("test").unwrap();
+    assert_eq!(app.app_image_path, new_install_dir.join("test/test.AppImage"));
+    assert!(app.app_image_path.exists());
+    assert!(app
+        .versions
+        .iter()
+        .all(|version| version.app_image_path.starts_with(&new_install_dir)));
+    // no app moved in the system scope
+    assert!(!env.registry_path.exists());
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use backend::app_image_installer::{install_app_image, InstallOptions};
+use backend::app_image_uninstaller::uninstall_app_image;
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::install_scope::InstallScope;
+use common_utils::registry::{AppSelector, Registry};
+use std::fs;
+
+#[test]
+fn test_user_install_and_uninstall() {
+    let env = common::setup();
+    let data_dir = &env.data_dir;
+    let source = env.write_app_image("Test-x86_64.AppImage", "test");
+    let installation_dir = InstallScope::User.default_installation_dir().unwrap();
+    assert_eq!(installation_dir, data_dir.join("AppImages"));
+
+    let options = InstallOptions {
+        scope: InstallScope::User,
+        ..common::install_options(DuplicatePolicy::Refuse)
+    };
+    install_app_image(source, installation_dir.clone(), &options).unwrap();
+
+    let desktop_file_path = data_dir.join("applications/test.desktop");
+    let icon_path = data_dir.join("icons/hicolor/64x64/apps/test.png");
+    assert!(installation_dir.join("test/test.AppImage").exists());
+    assert!(desktop_file_path.exists());
+    assert!(icon_path.exists());
+
+    let registry = Registry::load(InstallScope::User).unwrap();
+    assert_eq!(registry.path(), data_dir.join("apphub/registry.json"));
+    let app = registry.find_by_id("test").unwrap();
+    assert_eq!(app.scope, InstallScope::User);
+    assert!(app.icon_paths.contains(&icon_path));
+    // the system registry is not touched
+    assert!(!env.registry_path.exists());
+
+    uninstall_app_image(AppSelector::Id("test".to_string()), InstallScope::User).unwrap();
+
+    assert!(!installation_dir.join("test/test.AppImage").exists());
+    assert!(!desktop_file_path.exists());
+    assert!(app.icon_paths.iter().all(|icon_path| !icon_path.exists()));
+    assert!(Registry::load(InstallScope::User).unwrap().apps().is_empty());
+    assert!(fs::read_dir(&installation_dir).unwrap().next().is_none());
+}
@@ -0,0 +1,116 @@
+use crate::app_image_reader::AppImageReader;
+use crate::error::Result;
+use crate::file_system_helpers::file_sha256;
+use log::{debug, info};
+use regex::Regex;
+use std::path::Path;
+
+/// Derive a stable identifier for the app packaged in an AppImage.
+/// In order of preference: the AppStream component id, the basename of the desktop
+/// file and a hash of the AppImage content.
+pub fn derive_app_id(reader: &mut AppImageReader) -> Result<String> {
+    if let Some(id) = appstream_app_id(reader) {
+        info!("Using AppStream id: {}", id);
+        return Ok(id);
+    }
+
+    if let Some(id) = reader
+        .find_desktop_file()
+        .ok()
+        .and_then(|desktop_file| desktop_file_app_id(&desktop_file))
+    {
+        info!("Using desktop file id: {}", id);
+        return Ok(id);
+    }
+
+    let sha256 = file_sha256(reader.path())?;
+    let id = format!("appimage-{}", &sha256[..16]);
+    info!("Using content hash id: {}", id);
+    Ok(id)
+}
+
+fn appstream_app_id(reader: &mut AppImageReader) -> Option<String> {
+    let appstream_file = reader.find_appstream_file()?;
+    let content = reader.read_file(&appstream_file).ok()?;
+    let id = parse_appstream_id(&String::from_utf8_lossy(&content));
+    debug!("AppStream id found in {}: {:?}", appstream_file, id);
+    id
+}
+
+/// Read the component id of an AppStream metadata file
+pub fn parse_appstream_id(content: &str) -> Option<String> {
+    let re_id = Regex::new(r"<id(?:\s[^>]*)?>\s*([^<]+?)\s*</id>").unwrap();
+    let id = re_id.captures(content)?[1].to_string();
+    // legacy metadata uses the desktop file name as id
+    sanitize_app_id(id.strip_suffix(".desktop").unwrap_or(&id))
+}
+
+/// The id given by the name of a desktop file, without its extension
+pub fn desktop_file_app_id(desktop_file: &str) -> Option<String> {
+    let stem = Path::new(desktop_file).file_stem()?.to_string_lossy().to_string();
+    sanitize_app_id(&stem)
+}
+
+/// Keep only the characters allowed in desktop file ids
+pub fn sanitize_app_id(id: &str) -> Option<String> {
+    let id: String = id
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let id = id.trim_matches('.').to_string();
+
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::AppImageFixture;
+
+    #[test]
+    fn test_parse_appstream_id() {
+        let content = r#"<?xml version="1.0"?>
+<component type="desktop-application">
+  <id>org.example.Test.desktop</id>
+  <name>Test</name>
+</component>"#;
+        assert_eq!(parse_appstream_id(content), Some("org.example.Test".to_string()));
+        assert_eq!(parse_appstream_id("<component/>"), None);
+    }
+
+    #[test]
+    fn test_sanitize_app_id() {
+        assert_eq!(sanitize_app_id("My App/1"), Some("My_App_1".to_string()));
+        assert_eq!(sanitize_app_id(" .. "), None);
+    }
+
+    #[test]
+    fn test_derive_app_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Test.AppImage");
+
+        AppImageFixture::with_app("test").write_to(&path);
+        let mut reader = AppImageReader::open(&path).unwrap();
+        assert_eq!(derive_app_id(&mut reader).unwrap(), "test");
+
+        let mut fixture = AppImageFixture::with_app("test");
+        fixture.file(
+            "usr/share/metainfo/org.example.Test.appdata.xml",
+            b"<component><id>org.example.Test</id></component>",
+        );
+        fixture.write_to(&path);
+        let mut reader = AppImageReader::open(&path).unwrap();
+        assert_eq!(derive_app_id(&mut reader).unwrap(), "org.example.Test");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::app_id::derive_app_id;
+use crate::app_image_reader::AppImageReader;
+use crate::app_images_helpers::collect_theme_icons;
+use crate::desktop_file_builder::DesktopFileBuilder;
+use crate::desktop_file_helpers::desktop_entry_keys;
+use crate::error::{Error, Result};
+use crate::file_system_helpers::{file_sha256, get_file_name};
+use crate::install_layout::{app_dir, launcher_file_name};
+use crate::install_scope::InstallScope;
+use crate::registry::{Registry, RegistryEntry};
+use crate::signature::{verify_app_image_signature, SignatureStatus};
+use crate::update_info::UpdateInfo;
+use log::info;
+use std::path::{Path, PathBuf};
+
+/// The size of the icon shown in the preview
+const PREVIEW_ICON_SIZE: &str = "128x128";
+
+/// What installing an AppImage would do, read from the image without installing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppImagePreview {
+    pub file_path: PathBuf,
+    pub file_name: String,
+    pub id: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub comment: Option<String>,
+    pub categories: Vec<String>,
+    /// The icon that would be installed, as a PNG image
+    pub icon_png: Option<Vec<u8>>,
+    pub size: u64,
+    pub sha256: String,
+    pub signature: SignatureStatus,
+    pub update_info: Option<UpdateInfo>,
+    /// The installed copies of the app, in every scope
+    pub installed: Vec<RegistryEntry>,
+    /// The desktop entry embedded in the AppImage
+    desktop_entry: String,
+}
+
+impl AppImagePreview {
+    /// The Exec line the desktop entry would get when installing in the given directory
+    pub fn exec_line(&self, installation_dir: &Path, no_sandbox: bool) -> Option<String> {
+        let mut desktop_builder =
+            DesktopFileBuilder::from_desktop_entry_content(&self.desktop_entry, false).ok()?;
+        desktop_builder.target_app_image(
+            &app_dir(installation_dir, &self.id),
+            &launcher_file_name(&self.id),
+            no_sandbox,
+        );
+        let content = desktop_builder.generate_content_string().ok()?;
+        desktop_entry_keys(&content).remove("Exec")
+    }
+}
+
+/// Inspect an AppImage the way the install would, without running it or writing anything.
+/// The signature is checked against the keyring in `trusted_keys_dir`.
+pub fn preview_app_image(
+    file_path: &Path,
+    trusted_keys_dir: Option<&Path>,
+) -> Result<AppImagePreview> {
+    info!("Previewing AppImage: {:?}", file_path);
+    let file_path = file_path.to_path_buf();
+    let file_name = get_file_name(&file_path)?;
+    let size = file_path
+        .metadata()
+        .map_err(|e| Error::io("Failed to read AppImage metadata", e))?
+        .len();
+    let sha256 = file_sha256(&file_path)?;
+    let signature = verify_app_image_signature(&file_path, trusted_keys_dir)?;
+
+    let mut reader = AppImageReader::open(&file_path)?;
+    let desktop_entry = reader.read_desktop_file()?;
+    let desktop_builder = DesktopFileBuilder::from_desktop_entry_content(&desktop_entry, false)?;
+    let update_info = reader.update_info().unwrap_or_default();
+    let id = derive_app_id(&mut reader)?;
+    let name = desktop_builder.name().unwrap_or_else(|| id.clone());
+
+    let icon_png = collect_theme_icons(&mut reader, desktop_builder.icon().as_deref(), &name)
+        .into_iter()
+        .find(|icon| icon.size_dir == PREVIEW_ICON_SIZE)
+        .map(|icon| icon.content);
+
+    let installed = InstallScope::ALL
+        .into_iter()
+        .filter_map(|scope| {
+            let registry = Registry::load_or_list(scope).ok()?;
+            registry.find_by_id(&id).cloned()
+        })
+        .collect();
+
+    Ok(AppImagePreview {
+        file_path,
+        file_name,
+        name,
+        version: desktop_builder.app_image_version(),
+        comment: desktop_builder.comment(),
+        categories: desktop_builder
+            .categories()
+            .map(|categories| {
+                categories
+                    .split(';')
+                    .filter(|category| !category.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        icon_png,
+        size,
+        sha256,
+        signature,
+        update_info,
+        installed,
+        id,
+        desktop_entry,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::REGISTRY_PATH_ENV;
+    use crate::test_fixtures::AppImageFixture;
+    use std::env;
+
+    #[test]
+    fn test_preview_app_image() {
+        let dir = tempfile::tempdir().unwrap();
+        env::set_var(REGISTRY_PATH_ENV, dir.path().join("registry.json"));
+        env::set_var("XDG_DATA_HOME", dir.path().join("data"));
+        let source = dir.path().join("Test-x86_64.AppImage");
+        AppImageFixture::with_app("test").write_to(&source);
+
+        let preview = preview_app_image(&source, None).unwrap();
+        assert_eq!(preview.id, "test");
+        assert_eq!(preview.name, "test");
+        assert_eq!(preview.categories, vec!["Utility".to_string()]);
+        assert_eq!(preview.signature, SignatureStatus::Unsigned);
+        assert_eq!(preview.size, source.metadata().unwrap().len());
+        assert!(preview.icon_png.is_some());
+        assert!(preview.installed.is_empty());
+        assert_eq!(
+            preview.exec_line(Path::new("/opt/My Apps"), true),
+            Some("\"/opt/My Apps/test/test.AppImage\" --no-sandbox".to_string())
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::elf_reader::ElfHeader;
+use crate::error::{Error, ErrorKind, Result};
+use crate::image_payload::{EntryKind, ImageEntry, ImagePayload};
+use crate::iso9660_reader::Iso9660Reader;
+use crate::squashfs_reader::SquashFsReader;
+use crate::update_info::{read_update_info, UpdateInfo};
+use log::{debug, info};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Offset of the AppImage magic bytes inside the ELF identification padding
+const APP_IMAGE_MAGIC_OFFSET: u64 = 8;
+/// Offset of the "CD001" identifier of the first ISO 9660 volume descriptor
+const ISO_9660_IDENTIFIER_OFFSET: u64 = 32769;
+
+/// The AppImage format versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AppImageType {
+    /// An ISO 9660 image whose system area holds the ELF runtime
+    Type1,
+    /// An ELF runtime followed by a SquashFS image
+    Type2,
+}
+
+/// Detect the AppImage type from the magic bytes at offset 8.
+/// Files without the magic bytes are recognized by their ISO 9660 or ELF headers.
+pub fn detect_app_image_type(file: &mut File) -> Result<AppImageType> {
+    let mut magic = [0u8; 3];
+    file.seek(SeekFrom::Start(APP_IMAGE_MAGIC_OFFSET))
+        .and_then(|_| file.read_exact(&mut magic))
+        .map_err(|e| Error::io("Failed to read AppImage magic bytes", e))?;
+
+    match &magic {
+        b"AI\x01" => return Ok(AppImageType::Type1),
+        b"AI\x02" => return Ok(AppImageType::Type2),
+        _ => debug!("AppImage magic bytes not found: {:?}", magic),
+    }
+
+    let mut identifier = [0u8; 5];
+    let is_iso = file
+        .seek(SeekFrom::Start(ISO_9660_IDENTIFIER_OFFSET))
+        .and_then(|_| file.read_exact(&mut identifier))
+        .is_ok()
+        && &identifier == b"CD001";
+    if is_iso {
+        return Ok(AppImageType::Type1);
+    }
+
+    ElfHeader::read_from(file)?;
+    Ok(AppImageType::Type2)
+}
+
+/// Read the metadata of an AppImage straight from the file, without executing it.
+pub struct AppImageReader {
+    path: PathBuf,
+    app_image_type: AppImageType,
+    payload: Box<dyn ImagePayload + Send>,
+}
+
+impl AppImageReader {
+    /// Open an AppImage and locate its payload: the ISO 9660 image starting at the
+    /// beginning of type 1 AppImages, or the SquashFS image right after the ELF runtime
+    /// of type 2 AppImages
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.exists() || !path.is_file() {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "AppImage file does not exist or is not a file"));
+        }
+
+        let mut file =
+            File::open(path).map_err(|e| Error::io("Failed to open AppImage", e))?;
+
+        let app_image_type = detect_app_image_type(&mut file)?;
+        info!("Detected AppImage {:?}", app_image_type);
+
+        let payload: Box<dyn ImagePayload + Send> = match app_image_type {
+            AppImageType::Type1 => Box::new(Iso9660Reader::open(file, 0)?),
+            AppImageType::Type2 => {
+                let elf_header = ElfHeader::read_from(&mut file)?;
+                let payload_offset = elf_header.file_size();
+                info!("AppImage payload found at offset {}", payload_offset);
+                Box::new(SquashFsReader::open(file, payload_offset)?)
+            }
+        };
+
+        Ok(AppImageReader {
+            path: path.to_path_buf(),
+            app_image_type,
+            payload,
+        })
+    }
+
+    pub fn app_image_type(&self) -> AppImageType {
+        self.app_image_type
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// List every entry contained in the AppImage
+    pub fn entries(&mut self) -> Result<Vec<ImageEntry>> {
+        self.payload.entries()
+    }
+
+    /// List the entries of a directory of the AppImage
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<ImageEntry>> {
+        self.payload.read_dir(path)
+    }
+
+    /// Read a file from the AppImage, following symlinks
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        self.payload.read_file(path)
+    }
+
+    /// Find the path of the .desktop file placed at the root of the AppImage
+    pub fn find_desktop_file(&mut self) -> Result<String> {
+        self.read_dir("")?
+            .into_iter()
+            .find(|entry| entry.path.ends_with(".desktop") && entry.kind != EntryKind::Directory)
+            .map(|entry| entry.path)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidAppImage, "No desktop file found"))
+    }
+
+    /// Read the content of the .desktop file placed at the root of the AppImage
+    pub fn read_desktop_file(&mut self) -> Result<String> {
+        let desktop_file = self.find_desktop_file()?;
+        let content = self.read_file(&desktop_file)?;
+        String::from_utf8(content).map_err(|_| Error::new(ErrorKind::InvalidAppImage, "Desktop file is not valid UTF-8"))
+    }
+
+    /// Read the `.DirIcon` of the AppImage, if any
+    pub fn read_dir_icon(&mut self) -> Option<Vec<u8>> {
+        self.read_file(".DirIcon").ok()
+    }
+
+    /// Find the AppStream metadata file shipped in the AppImage, if any
+    pub fn find_appstream_file(&mut self) -> Option<String> {
+        ["usr/share/metainfo", "usr/share/appdata"]
+            .iter()
+            .filter_map(|dir| self.read_dir(dir).ok())
+            .flatten()
+            .find(|entry| entry.path.ends_with(".appdata.xml") || entry.path.ends_with(".metainfo.xml"))
+            .map(|entry| entry.path)
+    }
+
+    /// Read the update information embedded in the AppImage, if any
+    pub fn update_info(&self) -> Result<Option<UpdateInfo>> {
+        read_update_info(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{png_icon, png_stub, AppImageFixture};
+
+    #[test]
+    fn test_read_app_image_without_executing_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Test.AppImage");
+        let mut fixture = AppImageFixture::with_app("test");
+        fixture
+            .file("usr/share/metainfo/org.example.test.appdata.xml", b"<component/>")
+            .file("usr/share/icons/hicolor/256x256/apps/test.png", &png_stub(300_000));
+        fixture.write_to(&app_image_path);
+
+        let mut reader = AppImageReader::open(&app_image_path).unwrap();
+
+        assert_eq!(reader.app_image_type(), AppImageType::Type2);
+        assert_eq!(reader.find_desktop_file().unwrap(), "test.desktop");
+        assert!(reader.read_desktop_file().unwrap().contains("Name=test"));
+        assert_eq!(reader.read_dir_icon().unwrap(), png_icon(64));
+        assert_eq!(
+            reader.find_appstream_file().unwrap(),
+            "usr/share/metainfo/org.example.test.appdata.xml"
+        );
+        assert_eq!(
+            reader
+                .read_file("usr/share/icons/hicolor/256x256/apps/test.png")
+                .unwrap()
+                .len(),
+            300_000
+        );
+        assert!(reader
+            .entries()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.path == ".DirIcon" && entry.kind == EntryKind::Symlink("test.png".to_string())));
+    }
+
+    #[test]
+    fn test_read_type1_app_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_image_path = dir.path().join("Test.AppImage");
+        let mut fixture = AppImageFixture::with_app("test");
+        fixture.file("usr/share/icons/hicolor/256x256/apps/test.png", &png_stub(100_000));
+        fixture.write_type1_to(&app_image_path);
+
+        let mut reader = AppImageReader::open(&app_image_path).unwrap();
+
+        assert_eq!(reader.app_image_type(), AppImageType::Type1);
+        assert_eq!(reader.find_desktop_file().unwrap(), "test.desktop");
+        assert!(reader.read_desktop_file().unwrap().contains("Name=test"));
+        assert_eq!(reader.read_dir_icon().unwrap(), png_icon(64));
+        assert_eq!(
+            reader
+                .read_file("usr/share/icons/hicolor/256x256/apps/test.png")
+                .unwrap(),
+            png_stub(100_000)
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_non_elf_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("NotAnAppImage.AppImage");
+        std::fs::write(&path, vec![0u8; 128]).unwrap();
+
+        assert!(AppImageReader::open(&path).is_err());
+    }
+}
@@ -1,26 +1,38 @@
+use crate::error::{Error, ErrorKind, Result};
 use fs_extra::dir;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat, RgbaImage};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, TreeParsing};
+use std::cmp::Reverse;
 use std::fs;
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::process::Command;
 
-use crate::desktop_file_builder::DesktopFileBuilder;
-use crate::desktop_file_helpers::find_desktop_file_location;
+use crate::app_image_reader::AppImageReader;
 use crate::file_system_helpers::add_executable_permission;
+use crate::desktop_file_helpers::desktop_entry_keys;
+use crate::models::app_info::AppInfo;
 use crate::models::installed_app::InstalledApp;
+use crate::install_scope::InstallScope;
+use crate::registry::{Registry, RegistryEntry};
+use crate::image_payload::{EntryKind, ImageEntry};
+use crate::letter_avatar::letter_avatar_svg;
 use log::{debug, error, info};
 
 /// Install an AppImage file using the given file path
 pub fn install_app_image_from_path(
     file_path: &String,
     installation_path: &String,
-) -> Result<String, String> {
+) -> Result<String> {
     // Try to create the directory and handle the error if it already exists
     match fs::create_dir(&installation_path) {
         Ok(_) => {}
         Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
             info!("Directory already exists");
         }
-        Err(e) => return Err(format!("Failed to create directory: {}", e)),
+        Err(e) => return Err(Error::new(ErrorKind::Io, format!("Failed to create directory: {}", e))),
     }
 
     let path_buf = std::path::PathBuf::from(file_path);
@@ -39,110 +51,322 @@ pub fn install_app_image_from_path(
     Ok("Installation successful".to_string())
 }
 
-/// Extract the .desktop file from the AppImage
-/// Returns the path to the extracted .desktop file
-pub fn app_image_extract_squashroot(app_image_path: &PathBuf) -> Result<PathBuf, &'static str> {
-    info!("Starting extraction of .desktop file from AppImage...");
+/// The sizes of the directories of the hicolor icon theme
+const HICOLOR_SIZES: [u32; 13] = [16, 22, 24, 32, 36, 48, 64, 72, 96, 128, 192, 256, 512];
 
-    if !app_image_path.exists() || !app_image_path.is_file() {
-        error!("AppImage file does not exist or is not a file");
-        return Err("AppImage file does not exist or is not a file");
+/// The sizes of the PNG icons installed for every app
+pub const ICON_SIZES: [u32; 4] = [32, 64, 128, 256];
+
+/// The hicolor icon theme shipped inside AppImages
+const APP_IMAGE_HICOLOR_DIR: &str = "usr/share/icons/hicolor";
+
+/// The image extensions looked up for the icon named by the `Icon` key
+const ICON_EXTENSIONS: [&str; 5] = ["png", "svg", "jpg", "jpeg", "ico"];
+
+/// An icon of the app, to install in the hicolor icon theme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeIcon {
+    /// The size directory of the theme, like `48x48` or `scalable`
+    pub size_dir: String,
+    /// The file extension, `png` or `svg`
+    pub extension: &'static str,
+    pub content: Vec<u8>,
+}
+
+impl ThemeIcon {
+    fn png(size: u32, content: Vec<u8>) -> Self {
+        ThemeIcon {
+            size_dir: format!("{0}x{0}", size),
+            extension: "png",
+            content,
+        }
     }
 
-    let app_name = match app_image_path.file_name() {
-        None => return Err("Failed to get AppImage file name"),
-        Some(name) => name
-            .to_str()
-            .ok_or_else(|| "Failed to convert OsStr to str")?,
-    };
+    /// The path of the icon inside the theme, `<size>/apps/<name>.<extension>`
+    pub fn theme_path(&self, name: &str) -> PathBuf {
+        PathBuf::from(&self.size_dir)
+            .join("apps")
+            .join(format!("{}.{}", name, self.extension))
+    }
+}
+
+/// An image decoded from PNG, JPEG, ICO or SVG
+enum DecodedIcon {
+    Raster {
+        image: DynamicImage,
+        /// The original content, when it can be installed as it is
+        png: Option<Vec<u8>>,
+    },
+    Svg {
+        tree: resvg::Tree,
+        content: Vec<u8>,
+    },
+}
+
+impl DecodedIcon {
+    fn decode(content: Vec<u8>) -> Result<Self> {
+        if is_svg(&content) {
+            let tree = usvg::Tree::from_data(&content, &usvg::Options::default())
+                .map_err(|e| e.to_string())?;
+            return Ok(DecodedIcon::Svg {
+                tree: resvg::Tree::from_usvg(&tree),
+                content,
+            });
+        }
+        let image = image::load_from_memory(&content).map_err(|e| e.to_string())?;
+        let png = (image::guess_format(&content).ok() == Some(ImageFormat::Png)).then_some(content);
+        Ok(DecodedIcon::Raster { image, png })
+    }
 
-    // Get parent directory of app_image_path
-    let parent_dir = match app_image_path.parent() {
-        None => {
-            error!("Failed to get parent directory of AppImage file");
-            return Err("Failed to get parent directory of AppImage file");
+    /// The size of the smallest side, an SVG is considered bigger than any raster
+    fn size(&self) -> u32 {
+        match self {
+            DecodedIcon::Raster { image, .. } => image.width().min(image.height()),
+            DecodedIcon::Svg { .. } => u32::MAX,
         }
-        Some(dir) => dir,
+    }
+
+    /// Encode the icon as a square PNG of the given size, centered and keeping its aspect ratio
+    fn render_png(&self, size: u32) -> Option<Vec<u8>> {
+        match self {
+            DecodedIcon::Raster { image, .. } => {
+                let resized = image.resize(size, size, FilterType::Lanczos3).to_rgba8();
+                let mut canvas = RgbaImage::new(size, size);
+                let x = (size - resized.width()) / 2;
+                let y = (size - resized.height()) / 2;
+                imageops::overlay(&mut canvas, &resized, x as i64, y as i64);
+                let mut content = Cursor::new(Vec::new());
+                canvas.write_to(&mut content, ImageOutputFormat::Png).ok()?;
+                Some(content.into_inner())
+            }
+            DecodedIcon::Svg { tree, .. } => {
+                let mut pixmap = Pixmap::new(size, size)?;
+                let (width, height) = (tree.size.width(), tree.size.height());
+                let scale = size as f32 / width.max(height);
+                let x = (size as f32 - width * scale) / 2.0;
+                let y = (size as f32 - height * scale) / 2.0;
+                tree.render(
+                    Transform::from_row(scale, 0.0, 0.0, scale, x, y),
+                    &mut pixmap.as_mut(),
+                );
+                pixmap.encode_png().ok()
+            }
+        }
+    }
+}
+
+/// Collect the icons of the AppImage, as PNG images of the standard [`ICON_SIZES`] and
+/// an SVG image when the app ships one.
+/// The icon named by the `Icon` key in the hicolor theme shipped in the AppImage,
+/// `.DirIcon` and the icon at the root of the AppImage are used, the biggest image of the
+/// AppImage only when none of them can be decoded. When there is no usable image at all,
+/// a letter avatar is generated from the app name.
+/// PNG icons are sorted from the biggest, the SVG icon comes last.
+pub fn collect_theme_icons(
+    reader: &mut AppImageReader,
+    icon_name: Option<&str>,
+    app_name: &str,
+) -> Vec<ThemeIcon> {
+    let icon_name = icon_name.map(icon_name_without_extension);
+    let mut sources: Vec<(String, Vec<u8>)> = Vec::new();
+
+    if let Some(icon_name) = &icon_name {
+        for size_dir in reader.read_dir(APP_IMAGE_HICOLOR_DIR).unwrap_or_default() {
+            for extension in ["png", "svg"] {
+                let path = format!("{}/apps/{}.{}", size_dir.path, icon_name, extension);
+                if let Ok(content) = reader.read_file(&path) {
+                    sources.push((path, content));
+                }
+            }
+        }
+    }
+
+    if let Some(content) = reader.read_dir_icon() {
+        sources.push((".DirIcon".to_string(), content));
+    }
+
+    if let Some(icon_name) = &icon_name {
+        for extension in ICON_EXTENSIONS {
+            let path = format!("{}.{}", icon_name, extension);
+            if let Ok(content) = reader.read_file(&path) {
+                sources.push((path, content));
+            }
+        }
+    }
+
+    let mut icons = normalize_icons(sources);
+    if icons.is_empty() {
+        if let Ok(source) = biggest_image(reader) {
+            icons = normalize_icons(vec![source]);
+        }
+    }
+    if icons.is_empty() {
+        info!("No usable icon found, generating one for {}", app_name);
+        icons = normalize_icons(vec![(
+            "letter avatar".to_string(),
+            letter_avatar_svg(app_name).into_bytes(),
+        )]);
+    }
+    icons
+}
+
+/// Turn the images of an app into theme icons.
+/// PNG images of a hicolor size are kept as they are, the missing [`ICON_SIZES`] are rendered
+/// from the SVG image, or else from the biggest raster image. Images that cannot be decoded
+/// are ignored, so no icon is returned when none of them can be.
+pub fn normalize_icons(sources: Vec<(String, Vec<u8>)>) -> Vec<ThemeIcon> {
+    let decoded: Vec<DecodedIcon> = sources
+        .into_iter()
+        .filter_map(|(source, content)| match DecodedIcon::decode(content) {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                debug!("Ignoring the icon {}: {}", source, e);
+                None
+            }
+        })
+        .collect();
+    let Some(master) = decoded.iter().max_by_key(|icon| icon.size()) else {
+        return Vec::new();
     };
 
-    let command = format!(
-        "cd {} && ./{} --appimage-extract",
-        parent_dir.to_str().unwrap(),
-        app_name
-    );
-    debug!("Running command: {}", command);
-
-    let output = std::process::Command::new("bash")
-        .arg("-c")
-        .arg(command)
-        .output()
-        .expect("Failed to execute command");
-
-    if output.status.success() {
-        info!("Successfully extracted .desktop file from AppImage.");
-    } else {
-        let err = String::from_utf8_lossy(&output.stderr);
-        error!("Failed to extract AppImage desktop file: {}", err);
-        return Err("Failed to extract AppImage desktop file");
+    let mut icons: Vec<ThemeIcon> = Vec::new();
+    for icon in &decoded {
+        if let DecodedIcon::Raster {
+            image,
+            png: Some(content),
+        } = icon
+        {
+            let size = image.width();
+            if size == image.height()
+                && HICOLOR_SIZES.contains(&size)
+                && !icons.iter().any(|icon| icon.size_dir == format!("{0}x{0}", size))
+            {
+                icons.push(ThemeIcon::png(size, content.clone()));
+            }
+        }
+    }
+
+    for size in ICON_SIZES {
+        if icons.iter().any(|icon| icon.size_dir == format!("{0}x{0}", size)) {
+            continue;
+        }
+        match master.render_png(size) {
+            Some(content) => icons.push(ThemeIcon::png(size, content)),
+            None => error!("Failed to render the {0}x{0} icon", size),
+        }
+    }
+
+    if let Some(DecodedIcon::Svg { content, .. }) = decoded
+        .iter()
+        .find(|icon| matches!(icon, DecodedIcon::Svg { .. }))
+    {
+        icons.push(ThemeIcon {
+            size_dir: "scalable".to_string(),
+            extension: "svg",
+            content: content.clone(),
+        });
     }
-    let squashfs_root_path = parent_dir.join(format!("squashfs-root"));
-    Ok(squashfs_root_path)
+
+    icons.sort_by_key(|icon| theme_size_order(&icon.size_dir));
+    icons
 }
 
-/// Install the icons from the AppImage by moving them to the installation dir icons folder
-pub fn choose_icon(
-    squashfs_root_path: &PathBuf,
-    installation_path: &PathBuf,
-) -> Result<PathBuf, &'static str> {
+/// Find the icons with the given name installed in an icon theme, sorted like
+/// [`collect_theme_icons`]
+pub fn find_theme_icons(theme_dir: &Path, icon_name: &str) -> Vec<PathBuf> {
+    let mut icons: Vec<(String, PathBuf)> = fs::read_dir(theme_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(std::result::Result::ok)
+        .flat_map(|size_dir| {
+            let size_name = size_dir.file_name().to_string_lossy().to_string();
+            ["png", "svg"].into_iter().map(move |extension| {
+                let path = size_dir
+                    .path()
+                    .join("apps")
+                    .join(format!("{}.{}", icon_name, extension));
+                (size_name.clone(), path)
+            })
+        })
+        .filter(|(_, path)| path.is_file())
+        .collect();
+    icons.sort_by_key(|(size_name, _)| theme_size_order(size_name));
+    icons.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Tell the desktop environments that the icons of a theme changed.
+/// The theme directory is touched, and its GTK icon cache is rebuilt when there is one.
+pub fn refresh_icon_cache(theme_dir: &Path) {
+    // creating a file updates the modification time of the directory
+    if let Err(e) = tempfile::tempfile_in(theme_dir) {
+        debug!("Failed to touch {:?}: {}", theme_dir, e);
+    }
+
+    if !theme_dir.join("icon-theme.cache").exists() {
+        return;
+    }
+    match Command::new("gtk-update-icon-cache")
+        .args(["--force", "--ignore-theme-index", "--quiet"])
+        .arg(theme_dir)
+        .status()
+    {
+        Ok(status) if status.success() => info!("Icon cache of {:?} updated", theme_dir),
+        Ok(status) => error!("gtk-update-icon-cache failed with {}", status),
+        Err(e) => debug!("gtk-update-icon-cache not available: {}", e),
+    }
+}
+
+/// The icon name of the `Icon` key, without the path and the image extension
+fn icon_name_without_extension(icon: &str) -> String {
+    let name = icon.rsplit('/').next().unwrap_or(icon);
+    [".png", ".svg", ".xpm", ".jpg", ".jpeg", ".ico"]
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// Sort the PNG sizes from the biggest, then the scalable icons
+fn theme_size_order(size_dir: &str) -> (bool, Reverse<u32>) {
+    let size = size_dir
+        .split('x')
+        .next()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(0);
+    (size_dir == "scalable", Reverse(size))
+}
+
+/// Whether the content is an SVG image, possibly gzip compressed
+fn is_svg(content: &[u8]) -> bool {
+    content.starts_with(&[0x1f, 0x8b])
+        || String::from_utf8_lossy(&content[..content.len().min(1024)]).contains("<svg")
+}
+
+/// The biggest image file of the AppImage, with its content
+fn biggest_image(reader: &mut AppImageReader) -> Result<(String, Vec<u8>)> {
     // Find all icons file
-    let image_files = find_image_files(squashfs_root_path);
+    let image_files = find_image_files(reader);
     debug!("Found {} image files", image_files.len());
     debug!("Image files: {:?}", image_files);
     if image_files.len() == 0 {
-        return Err("No image files found");
+        return Err(Error::new(ErrorKind::NotFound, "No image files found"));
     }
 
     // Choose the biggest image file
-    let biggest_image = image_files
-        .iter()
-        .max_by_key(|f| f.metadata().unwrap().len())
-        .unwrap();
+    let biggest_image = image_files.iter().max_by_key(|f| f.size).unwrap();
 
     debug!("Biggest image file: {:?}", biggest_image);
 
-    // Check if the icons directory exists or create it
-    let icons_dir = installation_path.join("icons");
-    match fs::create_dir(&icons_dir) {
-        Ok(_) => {
-            info!("Created icons directory: {:?}", icons_dir);
-        }
-        Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
-            info!("Icons directory already exists");
-        }
-        Err(e) => {
-            error!("Failed to create icons directory: {}", e);
-            return Err("Failed to create icons directory");
-        }
-    }
-
-    // Copy the biggest image file into the installation path
-    let icon_path = installation_path
-        .join("icons")
-        .join(biggest_image.file_name().unwrap());
-    match fs::copy(biggest_image, &icon_path) {
-        Ok(_) => {
-            info!("Copied icon to: {:?}", icon_path);
-        }
+    match reader.read_file(&biggest_image.path) {
+        Ok(content) => Ok((biggest_image.path.clone(), content)),
         Err(e) => {
-            error!("Failed to copy icon: {}", e);
-            return Err("Failed to copy icon");
+            error!("Failed to read icon from AppImage: {}", e);
+            Err(e.context("Failed to read icon from AppImage"))
         }
     }
-
-    Ok(icon_path)
 }
 
-fn recursive_copy(source: &Path, destination: &Path) -> Result<(), &'static str> {
+fn recursive_copy(source: &Path, destination: &Path) -> Result<()> {
     let mut options = dir::CopyOptions::new();
     options.overwrite = true;
     match dir::copy(source, destination, &options) {
@@ -151,13 +375,13 @@ fn recursive_copy(source: &Path, destination: &Path) -> Result<(), &'static str>
         }
         Err(error) => {
             error!("Failed to copy icons: {}", error);
-            return Err("Failed to copy icons");
+            return Err(Error::new(ErrorKind::Io, "Failed to copy icons").caused_by(error));
         }
     }
     Ok(())
 }
 
-pub fn remove_icon(icon_path: &PathBuf) -> Result<(), &'static str> {
+pub fn remove_icon(icon_path: &PathBuf) -> Result<()> {
     if icon_path.exists() {
         match fs::remove_file(icon_path) {
             Ok(_) => {
@@ -165,7 +389,7 @@ pub fn remove_icon(icon_path: &PathBuf) -> Result<(), &'static str> {
             }
             Err(e) => {
                 error!("Failed to remove icon: {}", e);
-                return Err("Failed to remove icon");
+                return Err(Error::io(format!("Failed to remove icon {:?}", icon_path), e));
             }
         }
     } else {
@@ -175,60 +399,139 @@ pub fn remove_icon(icon_path: &PathBuf) -> Result<(), &'static str> {
     Ok(())
 }
 
-/// Find all the image files in the given directory
-fn find_image_files(dir: &PathBuf) -> Vec<PathBuf> {
-    let mut image_files = Vec::new();
-    let extensions = vec!["png", "jpg", "jpeg", "svg"];
+/// Find all the image files in the given AppImage
+fn find_image_files(reader: &mut AppImageReader) -> Vec<ImageEntry> {
+    let extensions = vec!["png", "jpg", "jpeg", "svg", "ico"];
 
-    for entry in WalkDir::new(dir) {
-        let entry = entry.unwrap();
-        if entry.file_type().is_file() {
-            if let Some(ext) = entry.path().extension() {
-                if extensions.contains(&ext.to_str().unwrap()) {
-                    image_files.push(entry.path().to_path_buf());
-                }
-            }
+    let entries = match reader.entries() {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to list AppImage content: {}", e);
+            return Vec::new();
         }
+    };
+
+    entries
+        .into_iter()
+        .filter(|entry| entry.kind == EntryKind::File)
+        .filter(|entry| match Path::new(&entry.path).extension() {
+            Some(ext) => extensions.contains(&ext.to_string_lossy().as_ref()),
+            None => false,
+        })
+        .collect()
+}
+
+/// Read the metadata of all the installed app images from the registries of both scopes.
+/// A registry that was never written is listed without hashing the AppImages.
+pub fn read_all_app() -> Result<Vec<InstalledApp>> {
+    let mut apps = Vec::new();
+    for scope in InstallScope::ALL {
+        let registry = match Registry::load_or_list(scope) {
+            Ok(registry) => registry,
+            Err(err) => {
+                error!("Failed to read the {} registry: {}", scope, err);
+                continue;
+            }
+        };
+
+        apps.extend(registry.apps().iter().map(|app| InstalledApp {
+            id: app.id.clone(),
+            name: app.name.clone(),
+            app_path: app.app_image_path.to_string_lossy().to_string(),
+            size: app.size,
+            version: app.version.clone(),
+            categories: app.categories.clone(),
+            icon_path: list_icon(&app.icon_paths),
+            update_info: app.update_info.clone(),
+            scope: app.scope,
+            versions: app.versions.clone(),
+        }));
     }
 
-    image_files
+    Ok(apps)
 }
 
-/// Detect all installed app images and reads it's metadata
-pub fn read_all_app() -> Result<Vec<InstalledApp>, String> {
-    let mut apps: Vec<InstalledApp> = Vec::new();
+/// The icon shown in the lists of apps, the 64x64 PNG icon when there is one
+fn list_icon(icon_paths: &[PathBuf]) -> Option<PathBuf> {
+    let is_png = |path: &&PathBuf| path.extension().is_some_and(|extension| extension == "png");
+    icon_paths
+        .iter()
+        .filter(is_png)
+        .find(|path| path.components().any(|component| component.as_os_str() == "64x64"))
+        .or_else(|| icon_paths.iter().find(is_png))
+        .or_else(|| icon_paths.first())
+        .cloned()
+}
 
-    // read all .desktop files in the applications directory
-    let applications_dir: PathBuf = find_desktop_file_location().map_err(|e| e.to_string())?;
+/// Read everything known about an installed app
+pub fn read_app_info(app: RegistryEntry) -> AppInfo {
+    let desktop_entry = match fs::read_to_string(&app.desktop_file_path) {
+        Ok(content) => Some(desktop_entry_keys(&content)),
+        Err(e) => {
+            error!("Failed to read desktop entry {:?}: {}", app.desktop_file_path, e);
+            None
+        }
+    };
 
-    match std::fs::read_dir(applications_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = entry.as_ref().unwrap();
-                let desktop_file = DesktopFileBuilder::from_desktop_entry_path(&entry.path(), true);
-                if desktop_file.is_err() {
-                    error!(
-                        "Failed to read desktop file: {}",
-                        desktop_file.err().unwrap()
-                    );
-                    continue;
-                }
+    AppInfo { app, desktop_entry }
+}
 
-                let desktop_entry = desktop_file.unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{png_icon, AppImageFixture};
 
-                apps.push(InstalledApp {
-                    name: desktop_entry.name().unwrap(),
-                    app_path: desktop_entry.exec().unwrap(),
-                    version: desktop_entry.version(),
-                    categories: desktop_entry.categories(),
-                    icon_path: Some(PathBuf::from(desktop_entry.icon().unwrap())),
-                });
-            }
-        }
-        Err(err) => {
-            return Err(format!("Failed to read directory: {}", err));
+    fn encode(image: &DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut content = Cursor::new(Vec::new());
+        image.write_to(&mut content, format).unwrap();
+        content.into_inner()
+    }
+
+    fn sizes(icons: &[ThemeIcon]) -> Vec<&str> {
+        icons.iter().map(|icon| icon.size_dir.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize_icons() {
+        // a huge PNG is only used to render the standard sizes
+        let icons = normalize_icons(vec![("big.png".to_string(), png_icon(1000))]);
+        assert_eq!(sizes(&icons), ["256x256", "128x128", "64x64", "32x32"]);
+        for (icon, size) in icons.iter().zip([256, 128, 64, 32]) {
+            let image = image::load_from_memory(&icon.content).unwrap();
+            assert_eq!((image.width(), image.height()), (size, size));
         }
+
+        // JPEG and ICO images are converted, undecodable images ignored
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(48, 24, image::Rgba([255, 0, 0, 255])));
+        let jpeg = encode(&image.to_rgb8().into(), ImageOutputFormat::Jpeg(90));
+        let icons = normalize_icons(vec![
+            ("broken.png".to_string(), b"\x89PNG\r\n\x1a\nbroken".to_vec()),
+            ("icon.jpg".to_string(), jpeg),
+        ]);
+        assert_eq!(sizes(&icons), ["256x256", "128x128", "64x64", "32x32"]);
+        let ico = encode(&image, ImageOutputFormat::Ico);
+        let icons = normalize_icons(vec![("icon.ico".to_string(), ico)]);
+        let rendered = image::load_from_memory(&icons[3].content).unwrap().to_rgba8();
+        // the wide image is centered on a transparent square
+        assert_eq!(rendered.get_pixel(16, 0)[3], 0);
+        assert_eq!(rendered.get_pixel(16, 16)[0], 255);
+
+        assert!(normalize_icons(vec![("empty.svg".to_string(), b"<svg".to_vec())]).is_empty());
     }
 
-    Ok(apps)
+    #[test]
+    fn test_letter_avatar_fallback() {
+        let mut fixture = AppImageFixture::new();
+        fixture.file("AppRun", b"#!/bin/sh\n");
+        let path = tempfile::NamedTempFile::new().unwrap();
+        fixture.write_to(path.path());
+        let mut reader = AppImageReader::open(path.path()).unwrap();
+
+        let icons = collect_theme_icons(&mut reader, Some("missing"), "Krita");
+        assert_eq!(sizes(&icons), ["256x256", "128x128", "64x64", "32x32", "scalable"]);
+        assert_eq!(icons[4].content, letter_avatar_svg("Krita").into_bytes());
+        let rendered = image::load_from_memory(&icons[2].content).unwrap().to_rgba8();
+        assert_eq!(rendered.get_pixel(0, 0)[3], 0);
+        assert_eq!(rendered.get_pixel(32, 8)[3], 255);
+    }
 }
@@ -0,0 +1,304 @@
+use crate::error::Error;
+use crate::install_scope::InstallScope;
+use crate::models::app_info::AppInfo;
+use crate::models::installed_app::InstalledApp;
+use crate::models::verify_report::VerifyReport;
+use crate::registry::InstalledVersion;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// The line the GUI writes on the stdin of `app_hub_backend --output json` to cancel
+/// the running install
+pub const CANCEL_REQUEST: &str = "cancel";
+
+/// An event written by `app_hub_backend --output json`, one JSON object per line on stdout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum BackendEvent {
+    Progress { message: String },
+    InstallPhase(InstallPhase),
+    Warning { message: String },
+    /// An AppImage of a batch install started or finished installing
+    BatchFile(BatchFile),
+    /// The failure of the action: the `code` is the exit code, the error tells what failed
+    Error {
+        code: ErrorCode,
+        #[serde(flatten)]
+        error: Error,
+    },
+    Result { result: ActionResult },
+}
+
+impl BackendEvent {
+    /// Parse a line of the backend output. Lines that are not events are ignored.
+    pub fn parse(line: &str) -> Option<Self> {
+        serde_json::from_str(line).ok()
+    }
+
+    /// The event as a single line of JSON
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// The phases of an install, in the order they happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "phase", rename_all = "kebab-case")]
+pub enum InstallPhase {
+    /// Copying the AppImage into the installation directory. The copy is checked and
+    /// read, so that the file can't change between the checks and the install.
+    #[serde(rename_all = "camelCase")]
+    Copying { done_bytes: u64, total_bytes: u64 },
+    /// Hashing the AppImage and checking its signature
+    Hashing,
+    /// Reading the desktop entry and the metadata of the AppImage
+    Inspecting,
+    /// Preparing the icons
+    Icons,
+    /// Writing the desktop entry
+    DesktopEntry,
+    /// Moving the files into place and saving the registry, the install can no longer
+    /// be cancelled
+    Committing,
+}
+
+impl InstallPhase {
+    /// How much of the whole install is done when the phase is reached, from 0 to 1.
+    /// Copying takes most of the time, the progress moves with the copied bytes.
+    pub fn fraction(&self) -> f32 {
+        match self {
+            InstallPhase::Copying {
+                done_bytes,
+                total_bytes,
+            } => {
+                let copied = match total_bytes {
+                    0 => 1.0,
+                    total => *done_bytes as f32 / *total as f32,
+                };
+                0.7 * copied.min(1.0)
+            }
+            InstallPhase::Hashing => 0.7,
+            InstallPhase::Inspecting => 0.8,
+            InstallPhase::Icons => 0.85,
+            InstallPhase::DesktopEntry => 0.9,
+            InstallPhase::Committing => 0.95,
+        }
+    }
+}
+
+/// An AppImage of a batch install, with how far its install went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFile {
+    /// The position of the AppImage in the batch
+    pub index: usize,
+    pub file_path: PathBuf,
+    pub status: BatchFileStatus,
+}
+
+impl BatchFile {
+    /// The name of the AppImage file, to show to the user
+    pub fn file_name(&self) -> String {
+        self.file_path
+            .file_name()
+            .unwrap_or(self.file_path.as_os_str())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// How far the install of an AppImage of a batch went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum BatchFileStatus {
+    Installing,
+    #[serde(rename_all = "camelCase")]
+    Installed {
+        id: String,
+        name: String,
+        app_image_path: PathBuf,
+    },
+    /// The install failed and was undone, the batch went on with the next AppImage
+    Failed {
+        #[serde(flatten)]
+        error: Error,
+    },
+}
+
+/// What a successful backend action did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum ActionResult {
+    #[serde(rename_all = "camelCase")]
+    Installed {
+        id: String,
+        name: String,
+        app_image_path: PathBuf,
+        scope: InstallScope,
+    },
+    /// The outcome of each AppImage of a batch install, in the order they were given
+    BatchInstalled { files: Vec<BatchFile> },
+    Uninstalled { id: String },
+    #[serde(rename_all = "camelCase")]
+    Moved { install_dir: PathBuf },
+    UpToDate { id: String },
+    #[serde(rename_all = "camelCase")]
+    Updated {
+        id: String,
+        app_image_path: PathBuf,
+        reused_bytes: u64,
+        downloaded_bytes: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    RolledBack {
+        id: String,
+        version: Option<String>,
+        app_image_path: PathBuf,
+    },
+    Pruned {
+        id: String,
+        removed: Vec<InstalledVersion>,
+    },
+    RegistryRebuilt { apps: usize },
+    Listed { apps: Vec<InstalledApp> },
+    Info { app: Box<AppInfo> },
+    Verified { reports: Vec<VerifyReport> },
+}
+
+/// The class of a backend failure. Each class has its own exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    /// Unexpected failure, exit code 1
+    Failure,
+    /// Missing or invalid arguments, exit code 2
+    InvalidArguments,
+    /// The AppImage file or the installed app does not exist, exit code 3
+    NotFound,
+    /// The AppImage could not be installed, exit code 4
+    InstallFailed,
+    /// The app could not be uninstalled, exit code 5
+    UninstallFailed,
+    /// The app or the installation directory could not be updated, exit code 6
+    UpdateFailed,
+    /// The registry could not be read or written, exit code 7
+    RegistryFailed,
+    /// Installed files are missing or do not match the registry, exit code 8
+    VerifyFailed,
+    /// The action was cancelled and undone, exit code 9
+    Cancelled,
+    /// The app is already installed in the scope, exit code 10
+    AlreadyInstalled,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 10] = [
+        ErrorCode::Failure,
+        ErrorCode::InvalidArguments,
+        ErrorCode::NotFound,
+        ErrorCode::InstallFailed,
+        ErrorCode::UninstallFailed,
+        ErrorCode::UpdateFailed,
+        ErrorCode::RegistryFailed,
+        ErrorCode::VerifyFailed,
+        ErrorCode::Cancelled,
+        ErrorCode::AlreadyInstalled,
+    ];
+
+    /// The exit code of the backend process
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorCode::Failure => 1,
+            ErrorCode::InvalidArguments => 2,
+            ErrorCode::NotFound => 3,
+            ErrorCode::InstallFailed => 4,
+            ErrorCode::UninstallFailed => 5,
+            ErrorCode::UpdateFailed => 6,
+            ErrorCode::RegistryFailed => 7,
+            ErrorCode::VerifyFailed => 8,
+            ErrorCode::Cancelled => 9,
+            ErrorCode::AlreadyInstalled => 10,
+        }
+    }
+
+    /// The error code matching an exit code of the backend process
+    pub fn from_exit_code(exit_code: i32) -> Option<Self> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|code| i32::from(code.exit_code()) == exit_code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_event_round_trip() {
+        let events = [
+            BackendEvent::Progress {
+                message: "Installing".to_string(),
+            },
+            BackendEvent::InstallPhase(InstallPhase::Copying {
+                done_bytes: 5,
+                total_bytes: 10,
+            }),
+            BackendEvent::InstallPhase(InstallPhase::DesktopEntry),
+            BackendEvent::Error {
+                code: ErrorCode::NotFound,
+                error: Error::new(ErrorKind::NotFound, "App not found: test")
+                    .for_app("test")
+                    .caused_by("No such file or directory"),
+            },
+            BackendEvent::BatchFile(BatchFile {
+                index: 1,
+                file_path: PathBuf::from("/tmp/Test.AppImage"),
+                status: BatchFileStatus::Failed {
+                    error: Error::new(ErrorKind::AlreadyInstalled, "Test 1.0 is already installed"),
+                },
+            }),
+            BackendEvent::Result {
+                result: ActionResult::Updated {
+                    id: "test".to_string(),
+                    app_image_path: PathBuf::from("/opt/test.AppImage"),
+                    reused_bytes: 10,
+                    downloaded_bytes: 2,
+                },
+            },
+        ];
+
+        for event in events {
+            assert_eq!(BackendEvent::parse(&event.to_json_line()), Some(event));
+        }
+        assert_eq!(
+            BackendEvent::Error {
+                code: ErrorCode::InstallFailed,
+                error: Error::other("failed")
+            }
+            .to_json_line(),
+            r#"{"event":"error","code":"install-failed","kind":"other","message":"failed"}"#
+        );
+        assert_eq!(
+            BackendEvent::InstallPhase(InstallPhase::Copying {
+                done_bytes: 5,
+                total_bytes: 10
+            })
+            .to_json_line(),
+            r#"{"event":"install-phase","phase":"copying","doneBytes":5,"totalBytes":10}"#
+        );
+        assert_eq!(BackendEvent::parse("[INFO] Starting AppHub backend"), None);
+        assert_eq!(ErrorCode::from_exit_code(3), Some(ErrorCode::NotFound));
+    }
+}
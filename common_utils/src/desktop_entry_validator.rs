@@ -0,0 +1,475 @@
+use crate::desktop_file_parser::{unescape, DesktopFile, DesktopGroup, DESKTOP_ENTRY_GROUP};
+use crate::exec_command::{ExecCommand, FILE_FIELD_CODES};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// The keys defined by the Desktop Entry Specification
+const KNOWN_KEYS: &[&str] = &[
+    "Type",
+    "Version",
+    "Name",
+    "GenericName",
+    "NoDisplay",
+    "Comment",
+    "Icon",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "DBusActivatable",
+    "TryExec",
+    "Exec",
+    "Path",
+    "Terminal",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+    "StartupNotify",
+    "StartupWMClass",
+    "URL",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+    // deprecated, still found in many files
+    "Encoding",
+    "MiniIcon",
+    "TerminalOptions",
+];
+
+const BOOLEAN_KEYS: &[&str] = &[
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+    "X-AppHub",
+];
+
+const KNOWN_VERSIONS: &[&str] = &["1.0", "1.1", "1.2", "1.3", "1.4", "1.5"];
+
+const MAIN_CATEGORIES: &[&str] = &[
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+const ADDITIONAL_CATEGORIES: &[&str] = &[
+    "Building", "Debugger", "IDE", "GUIDesigner", "Profiling", "RevisionControl",
+    "Translation", "Calendar", "ContactManagement", "Database", "Dictionary", "Chart",
+    "Email", "Finance", "FlowChart", "PDA", "ProjectManagement", "Presentation",
+    "Spreadsheet", "WordProcessor", "2DGraphics", "VectorGraphics", "RasterGraphics",
+    "3DGraphics", "Scanning", "OCR", "Photography", "Publishing", "Viewer", "TextTools",
+    "DesktopSettings", "HardwareSettings", "Printing", "PackageManager", "Dialup",
+    "InstantMessaging", "Chat", "IRCClient", "Feed", "FileTransfer", "HamRadio", "News",
+    "P2P", "RemoteAccess", "Telephony", "TelephonyTools", "VideoConference",
+    "WebBrowser", "WebDevelopment", "Midi", "Mixer", "Sequencer", "Tuner", "TV",
+    "AudioVideoEditing", "Player", "Recorder", "DiscBurning", "ActionGame",
+    "AdventureGame", "ArcadeGame", "BoardGame", "BlocksGame", "CardGame", "KidsGame",
+    "LogicGame", "RolePlaying", "Shooter", "Simulation", "SportsGame", "StrategyGame",
+    "Art", "Construction", "Music", "Languages", "ArtificialIntelligence", "Astronomy",
+    "Biology", "Chemistry", "ComputerScience", "DataVisualization", "Economy",
+    "Electricity", "Geography", "Geology", "Geoscience", "History", "Humanities",
+    "ImageProcessing", "Literature", "Maps", "Math", "NumericalAnalysis",
+    "MedicalSoftware", "Physics", "Robotics", "Spirituality", "Sports",
+    "ParallelComputing", "Amusement", "Archiving", "Compression", "Electronics",
+    "Emulator", "Engineering", "FileTools", "FileManager", "TerminalEmulator",
+    "Filesystem", "Monitor", "Security", "Accessibility", "Calculator", "Clock",
+    "TextEditor", "Documentation", "Adult", "Core", "KDE", "GNOME", "XFCE", "DDE", "GTK",
+    "Qt", "Motif", "Java", "ConsoleOnly", "Screensaver", "TrayIcon", "Applet", "Shell",
+];
+
+const FIELD_CODES: [char; 8] = ['f', 'F', 'u', 'U', 'i', 'c', 'k', '%'];
+const DEPRECATED_FIELD_CODES: [char; 6] = ['d', 'D', 'n', 'N', 'v', 'm'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a desktop file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub group: Option<String>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        if let Some(group) = &self.group {
+            write!(f, "[{}] ", group)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The diagnostics of a desktop file, in the order they were found
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validation {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Validation {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    fn push(&mut self, severity: Severity, group: &str, key: Option<&str>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            group: Some(group.to_string()),
+            key: key.map(str::to_string),
+            message,
+        });
+    }
+
+    fn error(&mut self, group: &str, key: Option<&str>, message: impl Into<String>) {
+        self.push(Severity::Error, group, key, message.into());
+    }
+
+    fn warning(&mut self, group: &str, key: Option<&str>, message: impl Into<String>) {
+        self.push(Severity::Warning, group, key, message.into());
+    }
+}
+
+/// Validate the content of a desktop file against the Desktop Entry Specification
+pub fn validate_desktop_entry(content: &str) -> Validation {
+    match DesktopFile::parse(content) {
+        Ok(desktop_file) => validate_desktop_file(&desktop_file),
+        Err(e) => Validation {
+            diagnostics: vec![Diagnostic {
+                severity: Severity::Error,
+                group: None,
+                key: None,
+                message: e.to_string(),
+            }],
+        },
+    }
+}
+
+/// Validate a parsed desktop file against the Desktop Entry Specification
+pub fn validate_desktop_file(desktop_file: &DesktopFile) -> Validation {
+    let mut validation = Validation::default();
+
+    let desktop_entry = match desktop_file.groups().first() {
+        Some(group) if group.name() == DESKTOP_ENTRY_GROUP => group,
+        _ => {
+            validation.error(
+                DESKTOP_ENTRY_GROUP,
+                None,
+                "The first group must be [Desktop Entry]",
+            );
+            match desktop_file.desktop_entry() {
+                Some(group) => group,
+                None => return validation,
+            }
+        }
+    };
+
+    check_keys(&mut validation, desktop_entry, true);
+
+    match desktop_entry.get("Type") {
+        None => validation.error(DESKTOP_ENTRY_GROUP, Some("Type"), "Required key is missing"),
+        Some("Application") => {
+            let dbus_activatable = desktop_entry.get_bool("DBusActivatable") == Some(true);
+            if desktop_entry.get("Exec").is_none() && !dbus_activatable {
+                validation.error(
+                    DESKTOP_ENTRY_GROUP,
+                    Some("Exec"),
+                    "Required key is missing for Type=Application",
+                );
+            }
+        }
+        Some("Link") => {
+            if desktop_entry.get("URL").is_none() {
+                validation.error(
+                    DESKTOP_ENTRY_GROUP,
+                    Some("URL"),
+                    "Required key is missing for Type=Link",
+                );
+            }
+        }
+        Some("Directory") => {}
+        Some(type_) => validation.error(
+            DESKTOP_ENTRY_GROUP,
+            Some("Type"),
+            format!("Invalid type {}, expected Application, Link or Directory", type_),
+        ),
+    }
+
+    if desktop_entry.get("Name").is_none() {
+        validation.error(DESKTOP_ENTRY_GROUP, Some("Name"), "Required key is missing");
+    }
+
+    if let Some(version) = desktop_entry.get("Version") {
+        if !KNOWN_VERSIONS.contains(&version) {
+            validation.warning(
+                DESKTOP_ENTRY_GROUP,
+                Some("Version"),
+                format!("Unknown specification version {}", version),
+            );
+        }
+    }
+
+    if let Some(categories) = desktop_entry.get_list("Categories") {
+        check_categories(&mut validation, &categories);
+    }
+
+    // the actions listed in the desktop entry and their groups
+    let actions = desktop_entry.get_list("Actions").unwrap_or_default();
+    for action in &actions {
+        let group_name = format!("Desktop Action {}", action);
+        match desktop_file.group(&group_name) {
+            Some(group) => {
+                check_keys(&mut validation, group, false);
+                if group.get("Name").is_none() {
+                    validation.error(&group_name, Some("Name"), "Required key is missing");
+                }
+            }
+            None => validation.error(
+                DESKTOP_ENTRY_GROUP,
+                Some("Actions"),
+                format!("The action {} has no [{}] group", action, group_name),
+            ),
+        }
+    }
+    for group in desktop_file.groups() {
+        if let Some(action) = group.name().strip_prefix("Desktop Action ") {
+            if !actions.iter().any(|listed| listed == action) {
+                validation.warning(group.name(), None, "The action is not listed in Actions");
+            }
+        } else if group.name() != DESKTOP_ENTRY_GROUP && !group.name().starts_with("X-") {
+            validation.warning(
+                group.name(),
+                None,
+                "Unknown group, extension groups must start with X-",
+            );
+        }
+    }
+
+    validation
+}
+
+/// Check the keys of a group: duplicates, unknown keys, booleans, Exec and Icon
+fn check_keys(validation: &mut Validation, group: &DesktopGroup, is_desktop_entry: bool) {
+    let mut seen = HashSet::new();
+    for (key, locale, value) in group.entries() {
+        if !seen.insert((key, locale)) {
+            validation.error(group.name(), Some(key), "Duplicate key");
+            continue;
+        }
+        if locale.is_some() {
+            continue;
+        }
+
+        if is_desktop_entry && !KNOWN_KEYS.contains(&key) && !key.starts_with("X-") {
+            validation.warning(
+                group.name(),
+                Some(key),
+                "Unknown key, extension keys must start with X-",
+            );
+        }
+
+        if BOOLEAN_KEYS.contains(&key) {
+            match value {
+                "true" | "false" => {}
+                "0" | "1" => validation.warning(
+                    group.name(),
+                    Some(key),
+                    format!("Deprecated boolean value {}, use true or false", value),
+                ),
+                _ => validation.error(
+                    group.name(),
+                    Some(key),
+                    format!("Invalid boolean value {}, expected true or false", value),
+                ),
+            }
+        }
+
+        match key {
+            "Exec" => check_exec(validation, group.name(), value),
+            "Icon" => check_icon(validation, group.name(), value),
+            _ => {}
+        }
+    }
+}
+
+/// Check the field codes of an Exec key
+fn check_exec(validation: &mut Validation, group: &str, exec: &str) {
+    if let Err(e) = ExecCommand::parse(&unescape(exec)) {
+        validation.error(group, Some("Exec"), e.to_string());
+        return;
+    }
+
+    let mut file_field_codes = 0;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some(code) if FILE_FIELD_CODES.contains(&code) => file_field_codes += 1,
+            Some(code) if FIELD_CODES.contains(&code) => {}
+            Some(code) if DEPRECATED_FIELD_CODES.contains(&code) => validation.warning(
+                group,
+                Some("Exec"),
+                format!("Deprecated field code %{}", code),
+            ),
+            Some(code) => validation.error(
+                group,
+                Some("Exec"),
+                format!("Invalid field code %{}, use %% for a literal %", code),
+            ),
+            None => validation.error(
+                group,
+                Some("Exec"),
+                "Incomplete field code at the end of the command",
+            ),
+        }
+    }
+    if file_field_codes > 1 {
+        validation.error(
+            group,
+            Some("Exec"),
+            "Only one of the field codes %f, %F, %u and %U may be used",
+        );
+    }
+}
+
+/// Check that an Icon key is an absolute path or an icon name
+fn check_icon(validation: &mut Validation, group: &str, icon: &str) {
+    let path = Path::new(icon);
+    if path.is_absolute() {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        if !matches!(extension.as_deref(), Some("png" | "svg" | "svgz" | "xpm")) {
+            validation.warning(
+                group,
+                Some("Icon"),
+                format!("{} is not a PNG, SVG or XPM image", icon),
+            );
+        }
+    } else if icon.contains('/') {
+        validation.error(
+            group,
+            Some("Icon"),
+            format!("{} must be an absolute path or an icon name", icon),
+        );
+    } else if icon.ends_with(".png") || icon.ends_with(".svg") || icon.ends_with(".xpm") {
+        validation.warning(
+            group,
+            Some("Icon"),
+            format!("The icon name {} should not have an extension", icon),
+        );
+    }
+}
+
+fn check_categories(validation: &mut Validation, categories: &[String]) {
+    for category in categories {
+        let registered = MAIN_CATEGORIES.contains(&category.as_str())
+            || ADDITIONAL_CATEGORIES.contains(&category.as_str());
+        if !registered && !category.starts_with("X-") {
+            validation.warning(
+                DESKTOP_ENTRY_GROUP,
+                Some("Categories"),
+                format!("Unregistered category {}", category),
+            );
+        }
+    }
+    if !categories
+        .iter()
+        .any(|category| MAIN_CATEGORIES.contains(&category.as_str()))
+    {
+        validation.warning(
+            DESKTOP_ENTRY_GROUP,
+            Some("Categories"),
+            "No main category, the app may not be shown in the menus",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<String> {
+        validate_desktop_entry(content)
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_desktop_entry() {
+        let content = "[Desktop Entry]\nType=Application\nName=Test\nExec=/opt/Test.AppImage %U\nIcon=/usr/share/icons/test.png\nCategories=Utility;TextEditor;\nTerminal=false\nActions=new;\n\n[Desktop Action new]\nName=New\nExec=/opt/Test.AppImage --new\n";
+        assert_eq!(messages(content), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let content = "[Desktop Entry]\nType=Program\nExec=test %f %U %x %d\nTerminal=yes\nNoDisplay=0\nIcon=icons/test.png\nCategories=Foo;\nFoo=bar\nActions=new;\n";
+        assert_eq!(
+            messages(content),
+            vec![
+                "error: [Desktop Entry] Exec: Invalid field code %x, use %% for a literal %",
+                "warning: [Desktop Entry] Exec: Deprecated field code %d",
+                "error: [Desktop Entry] Exec: Only one of the field codes %f, %F, %u and %U may be used",
+                "error: [Desktop Entry] Terminal: Invalid boolean value yes, expected true or false",
+                "warning: [Desktop Entry] NoDisplay: Deprecated boolean value 0, use true or false",
+                "error: [Desktop Entry] Icon: icons/test.png must be an absolute path or an icon name",
+                "warning: [Desktop Entry] Foo: Unknown key, extension keys must start with X-",
+                "error: [Desktop Entry] Type: Invalid type Program, expected Application, Link or Directory",
+                "error: [Desktop Entry] Name: Required key is missing",
+                "warning: [Desktop Entry] Categories: Unregistered category Foo",
+                "warning: [Desktop Entry] Categories: No main category, the app may not be shown in the menus",
+                "error: [Desktop Entry] Actions: The action new has no [Desktop Action new] group",
+            ]
+        );
+        assert!(validate_desktop_entry("Name=Test\n").has_errors());
+        assert!(validate_desktop_entry("[Desktop Entry]\nType=Application\nName=Test\nExec=\"/opt/My Apps/test\n").has_errors());
+    }
+}
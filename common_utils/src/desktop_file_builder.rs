@@ -1,6 +1,43 @@
-use std::path::PathBuf;
-use regex::Regex;
-use log::info;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use crate::desktop_file_parser::DesktopFile;
+use crate::error::{Error, ErrorKind, Result};
+use crate::exec_command::ExecCommand;
+
+const TYPE: &str = "Type";
+const VERSION: &str = "Version";
+const NAME: &str = "Name";
+const COMMENT: &str = "Comment";
+const PATH: &str = "Path";
+const EXEC: &str = "Exec";
+const TRY_EXEC: &str = "TryExec";
+const GENERIC_NAME: &str = "GenericName";
+const MIME_TYPE: &str = "MimeType";
+const STARTUP_WM_CLASS: &str = "StartupWMClass";
+const KEYWORDS: &str = "Keywords";
+const ACTIONS: &str = "Actions";
+const ICON: &str = "Icon";
+const TERMINAL: &str = "Terminal";
+const CATEGORIES: &str = "Categories";
+const X_APPIMAGE_VERSION: &str = "X-AppImage-Version";
+const X_APPHUB: &str = "X-AppHub";
+const X_APPHUB_UPDATE_INFORMATION: &str = "X-AppHub-UpdateInformation";
+
+/// An action of the application, read from its `[Desktop Action <id>]` group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopAction {
+    pub id: String,
+    pub name: Option<String>,
+    pub exec: Option<String>,
+    pub icon: Option<String>,
+}
+
+impl DesktopAction {
+    fn group_name(&self) -> String {
+        format!("Desktop Action {}", self.id)
+    }
+}
 
 pub struct DesktopFileBuilder {
     /// The `type_` field represents the type of the application. It's usually "Application" for desktop applications.
@@ -32,6 +69,36 @@ pub struct DesktopFileBuilder {
 
     /// The `no_sandbox` field indicates whether the application should be run in a sandbox.
     no_sanbox: Option<bool>,
+
+    /// The `app_image_version` field represents the version of the application packaged in the AppImage.
+    app_image_version: Option<String>,
+
+    /// The `update_information` field holds the update information embedded in the AppImage.
+    update_information: Option<String>,
+
+    /// The `try_exec` field represents the executable used to check whether the application is installed.
+    try_exec: Option<String>,
+
+    /// The `generic_name` field represents the generic name of the application, like "Web Browser".
+    generic_name: Option<String>,
+
+    /// The `localized_names` field holds the translations of the name, by locale.
+    localized_names: BTreeMap<String, String>,
+
+    /// The `mime_types` field represents the MIME types the application can open.
+    mime_types: Option<Vec<String>>,
+
+    /// The `startup_wm_class` field represents the window class, used by docks to group the windows of the application.
+    startup_wm_class: Option<String>,
+
+    /// The `keywords` field represents additional words used to search the application.
+    keywords: Option<Vec<String>>,
+
+    /// The `actions` field represents the additional actions of the application, shown in its jump list.
+    actions: Vec<DesktopAction>,
+
+    /// The parsed desktop file the builder was read from, written back with the changes.
+    source: Option<DesktopFile>,
 }
 
 impl DesktopFileBuilder {
@@ -47,76 +114,150 @@ impl DesktopFileBuilder {
             terminal: None,
             categories: None,
             no_sanbox: None,
+            app_image_version: None,
+            update_information: None,
+            try_exec: None,
+            generic_name: None,
+            localized_names: BTreeMap::new(),
+            mime_types: None,
+            startup_wm_class: None,
+            keywords: None,
+            actions: Vec::new(),
+            source: None,
         }
     }
 
     /// Read a .desktop file from a given path and return a DesktopFileBuilder instance.
-    pub fn from_desktop_entry_path(path: &PathBuf, is_app_hub_app: bool) -> Result<Self, &'static str> {
+    pub fn from_desktop_entry_path(path: &PathBuf, is_app_hub_app: bool) -> Result<Self> {
         // Read the file content
         let file_content = match std::fs::read_to_string(&path) {
             Ok(content) => content,
             Err(e) => {
                 log::error!("Failed to read file: {}", e);
-                return Err("Failed to read .desktop file");
+                return Err(Error::io(format!("Failed to read {:?}", path), e));
             }
         };
 
-        // Check if the file content contains the AppHub specific field
-        if is_app_hub_app && !file_content.contains("X-AppHub=true") {
-            return Err("Invalid .desktop file");
+        let desktop_file_builder = Self::from_desktop_entry_content(&file_content, is_app_hub_app)?;
+        info!("Successfully parsed .desktop file from path: {:?}", path);
+        Ok(desktop_file_builder)
+    }
+
+    /// Parse the content of a .desktop file and return a DesktopFileBuilder instance.
+    /// The parsed file is kept, so that the keys and groups the builder does not know about
+    /// are written back as they are.
+    pub fn from_desktop_entry_content(file_content: &str, is_app_hub_app: bool) -> Result<Self> {
+        let desktop_file = match DesktopFile::parse(file_content) {
+            Ok(desktop_file) => desktop_file,
+            Err(e) => {
+                log::error!("Failed to parse .desktop file: {}", e);
+                return Err(e.context("Invalid .desktop file"));
+            }
+        };
+        let desktop_entry = desktop_file
+            .desktop_entry()
+            .ok_or_else(|| {
+                Error::new(ErrorKind::InvalidDesktopEntry, "Missing [Desktop Entry] group")
+            })?;
+
+        // Check if the desktop entry contains the AppHub specific field
+        if is_app_hub_app && desktop_entry.get(X_APPHUB) != Some("true") {
+            return Err(Error::new(
+                ErrorKind::InvalidDesktopEntry,
+                "The desktop entry was not written by AppHub",
+            ));
         }
 
-        // Parse the file content
         let mut desktop_file_builder = DesktopFileBuilder::new();
 
-        let re_type = Regex::new(r"(?m)Type=(.*)$").unwrap();
-        let re_version = Regex::new(r"(?m)Version=(.*)$").unwrap();
-        let re_name = Regex::new(r"(?m)Name=(.*)$").unwrap();
-        let re_comment = Regex::new(r"(?m)Comment=(.*)$").unwrap();
-        let re_path = Regex::new(r"(?m)Path=(.*)$").unwrap();
-        let re_exec = Regex::new(r"(?m)Exec=(.*)$").unwrap();
-        let re_icon = Regex::new(r"(?m)Icon=(.*)$").unwrap();
-        let re_terminal = Regex::new(r"(?m)Terminal=(.*)$").unwrap();
-        let re_categories = Regex::new(r"(?m)Categories=(.*)$").unwrap();
-
-        if let Some(cap) = re_type.captures(&file_content) {
-            info!("Setting 'Type' to '{}'", &cap[1]);
-            desktop_file_builder.set_type(cap[1].to_string());
+        if let Some(type_) = desktop_entry.get_string(TYPE) {
+            info!("Setting 'Type' to '{}'", type_);
+            desktop_file_builder.set_type(type_);
+        }
+        if let Some(version) = desktop_entry.get_string(VERSION) {
+            info!("Setting 'Version' to '{}'", version);
+            desktop_file_builder.set_version(version);
+        }
+        if let Some(name) = desktop_entry.get_string(NAME) {
+            info!("Setting 'Name' to '{}'", name);
+            desktop_file_builder.set_name(name);
         }
-        if let Some(cap) = re_version.captures(&file_content) {
-            info!("Setting 'Version' to '{}'", &cap[1]);
-            desktop_file_builder.set_version(cap[1].to_string());
+        if let Some(comment) = desktop_entry.get_string(COMMENT) {
+            info!("Setting 'Comment' to '{}'", comment);
+            desktop_file_builder.set_comment(comment);
         }
-        if let Some(cap) = re_name.captures(&file_content) {
-            info!("Setting 'Name' to '{}'", &cap[1]);
-            desktop_file_builder.set_name(cap[1].to_string());
+        if let Some(path) = desktop_entry.get_string(PATH) {
+            info!("Setting 'Path' to '{}'", path);
+            desktop_file_builder.set_path(path);
         }
-        if let Some(cap) = re_comment.captures(&file_content) {
-            info!("Setting 'Comment' to '{}'", &cap[1]);
-            desktop_file_builder.set_comment(cap[1].to_string());
+        if let Some(exec) = desktop_entry.get_string(EXEC) {
+            info!("Setting 'Exec' to '{}'", exec);
+            desktop_file_builder.set_exec(exec);
         }
-        if let Some(cap) = re_path.captures(&file_content) {
-            info!("Setting 'Path' to '{}'", &cap[1]);
-            desktop_file_builder.set_path(cap[1].to_string());
+        if let Some(icon) = desktop_entry.get_string(ICON) {
+            info!("Setting 'Icon' to '{}'", icon);
+            desktop_file_builder.set_icon(icon);
         }
-        if let Some(cap) = re_exec.captures(&file_content) {
-            info!("Setting 'Exec' to '{}'", &cap[1]);
-            desktop_file_builder.set_exec(cap[1].to_string());
+        if let Some(terminal) = desktop_entry.get_bool(TERMINAL) {
+            info!("Setting 'Terminal' to '{}'", terminal);
+            desktop_file_builder.set_terminal(terminal);
         }
-        if let Some(cap) = re_icon.captures(&file_content) {
-            info!("Setting 'Icon' to '{}'", &cap[1]);
-            desktop_file_builder.set_icon(cap[1].to_string());
+        if let Some(categories) = desktop_entry.get(CATEGORIES) {
+            info!("Setting 'Categories' to '{}'", categories);
+            desktop_file_builder.categories = Some(categories.to_string());
         }
-        if let Some(cap) = re_terminal.captures(&file_content) {
-            info!("Setting 'Terminal' to '{}'", &cap[1]);
-            desktop_file_builder.set_terminal(cap[1].eq("true"));
+        if let Some(app_image_version) = desktop_entry.get_string(X_APPIMAGE_VERSION) {
+            info!("Setting 'X-AppImage-Version' to '{}'", app_image_version);
+            desktop_file_builder.set_app_image_version(app_image_version);
         }
-        if let Some(cap) = re_categories.captures(&file_content) {
-            info!("Setting 'Categories' to '{}'", &cap[1]);
-            desktop_file_builder.set_categories(cap[1].split(";").map(|s| s.to_string()).collect());
+        if let Some(update_information) = desktop_entry.get_string(X_APPHUB_UPDATE_INFORMATION) {
+            info!("Setting 'X-AppHub-UpdateInformation' to '{}'", update_information);
+            desktop_file_builder.set_update_information(update_information);
         }
 
-        info!("Successfully parsed .desktop file from path: {:?}", path);
+        if let Some(try_exec) = desktop_entry.get_string(TRY_EXEC) {
+            info!("Setting 'TryExec' to '{}'", try_exec);
+            desktop_file_builder.set_try_exec(try_exec);
+        }
+        if let Some(generic_name) = desktop_entry.get_string(GENERIC_NAME) {
+            info!("Setting 'GenericName' to '{}'", generic_name);
+            desktop_file_builder.set_generic_name(generic_name);
+        }
+        desktop_file_builder.localized_names = desktop_entry.localized_strings(NAME);
+        if let Some(mime_types) = desktop_entry.get_list(MIME_TYPE) {
+            info!("Setting 'MimeType' to '{:?}'", mime_types);
+            desktop_file_builder.set_mime_types(mime_types);
+        }
+        if let Some(startup_wm_class) = desktop_entry.get_string(STARTUP_WM_CLASS) {
+            info!("Setting 'StartupWMClass' to '{}'", startup_wm_class);
+            desktop_file_builder.set_startup_wm_class(startup_wm_class);
+        }
+        if let Some(keywords) = desktop_entry.get_list(KEYWORDS) {
+            info!("Setting 'Keywords' to '{:?}'", keywords);
+            desktop_file_builder.set_keywords(keywords);
+        }
+        for id in desktop_entry.get_list(ACTIONS).unwrap_or_default() {
+            let action = DesktopAction {
+                id,
+                name: None,
+                exec: None,
+                icon: None,
+            };
+            match desktop_file.group(&action.group_name()) {
+                Some(group) => {
+                    info!("Adding action '{}'", action.id);
+                    desktop_file_builder.add_action(DesktopAction {
+                        name: group.get_string(NAME),
+                        exec: group.get_string(EXEC),
+                        icon: group.get_string(ICON),
+                        ..action
+                    });
+                }
+                None => warn!("Missing group for the desktop action '{}'", action.id),
+            }
+        }
+
+        desktop_file_builder.source = Some(desktop_file);
         Ok(desktop_file_builder)
     }
 
@@ -175,65 +316,226 @@ impl DesktopFileBuilder {
         self
     }
 
+    pub fn set_app_image_version(&mut self, app_image_version: String) -> &mut Self {
+        self.app_image_version = Some(app_image_version);
+        self
+    }
+
+    pub fn set_update_information(&mut self, update_information: String) -> &mut Self {
+        self.update_information = Some(update_information);
+        self
+    }
+
+    pub fn set_try_exec(&mut self, try_exec: String) -> &mut Self {
+        self.try_exec = Some(try_exec);
+        self
+    }
+
+    pub fn set_generic_name(&mut self, generic_name: String) -> &mut Self {
+        self.generic_name = Some(generic_name);
+        self
+    }
+
+    pub fn set_localized_name(&mut self, locale: String, name: String) -> &mut Self {
+        self.localized_names.insert(locale, name);
+        self
+    }
+
+    /// Append a suffix to the name and to each of its translations
+    pub fn add_name_suffix(&mut self, suffix: &str) -> &mut Self {
+        if let Some(name) = &mut self.name {
+            name.push_str(suffix);
+        }
+        for name in self.localized_names.values_mut() {
+            name.push_str(suffix);
+        }
+        self
+    }
+
+    pub fn set_mime_types(&mut self, mime_types: Vec<String>) -> &mut Self {
+        self.mime_types = Some(mime_types);
+        self
+    }
+
+    pub fn set_startup_wm_class(&mut self, startup_wm_class: String) -> &mut Self {
+        self.startup_wm_class = Some(startup_wm_class);
+        self
+    }
+
+    pub fn set_keywords(&mut self, keywords: Vec<String>) -> &mut Self {
+        self.keywords = Some(keywords);
+        self
+    }
+
+    /// Add an action, replacing the action with the same id
+    pub fn add_action(&mut self, action: DesktopAction) -> &mut Self {
+        match self.actions.iter_mut().find(|existing| existing.id == action.id) {
+            Some(existing) => *existing = action,
+            None => self.actions.push(action),
+        }
+        self
+    }
+
+    /// The command line of the Exec field
+    pub fn exec_command(&self) -> Result<ExecCommand> {
+        let exec = self.exec.as_deref().ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, "Exec field is missing"))?;
+        ExecCommand::parse(exec)
+    }
+
+    pub fn set_exec_command(&mut self, exec_command: &ExecCommand) -> &mut Self {
+        self.exec = Some(exec_command.to_string());
+        self
+    }
+
+    /// Change the program run by the Exec and TryExec fields and by every action,
+    /// keeping the arguments and field codes of the commands
+    pub fn map_programs(&mut self, map: impl Fn(&str) -> String) -> &mut Self {
+        let map_exec = |exec: &str| match ExecCommand::parse(exec) {
+            Ok(command) => {
+                let program = map(&command.program);
+                command.with_program(&program).to_string()
+            }
+            Err(e) => {
+                warn!("Replacing the unparsable command '{}': {}", exec, e);
+                ExecCommand::new(&map(exec.trim())).to_string()
+            }
+        };
+        self.exec = self.exec.as_deref().map(map_exec);
+        for action in &mut self.actions {
+            action.exec = action.exec.as_deref().map(map_exec);
+        }
+        self.try_exec = self.try_exec.as_deref().map(&map);
+        self
+    }
+
+    /// Point the commands at an AppImage installed in the given directory, keeping their
+    /// arguments. The directory becomes the working directory of the app.
+    pub fn target_app_image(
+        &mut self,
+        installation_dir: &Path,
+        file_name: &str,
+        no_sandbox: bool,
+    ) -> &mut Self {
+        let program = installation_dir.join(file_name).to_string_lossy().to_string();
+        if self.exec.is_none() {
+            self.set_exec_command(&ExecCommand::new(&program));
+        }
+        self.map_programs(|_| program.clone());
+        self.set_path(installation_dir.to_string_lossy().to_string());
+        if no_sandbox {
+            self.set_no_sandbox(true);
+        }
+        self
+    }
+
     /// Generate the content of the .desktop file as a string.
-    pub fn generate_content_string(self) -> Result<String, &'static str> {
+    pub fn generate_content_string(self) -> Result<String> {
         // Check mandatory fields
         if self.type_.is_none() {
-            return Err("Type field is mandatory");
+            return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Type field is mandatory"));
         }
         if self.name.is_none() {
-            return Err("Name field is mandatory");
+            return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Name field is mandatory"));
         }
         if self.exec.is_none() {
-            return Err("Exec field is mandatory");
+            return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Exec field is mandatory"));
         }
 
-        // Create the file content
-        let mut desktop_file_content = String::from("[Desktop Entry]\n");
+        // Start from the parsed file, if any, so that the other keys and groups are kept
+        let mut desktop_file = self.source.unwrap_or_default();
+        let desktop_entry = desktop_file.desktop_entry_mut();
 
         if let Some(type_) = self.type_ {
-            desktop_file_content.push_str(&format!("Type={}\n", type_));
+            desktop_entry.set_string(TYPE, &type_);
         }
 
         if let Some(version) = self.version {
-            desktop_file_content.push_str(&format!("Version={}\n", version));
+            desktop_entry.set_string(VERSION, &version);
         }
 
         if let Some(name) = self.name {
-            desktop_file_content.push_str(&format!("Name={}\n", name));
+            desktop_entry.set_string(NAME, &name);
         }
 
         if let Some(comment) = self.comment {
-            desktop_file_content.push_str(&format!("Comment={}\n", comment));
+            desktop_entry.set_string(COMMENT, &comment);
         }
 
         if let Some(path) = self.path {
-            desktop_file_content.push_str(&format!("Path={}\n", path));
+            desktop_entry.set_string(PATH, &path);
         }
 
+        let no_sandbox = self.no_sanbox == Some(true);
         if let Some(exec) = self.exec {
-            if self.no_sanbox.is_some() && self.no_sanbox.unwrap() {
-                desktop_file_content.push_str(&format!("Exec={} --no-sandbox\n", exec));
-            } else {
-                desktop_file_content.push_str(&format!("Exec={}\n", exec));
-            }
+            desktop_entry.set_string(EXEC, &with_no_sandbox(&exec, no_sandbox));
         }
 
         if let Some(icon) = self.icon {
-            desktop_file_content.push_str(&format!("Icon={}\n", icon));
+            desktop_entry.set_string(ICON, &icon);
         }
 
         if let Some(terminal) = self.terminal {
-            desktop_file_content.push_str(&format!("Terminal={}\n", terminal));
+            desktop_entry.set_bool(TERMINAL, terminal);
         }
 
         if let Some(categories) = self.categories {
-            desktop_file_content.push_str(&format!("Categories={}\n", categories));
+            desktop_entry.set(CATEGORIES, &categories);
+        }
+
+        if let Some(app_image_version) = self.app_image_version {
+            desktop_entry.set_string(X_APPIMAGE_VERSION, &app_image_version);
+        }
+
+        if let Some(try_exec) = self.try_exec {
+            desktop_entry.set_string(TRY_EXEC, &try_exec);
+        }
+
+        if let Some(generic_name) = self.generic_name {
+            desktop_entry.set_string(GENERIC_NAME, &generic_name);
+        }
+
+        for (locale, name) in &self.localized_names {
+            desktop_entry.set_localized_string(NAME, locale, name);
+        }
+
+        if let Some(mime_types) = self.mime_types {
+            desktop_entry.set_list(MIME_TYPE, &mime_types);
+        }
+
+        if let Some(startup_wm_class) = self.startup_wm_class {
+            desktop_entry.set_string(STARTUP_WM_CLASS, &startup_wm_class);
+        }
+
+        if let Some(keywords) = self.keywords {
+            desktop_entry.set_list(KEYWORDS, &keywords);
+        }
+
+        if !self.actions.is_empty() {
+            let ids: Vec<String> = self.actions.iter().map(|action| action.id.clone()).collect();
+            desktop_entry.set_list(ACTIONS, &ids);
         }
 
         // AppHub specific fields
-        desktop_file_content.push_str("X-AppHub=true\n");
+        desktop_entry.set(X_APPHUB, "true");
 
+        if let Some(update_information) = self.update_information {
+            desktop_entry.set_string(X_APPHUB_UPDATE_INFORMATION, &update_information);
+        }
+
+        for action in &self.actions {
+            let group = desktop_file.group_or_insert(&action.group_name());
+            if let Some(name) = &action.name {
+                group.set_string(NAME, name);
+            }
+            if let Some(exec) = &action.exec {
+                group.set_string(EXEC, &with_no_sandbox(exec, no_sandbox));
+            }
+            if let Some(icon) = &action.icon {
+                group.set_string(ICON, icon);
+            }
+        }
+
+        let desktop_file_content = desktop_file.to_string();
         Ok(desktop_file_content)
     }
 
@@ -276,6 +578,53 @@ impl DesktopFileBuilder {
     pub fn no_sanbox(&self) -> Option<bool> {
         self.no_sanbox
     }
+
+    pub fn app_image_version(&self) -> Option<String> {
+        self.app_image_version.clone()
+    }
+
+    pub fn update_information(&self) -> Option<String> {
+        self.update_information.clone()
+    }
+
+    pub fn try_exec(&self) -> Option<String> {
+        self.try_exec.clone()
+    }
+
+    pub fn generic_name(&self) -> Option<String> {
+        self.generic_name.clone()
+    }
+
+    pub fn localized_names(&self) -> BTreeMap<String, String> {
+        self.localized_names.clone()
+    }
+
+    pub fn mime_types(&self) -> Option<Vec<String>> {
+        self.mime_types.clone()
+    }
+
+    pub fn startup_wm_class(&self) -> Option<String> {
+        self.startup_wm_class.clone()
+    }
+
+    pub fn keywords(&self) -> Option<Vec<String>> {
+        self.keywords.clone()
+    }
+
+    pub fn actions(&self) -> Vec<DesktopAction> {
+        self.actions.clone()
+    }
+}
+
+/// Add the `--no-sandbox` argument to a command line, before the field codes
+fn with_no_sandbox(exec: &str, no_sandbox: bool) -> String {
+    if !no_sandbox {
+        return exec.to_string();
+    }
+    match ExecCommand::parse(exec) {
+        Ok(mut command) => command.push_arg("--no-sandbox").to_string(),
+        Err(_) => format!("{} --no-sandbox", exec),
+    }
 }
 
 #[cfg(test)]
@@ -342,4 +691,68 @@ mod tests {
         let expected_content = "[Desktop Entry]\nType=Application\nVersion=1.0\nName=Test App\nComment=This is a test\nPath=/usr/bin/test\nExec=test --no-sandbox\nIcon=/usr/share/icons/test.png\nTerminal=true\nCategories=Utility\nX-AppHub=true\n";
         assert_eq!(content, expected_content);
     }
+
+    #[test]
+    fn test_update_information_round_trip() {
+        let mut builder = DesktopFileBuilder::new();
+        builder.set_type("Application".to_string());
+        builder.set_name("Test App".to_string());
+        builder.set_exec("test".to_string());
+        builder.set_update_information("zsync|https://example.com/Test.AppImage.zsync".to_string());
+
+        let content = builder.generate_content_string().unwrap();
+        let parsed = DesktopFileBuilder::from_desktop_entry_content(&content, true).unwrap();
+        assert_eq!(
+            parsed.update_information(),
+            Some("zsync|https://example.com/Test.AppImage.zsync".to_string())
+        );
+    }
+
+    #[test]
+    fn test_localized_keys_are_not_matched() {
+        let content = "[Desktop Entry]\nType=Application\nGenericName=Generic\nName[de]=Test DE\nName=Test\nExec=test\nMimeType=text/plain;\n";
+        let builder = DesktopFileBuilder::from_desktop_entry_content(content, false).unwrap();
+        assert_eq!(builder.name(), Some("Test".to_string()));
+        assert_eq!(builder.type_(), Some("Application".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_keys_round_trip() {
+        let content = "[Desktop Entry]\n# keep me\nType=Application\nName=Test\nName[de]=Test DE\nExec=test %U\nMimeType=text/plain;\nX-Custom=a\\sb\n\n[Desktop Action new]\nName=New\nExec=test --new\n";
+        let builder = DesktopFileBuilder::from_desktop_entry_content(content, false).unwrap();
+        assert_eq!(builder.exec(), Some("test %U".to_string()));
+
+        let mut builder = DesktopFileBuilder::from_desktop_entry_content(content, false).unwrap();
+        builder.set_exec("/opt/test.AppImage %U".to_string());
+        let generated = builder.generate_content_string().unwrap();
+        let expected = content.replace("Exec=test %U", "Exec=/opt/test.AppImage %U").replace(
+            "X-Custom=a\\sb\n",
+            "X-Custom=a\\sb\nX-AppHub=true\n",
+        );
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn test_actions() {
+        let content = "[Desktop Entry]\nType=Application\nName=Test\nExec=test\nActions=new;missing;\n\n[Desktop Action new]\nName=New\nExec=test --new\n";
+        let mut builder = DesktopFileBuilder::from_desktop_entry_content(content, false).unwrap();
+        let actions = builder.actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].id, "new");
+        assert_eq!(actions[0].exec, Some("test --new".to_string()));
+
+        builder.map_programs(|_| "/opt/My Apps/test.AppImage".to_string());
+        builder.set_no_sandbox(true);
+        builder.add_action(DesktopAction {
+            id: "private".to_string(),
+            name: Some("Private".to_string()),
+            exec: Some("/opt/test.AppImage --private".to_string()),
+            icon: None,
+        });
+        let generated = builder.generate_content_string().unwrap();
+        assert!(generated.contains("Actions=new;private;\n"));
+        assert!(generated.contains("Exec=\"/opt/My Apps/test.AppImage\" --no-sandbox\n"));
+        assert!(generated.contains("[Desktop Action new]\nName=New\nExec=\"/opt/My Apps/test.AppImage\" --new --no-sandbox\n"));
+        assert!(generated.contains("[Desktop Action private]\nName=Private\nExec=/opt/test.AppImage --private --no-sandbox\n"));
+    }
 }
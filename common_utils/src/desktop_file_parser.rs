@@ -0,0 +1,512 @@
+use crate::error::{Error, ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The main group of a desktop file
+pub const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+/// A desktop file parsed as described by the Desktop Entry Specification.
+/// Comments, blank lines, unknown keys and groups are kept, and the lines that are
+/// not modified are written back verbatim.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DesktopFile {
+    /// Comments and blank lines before the first group
+    header: Vec<String>,
+    groups: Vec<DesktopGroup>,
+}
+
+/// A `[Group Name]` section of a desktop file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopGroup {
+    name: String,
+    lines: Vec<GroupLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GroupLine {
+    Entry(DesktopKey),
+    /// A comment or a blank line
+    Other(String),
+}
+
+/// A `Key[locale]=value` line. The value is kept escaped, as found in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DesktopKey {
+    key: String,
+    locale: Option<String>,
+    value: String,
+    /// The line as read from the file, until the value is changed
+    raw: Option<String>,
+}
+
+impl DesktopKey {
+    fn line(&self) -> String {
+        if let Some(raw) = &self.raw {
+            return raw.clone();
+        }
+        match &self.locale {
+            Some(locale) => format!("{}[{}]={}", self.key, locale, self.value),
+            None => format!("{}={}", self.key, self.value),
+        }
+    }
+}
+
+impl DesktopFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the content of a desktop file
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut desktop_file = DesktopFile::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let number = index + 1;
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                match desktop_file.groups.last_mut() {
+                    Some(group) => group.lines.push(GroupLine::Other(line.to_string())),
+                    None => desktop_file.header.push(line.to_string()),
+                }
+                continue;
+            }
+
+            if let Some(name) = trimmed.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .filter(|name| is_valid_group_name(name))
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, format!("Line {}: invalid group header {}", number, trimmed)))?;
+                if desktop_file.group(name).is_some() {
+                    return Err(Error::new(ErrorKind::InvalidDesktopEntry, format!("Line {}: duplicate group [{}]", number, name)));
+                }
+                desktop_file.groups.push(DesktopGroup::new(name));
+                continue;
+            }
+
+            let group = desktop_file
+                .groups
+                .last_mut()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, format!("Line {}: key outside of a group", number)))?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, format!("Line {}: expected Key=Value", number)))?;
+            let (key, locale) = parse_key(key.trim())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, format!("Line {}: invalid key {}", number, key.trim())))?;
+
+            group.lines.push(GroupLine::Entry(DesktopKey {
+                key,
+                locale,
+                value: value.trim_start().to_string(),
+                raw: Some(line.to_string()),
+            }));
+        }
+
+        Ok(desktop_file)
+    }
+
+    pub fn groups(&self) -> &[DesktopGroup] {
+        &self.groups
+    }
+
+    pub fn group(&self, name: &str) -> Option<&DesktopGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut DesktopGroup> {
+        self.groups.iter_mut().find(|group| group.name == name)
+    }
+
+    /// The group with the given name, added at the end when missing
+    pub fn group_or_insert(&mut self, name: &str) -> &mut DesktopGroup {
+        let index = match self.groups.iter().position(|group| group.name == name) {
+            Some(index) => index,
+            None => {
+                self.groups.push(DesktopGroup::new(name));
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[index]
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Option<DesktopGroup> {
+        let index = self.groups.iter().position(|group| group.name == name)?;
+        Some(self.groups.remove(index))
+    }
+
+    /// The `[Desktop Entry]` group
+    pub fn desktop_entry(&self) -> Option<&DesktopGroup> {
+        self.group(DESKTOP_ENTRY_GROUP)
+    }
+
+    pub fn desktop_entry_mut(&mut self) -> &mut DesktopGroup {
+        self.group_or_insert(DESKTOP_ENTRY_GROUP)
+    }
+}
+
+impl fmt::Display for DesktopFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+        for group in &self.groups {
+            writeln!(f, "[{}]", group.name)?;
+            for line in &group.lines {
+                match line {
+                    GroupLine::Entry(entry) => writeln!(f, "{}", entry.line())?,
+                    GroupLine::Other(line) => writeln!(f, "{}", line)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DesktopGroup {
+    fn new(name: &str) -> Self {
+        DesktopGroup {
+            name: name.to_string(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The keys of the group as `(key, locale, escaped value)`, in file order
+    pub fn entries(&self) -> impl Iterator<Item = (&str, Option<&str>, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            GroupLine::Entry(entry) => Some((
+                entry.key.as_str(),
+                entry.locale.as_deref(),
+                entry.value.as_str(),
+            )),
+            GroupLine::Other(_) => None,
+        })
+    }
+
+    fn entry(&self, key: &str, locale: Option<&str>) -> Option<&DesktopKey> {
+        self.lines.iter().find_map(|line| match line {
+            GroupLine::Entry(entry) if entry.key == key && entry.locale.as_deref() == locale => {
+                Some(entry)
+            }
+            _ => None,
+        })
+    }
+
+    /// The escaped value of a key, without locale
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key, None).map(|entry| entry.value.as_str())
+    }
+
+    /// The value of a string key, without locale
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        self.get(key).map(unescape)
+    }
+
+    /// The value of a localestring key for the given locale (`lang_COUNTRY@MODIFIER`),
+    /// falling back to the less specific locales and to the key without locale
+    pub fn get_localized_string(&self, key: &str, locale: &str) -> Option<String> {
+        locale_fallbacks(locale)
+            .iter()
+            .find_map(|locale| self.entry(key, Some(locale)))
+            .or_else(|| self.entry(key, None))
+            .map(|entry| unescape(&entry.value))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The values of a list key, separated by semicolons
+    pub fn get_list(&self, key: &str) -> Option<Vec<String>> {
+        self.get(key).map(split_list)
+    }
+
+    /// Set the escaped value of a key without locale. An existing key keeps its place
+    /// in the group, and its line is left untouched when the value does not change.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.set_value(key, None, value);
+    }
+
+    fn set_value(&mut self, key: &str, locale: Option<&str>, value: &str) {
+        let existing = self.lines.iter_mut().find_map(|line| match line {
+            GroupLine::Entry(entry) if entry.key == key && entry.locale.as_deref() == locale => {
+                Some(entry)
+            }
+            _ => None,
+        });
+        match existing {
+            Some(entry) if entry.value == value => {}
+            Some(entry) => {
+                entry.value = value.to_string();
+                entry.raw = None;
+            }
+            None => {
+                // new keys go after the last key, before the trailing comments and blank lines
+                let index = self
+                    .lines
+                    .iter()
+                    .rposition(|line| matches!(line, GroupLine::Entry(_)))
+                    .map_or(0, |index| index + 1);
+                self.lines.insert(
+                    index,
+                    GroupLine::Entry(DesktopKey {
+                        key: key.to_string(),
+                        locale: locale.map(str::to_string),
+                        value: value.to_string(),
+                        raw: None,
+                    }),
+                );
+            }
+        }
+    }
+
+    /// Set the value of a string key without locale, escaping it
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        if self.get_string(key).as_deref() != Some(value) {
+            self.set(key, &escape(value));
+        }
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, if value { "true" } else { "false" });
+    }
+
+    /// Set the values of a list key
+    pub fn set_list(&mut self, key: &str, values: &[String]) {
+        if self.get_list(key).as_deref() != Some(values) {
+            self.set(key, &join_list(values));
+        }
+    }
+
+    /// Set the value of a localestring key for the given locale, escaping it
+    pub fn set_localized_string(&mut self, key: &str, locale: &str, value: &str) {
+        let current = self.entry(key, Some(locale)).map(|entry| unescape(&entry.value));
+        if current.as_deref() != Some(value) {
+            self.set_value(key, Some(locale), &escape(value));
+        }
+    }
+
+    /// The localized values of a localestring key, by locale
+    pub fn localized_strings(&self, key: &str) -> BTreeMap<String, String> {
+        self.entries()
+            .filter(|(name, _, _)| *name == key)
+            .filter_map(|(_, locale, value)| Some((locale?.to_string(), unescape(value))))
+            .collect()
+    }
+
+    /// Remove a key, with all its localized variants
+    pub fn remove(&mut self, key: &str) {
+        self.lines
+            .retain(|line| !matches!(line, GroupLine::Entry(entry) if entry.key == key));
+    }
+}
+
+/// Split `Key[locale]` into its parts
+fn parse_key(key: &str) -> Option<(String, Option<String>)> {
+    let (name, locale) = match key.split_once('[') {
+        Some((name, locale)) => {
+            let locale = locale.strip_suffix(']')?;
+            if locale.is_empty() || locale.contains(['[', ']']) {
+                return None;
+            }
+            (name, Some(locale.to_string()))
+        }
+        None => (key, None),
+    };
+
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| (name.to_string(), locale))
+}
+
+fn is_valid_group_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['[', ']']) && !name.chars().any(char::is_control)
+}
+
+/// The locales to look up for `lang_COUNTRY@MODIFIER`, most specific first
+fn locale_fallbacks(locale: &str) -> Vec<String> {
+    // the encoding is ignored
+    let locale = locale.split('.').next().unwrap_or(locale);
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let (lang, country) = match rest.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (rest, None),
+    };
+
+    let mut fallbacks = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        fallbacks.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        fallbacks.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        fallbacks.push(format!("{}@{}", lang, modifier));
+    }
+    fallbacks.push(lang.to_string());
+    fallbacks
+}
+
+/// Replace the escape sequences `\s`, `\n`, `\t`, `\r` and `\\`.
+/// Other sequences, like `\;` in lists, are kept as they are.
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Escape a string value. A leading space is escaped so that it is not trimmed.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            ' ' if index == 0 => escaped.push_str("\\s"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Split a list value on the semicolons that are not escaped
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            ';' => values.push(unescape(&std::mem::take(&mut current))),
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        values.push(unescape(&current));
+    }
+    values
+}
+
+/// Join list values, terminating the list with a semicolon
+pub fn join_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{};", escape(value).replace(';', "\\;")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "# Created by hand\n\
+[Desktop Entry]\n\
+Type=Application\n\
+Name=Test\n\
+Name[de]=Test DE\n\
+GenericName=Generic Test\n\
+TryExec=test\n\
+Exec=test %U\n\
+Comment=Line one\\nLine\\stwo\n\
+Keywords=one;two\\;three;\n\
+X-Unknown-Key = kept as is\n\
+\n\
+# actions\n\
+[Desktop Action new]\n\
+Name=New window\n\
+Exec=test --new-window\n";
+
+    #[test]
+    fn test_parse_and_round_trip() {
+        let desktop_file = DesktopFile::parse(CONTENT).unwrap();
+        assert_eq!(desktop_file.to_string(), CONTENT);
+
+        let entry = desktop_file.desktop_entry().unwrap();
+        assert_eq!(entry.get("Name"), Some("Test"));
+        assert_eq!(entry.get("Exec"), Some("test %U"));
+        assert_eq!(entry.get("X-Unknown-Key"), Some("kept as is"));
+        assert_eq!(entry.get_string("Comment"), Some("Line one\nLine two".to_string()));
+        assert_eq!(
+            entry.get_list("Keywords"),
+            Some(vec!["one".to_string(), "two;three".to_string()])
+        );
+        assert_eq!(entry.get_localized_string("Name", "de_DE.UTF-8"), Some("Test DE".to_string()));
+        assert_eq!(entry.get_localized_string("Name", "fr"), Some("Test".to_string()));
+        assert_eq!(entry.localized_strings("Name").len(), 1);
+
+        let action = desktop_file.group("Desktop Action new").unwrap();
+        assert_eq!(action.get("Exec"), Some("test --new-window"));
+    }
+
+    #[test]
+    fn test_modify_keeps_other_lines() {
+        let mut desktop_file = DesktopFile::parse(CONTENT).unwrap();
+        let entry = desktop_file.desktop_entry_mut();
+        entry.set_string("Exec", "/opt/test.AppImage %U");
+        entry.set_string("Name", "Test");
+        entry.set_list("Categories", &["Utility".to_string()]);
+        entry.remove("TryExec");
+
+        let expected = CONTENT
+            .replace("TryExec=test\n", "")
+            .replace("Exec=test %U", "Exec=/opt/test.AppImage %U")
+            .replace("X-Unknown-Key = kept as is\n", "X-Unknown-Key = kept as is\nCategories=Utility;\n");
+        assert_eq!(desktop_file.to_string(), expected);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(DesktopFile::parse("Name=Test\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry]\nName\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry]\nNa me=Test\n").is_err());
+        assert!(DesktopFile::parse("[Desktop Entry]\n[Desktop Entry]\n").is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(" a\\b\n"), "\\sa\\\\b\\n");
+        assert_eq!(unescape(&escape(" a\\b\n")), " a\\b\n");
+        assert_eq!(join_list(&["a;b".to_string(), "c".to_string()]), "a\\;b;c;");
+        assert_eq!(split_list("a\\;b;c;"), vec!["a;b".to_string(), "c".to_string()]);
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// What to do when an app with the same id is already installed in the scope
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// Fail, leaving the installed app untouched
+    #[default]
+    Refuse,
+    /// Replace the installed app, to upgrade or reinstall it. The files of the installed
+    /// version the new one does not reuse are removed.
+    Replace,
+    /// Install the new version next to the installed one, with its own id and name
+    KeepBoth,
+}
+
+impl DuplicatePolicy {
+    pub const ALL: [DuplicatePolicy; 3] = [
+        DuplicatePolicy::Refuse,
+        DuplicatePolicy::Replace,
+        DuplicatePolicy::KeepBoth,
+    ];
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplicatePolicy::Refuse => write!(f, "refuse"),
+            DuplicatePolicy::Replace => write!(f, "replace"),
+            DuplicatePolicy::KeepBoth => write!(f, "keep-both"),
+        }
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        DuplicatePolicy::ALL
+            .into_iter()
+            .find(|policy| policy.to_string() == value)
+            .ok_or_else(|| format!("Unknown duplicate policy: {}", value))
+    }
+}
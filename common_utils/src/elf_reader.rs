@@ -0,0 +1,219 @@
+use crate::error::{Error, ErrorKind, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_DATA_MSB: u8 = 2;
+
+/// The fields of the ELF header needed to locate the end of the AppImage runtime
+/// and its sections.
+#[derive(Debug, Clone)]
+pub struct ElfHeader {
+    pub is_64_bit: bool,
+    pub is_little_endian: bool,
+    /// Offset of the section header table
+    pub section_header_offset: u64,
+    /// Size of a single section header entry
+    pub section_header_entry_size: u16,
+    /// Number of entries in the section header table
+    pub section_header_count: u16,
+    /// Index of the section containing the section names
+    pub section_names_index: u16,
+}
+
+impl ElfHeader {
+    /// Read the ELF header at the start of the given file.
+    pub fn read_from(file: &mut File) -> Result<Self> {
+        let mut ident = [0u8; 64];
+        file.seek(SeekFrom::Start(0))
+            .map_err(|e| Error::io("Failed to seek ELF header", e))?;
+        file.read_exact(&mut ident)
+            .map_err(|e| Error::io("Failed to read ELF header", e))?;
+        Self::parse(&ident)
+    }
+
+    /// Parse an ELF header from the first 64 bytes of a file.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 52 || bytes[0..4] != ELF_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "Not an ELF file"));
+        }
+
+        let is_64_bit = match bytes[4] {
+            ELF_CLASS_32 => false,
+            ELF_CLASS_64 => true,
+            class => return Err(Error::new(ErrorKind::InvalidAppImage, format!("Unsupported ELF class: {}", class))),
+        };
+        let is_little_endian = match bytes[5] {
+            ELF_DATA_LSB => true,
+            ELF_DATA_MSB => false,
+            data => return Err(Error::new(ErrorKind::InvalidAppImage, format!("Unsupported ELF data encoding: {}", data))),
+        };
+
+        let reader = EndianReader { is_little_endian };
+        let header = if is_64_bit {
+            if bytes.len() < 64 {
+                return Err(Error::new(ErrorKind::InvalidAppImage, "Truncated ELF header"));
+            }
+            ElfHeader {
+                is_64_bit,
+                is_little_endian,
+                section_header_offset: reader.u64(&bytes[40..48]),
+                section_header_entry_size: reader.u16(&bytes[58..60]),
+                section_header_count: reader.u16(&bytes[60..62]),
+                section_names_index: reader.u16(&bytes[62..64]),
+            }
+        } else {
+            ElfHeader {
+                is_64_bit,
+                is_little_endian,
+                section_header_offset: reader.u32(&bytes[32..36]) as u64,
+                section_header_entry_size: reader.u16(&bytes[46..48]),
+                section_header_count: reader.u16(&bytes[48..50]),
+                section_names_index: reader.u16(&bytes[50..52]),
+            }
+        };
+
+        Ok(header)
+    }
+
+    /// Size of the ELF part of the file.
+    /// The AppImage runtime places the section header table at its very end, so the
+    /// payload (the SquashFS image for type 2 AppImages) starts right after it.
+    pub fn file_size(&self) -> u64 {
+        self.section_header_offset
+            + self.section_header_entry_size as u64 * self.section_header_count as u64
+    }
+}
+
+/// A section of the ELF file, located by its offset and size in the file
+#[derive(Debug, Clone)]
+pub struct ElfSection {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Read the section headers of an ELF file, resolving their names
+pub fn read_sections(file: &mut File) -> Result<Vec<ElfSection>> {
+    let header = ElfHeader::read_from(file)?;
+    let reader = EndianReader {
+        is_little_endian: header.is_little_endian,
+    };
+
+    let entry_size = header.section_header_entry_size as usize;
+    let minimum_entry_size = if header.is_64_bit { 64 } else { 40 };
+    if header.section_header_count == 0 {
+        return Ok(Vec::new());
+    }
+    if entry_size < minimum_entry_size {
+        return Err(Error::new(ErrorKind::InvalidAppImage, format!("Invalid ELF section header size: {}", entry_size)));
+    }
+
+    let mut table = vec![0u8; entry_size * header.section_header_count as usize];
+    file.seek(SeekFrom::Start(header.section_header_offset))
+        .map_err(|e| Error::io("Failed to seek ELF section headers", e))?;
+    file.read_exact(&mut table)
+        .map_err(|e| Error::io("Failed to read ELF section headers", e))?;
+
+    // (name offset, offset, size) of every section
+    let raw_sections: Vec<(u32, u64, u64)> = table
+        .chunks(entry_size)
+        .map(|entry| {
+            if header.is_64_bit {
+                (reader.u32(&entry[0..4]), reader.u64(&entry[24..32]), reader.u64(&entry[32..40]))
+            } else {
+                (
+                    reader.u32(&entry[0..4]),
+                    reader.u32(&entry[16..20]) as u64,
+                    reader.u32(&entry[20..24]) as u64,
+                )
+            }
+        })
+        .collect();
+
+    let names = match raw_sections.get(header.section_names_index as usize) {
+        Some((_, offset, size)) => read_bytes(file, *offset, *size)?,
+        None => return Err(Error::new(ErrorKind::InvalidAppImage, "ELF section names table not found")),
+    };
+
+    Ok(raw_sections
+        .into_iter()
+        .map(|(name_offset, offset, size)| {
+            let name = names
+                .get(name_offset as usize..)
+                .map(|name| {
+                    let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+                    String::from_utf8_lossy(&name[..end]).to_string()
+                })
+                .unwrap_or_default();
+            ElfSection { name, offset, size }
+        })
+        .collect())
+}
+
+/// Read the content of the section with the given name, if the file has one
+pub fn read_section(file: &mut File, name: &str) -> Result<Option<Vec<u8>>> {
+    let section = read_sections(file)?
+        .into_iter()
+        .find(|section| section.name == name);
+    match section {
+        Some(section) => Ok(Some(read_bytes(file, section.offset, section.size)?)),
+        None => Ok(None),
+    }
+}
+
+fn read_bytes(file: &mut File, offset: u64, size: u64) -> Result<Vec<u8>> {
+    let file_size = file
+        .metadata()
+        .map_err(|e| Error::io("Failed to read file metadata", e))?
+        .len();
+    if offset.saturating_add(size) > file_size {
+        return Err(Error::new(ErrorKind::InvalidAppImage, "ELF section exceeds the file size"));
+    }
+
+    let mut buf = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| Error::io("Failed to seek ELF section", e))?;
+    file.read_exact(&mut buf)
+        .map_err(|e| Error::io("Failed to read ELF section", e))?;
+    Ok(buf)
+}
+
+/// Reads integers with the byte order declared in the ELF header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EndianReader {
+    pub is_little_endian: bool,
+}
+
+impl EndianReader {
+    pub fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.is_little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    }
+
+    pub fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.is_little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    }
+
+    pub fn u64(&self, bytes: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes[0..8]);
+        if self.is_little_endian {
+            u64::from_le_bytes(buf)
+        } else {
+            u64::from_be_bytes(buf)
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, io};
+
+/// The class of a failure, for the callers to react to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// A file, a directory or an installed app does not exist
+    NotFound,
+    /// The current user is not allowed to read or write a file
+    PermissionDenied,
+    /// The file is not an AppImage, or its payload is damaged
+    InvalidAppImage,
+    /// A desktop entry is missing, malformed or rejected by the validation
+    InvalidDesktopEntry,
+    /// The signature is invalid, or missing or untrusted when required
+    Signature,
+    /// The registry of the installed apps could not be read or written
+    Registry,
+    /// A download failed
+    Network,
+    /// Reading or writing a file failed
+    Io,
+    /// The action was cancelled and undone
+    Cancelled,
+    /// The app is already installed, and replacing it was not asked
+    AlreadyInstalled,
+    Other,
+}
+
+/// An error of AppHub: what failed, the app it is about and the errors that caused it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// The name or id of the app the error is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// The messages of the underlying errors, the closest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub causes: Vec<String>,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Error {
+            kind,
+            message: message.into(),
+            app: None,
+            causes: Vec::new(),
+        }
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Other, message)
+    }
+
+    /// An IO error, classified by its kind
+    pub fn io(message: impl Into<String>, error: io::Error) -> Self {
+        Error::new(io_error_kind(&error), message).caused_by(error)
+    }
+
+    /// Record the app the error is about, unless it is already known
+    pub fn for_app(mut self, app: impl Into<String>) -> Self {
+        self.app.get_or_insert_with(|| app.into());
+        self
+    }
+
+    /// Add the underlying error
+    pub fn caused_by(mut self, cause: impl fmt::Display) -> Self {
+        self.causes.push(cause.to_string());
+        self
+    }
+
+    /// Wrap the error in a higher level message, keeping its kind and its message as a cause
+    pub fn context(mut self, message: impl Into<String>) -> Self {
+        let message = std::mem::replace(&mut self.message, message.into());
+        self.causes.insert(0, message);
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.kind == ErrorKind::Cancelled
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for cause in &self.causes {
+            write!(f, ": {}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::other(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::other(message)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::new(io_error_kind(&error), error.to_string())
+    }
+}
+
+fn io_error_kind(error: &io::Error) -> ErrorKind {
+    match error.kind() {
+        io::ErrorKind::NotFound => ErrorKind::NotFound,
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+        _ => ErrorKind::Io,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        let error = Error::io(
+            "Failed to stage Test.AppImage",
+            io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"),
+        )
+        .context("Install failed")
+        .for_app("test");
+        assert_eq!(error.kind, ErrorKind::PermissionDenied);
+        assert_eq!(error.app.as_deref(), Some("test"));
+        assert_eq!(
+            error.to_string(),
+            "Install failed: Failed to stage Test.AppImage: Permission denied"
+        );
+
+        let json = serde_json::to_string(&Error::other("failed")).unwrap();
+        assert_eq!(json, r#"{"kind":"other","message":"failed"}"#);
+        assert_eq!(serde_json::from_str::<Error>(&json).unwrap(), Error::other("failed"));
+    }
+}
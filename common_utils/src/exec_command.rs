@@ -0,0 +1,214 @@
+use crate::error::{Error, ErrorKind, Result};
+use std::fmt;
+
+/// Characters that require an argument of an Exec command line to be quoted
+const RESERVED_CHARS: &str = " \t\n\"'\\><~|&;$*?#()`";
+
+/// Field codes that expand to the files or URLs the app is asked to open
+pub const FILE_FIELD_CODES: [char; 4] = ['f', 'F', 'u', 'U'];
+
+/// An argument of an Exec command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecArg {
+    /// A plain argument, written quoted when needed
+    Literal(String),
+    /// A field code standing alone, like `%U`
+    FieldCode(char),
+    /// An unquoted argument mixing text and field codes, like `--file=%f`, kept as written
+    Template(String),
+}
+
+/// The command line of an `Exec` key, as described by the Desktop Entry Specification.
+/// The value is expected unescaped from the desktop file, as returned by `get_string`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecCommand {
+    pub program: String,
+    pub args: Vec<ExecArg>,
+}
+
+impl ExecCommand {
+    pub fn new(program: &str) -> Self {
+        ExecCommand {
+            program: program.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Parse an Exec command line
+    pub fn parse(exec: &str) -> Result<Self> {
+        let mut words = split_words(exec)?.into_iter();
+        let program = match words.next() {
+            Some(Word { text, .. }) => text.replace("%%", "%"),
+            None => return Err(Error::new(ErrorKind::InvalidDesktopEntry, "The Exec command is empty")),
+        };
+
+        let args = words
+            .map(|word| {
+                if word.quoted {
+                    return ExecArg::Literal(word.text.replace("%%", "%"));
+                }
+                let mut chars = word.text.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some('%'), Some(code), None) if code != '%' => ExecArg::FieldCode(code),
+                    _ if word.text.replace("%%", "").contains('%') => ExecArg::Template(word.text),
+                    _ => ExecArg::Literal(word.text.replace("%%", "%")),
+                }
+            })
+            .collect();
+
+        Ok(ExecCommand { program, args })
+    }
+
+    /// The same command running another program
+    pub fn with_program(mut self, program: &str) -> Self {
+        self.program = program.to_string();
+        self
+    }
+
+    /// Add an argument, before the field codes of the files to open so that it is not
+    /// taken for one of them. An argument already present is not added again.
+    pub fn push_arg(&mut self, arg: &str) -> &mut Self {
+        let literal = ExecArg::Literal(arg.to_string());
+        if self.args.contains(&literal) {
+            return self;
+        }
+        let index = self
+            .args
+            .iter()
+            .position(|arg| matches!(arg, ExecArg::FieldCode(code) if FILE_FIELD_CODES.contains(code)))
+            .unwrap_or(self.args.len());
+        self.args.insert(index, literal);
+        self
+    }
+
+    /// Whether the command accepts files or URLs to open
+    pub fn accepts_files(&self) -> bool {
+        self.args.iter().any(|arg| match arg {
+            ExecArg::FieldCode(code) => FILE_FIELD_CODES.contains(code),
+            ExecArg::Template(template) => FILE_FIELD_CODES
+                .iter()
+                .any(|code| template.contains(&format!("%{}", code))),
+            ExecArg::Literal(_) => false,
+        })
+    }
+}
+
+impl fmt::Display for ExecCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", quote(&self.program))?;
+        for arg in &self.args {
+            match arg {
+                ExecArg::Literal(literal) => write!(f, " {}", quote(literal))?,
+                ExecArg::FieldCode(code) => write!(f, " %{}", code)?,
+                ExecArg::Template(template) => write!(f, " {}", template)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A word of the command line, without its quotes
+struct Word {
+    text: String,
+    quoted: bool,
+}
+
+/// Split a command line in words. Quoted words start and end with a double quote,
+/// inside them `"`, `` ` ``, `$` and `\` are escaped with a backslash.
+fn split_words(exec: &str) -> Result<Vec<Word>> {
+    let mut words = Vec::new();
+    let mut chars = exec.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let Some(first) = chars.next() else {
+            return Ok(words);
+        };
+
+        if first == '"' {
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c @ ('"' | '`' | '$' | '\\')) => text.push(c),
+                        Some(c) => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                        None => return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Unterminated escape in the Exec command")),
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err(Error::new(ErrorKind::InvalidDesktopEntry, "Unterminated quote in the Exec command")),
+                }
+            }
+            if chars.peek().is_some_and(|c| *c != ' ' && *c != '\t') {
+                return Err(Error::new(ErrorKind::InvalidDesktopEntry, "A quoted argument of the Exec command must be a whole word"));
+            }
+            words.push(Word { text, quoted: true });
+        } else {
+            let mut text = first.to_string();
+            while let Some(c) = chars.next_if(|c| *c != ' ' && *c != '\t') {
+                text.push(c);
+            }
+            words.push(Word {
+                text,
+                quoted: false,
+            });
+        }
+    }
+}
+
+/// Quote an argument when it contains reserved characters. Percent signs are doubled.
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if !arg.is_empty() && !arg.contains(|c| RESERVED_CHARS.contains(c)) {
+        return arg;
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let command = ExecCommand::parse("\"/opt/My Apps/App \\\"1\\\".AppImage\" --new-window --file=%f 100%% %U").unwrap();
+        assert_eq!(command.program, "/opt/My Apps/App \"1\".AppImage");
+        assert_eq!(
+            command.args,
+            vec![
+                ExecArg::Literal("--new-window".to_string()),
+                ExecArg::Template("--file=%f".to_string()),
+                ExecArg::Literal("100%".to_string()),
+                ExecArg::FieldCode('U'),
+            ]
+        );
+        assert!(command.accepts_files());
+
+        assert!(ExecCommand::parse("").is_err());
+        assert!(ExecCommand::parse("\"/opt/App").is_err());
+        assert!(ExecCommand::parse("\"/opt/App\"x").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut command = ExecCommand::parse("app %U").unwrap().with_program("/opt/My Apps/$App`s 100%.AppImage");
+        command.push_arg("--no-sandbox").push_arg("--no-sandbox");
+        let exec = command.to_string();
+        assert_eq!(exec, "\"/opt/My Apps/\\$App\\`s 100%%.AppImage\" --no-sandbox %U");
+        assert_eq!(ExecCommand::parse(&exec).unwrap(), command);
+
+        let command = ExecCommand::new("/opt/App.AppImage");
+        assert_eq!(command.to_string(), "/opt/App.AppImage");
+        assert!(!command.accepts_files());
+    }
+}
@@ -1,32 +1,49 @@
+use crate::error::{Error, ErrorKind, Result};
 use log::{error, info};
-use std::os::unix::fs::PermissionsExt;
+use sha2::{Digest, Sha256};
+use std::os::unix::fs::{symlink, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 /// This function is used to remove a file from the filesystem (used to remove AppImages and icons)
 /// It returns a boolean indicating if the file was removed successfully
-pub fn rm_file(file_path: &String) -> Result<bool, String> {
+pub fn rm_file(file_path: &String) -> Result<bool> {
     match fs::remove_file(file_path) {
         Ok(_) => {
             info!("File removed successfully");
             Ok(true)
         }
-        Err(e) => Err(format!("Failed to remove file: {}", e)),
+        Err(e) => Err(Error::io(format!("Failed to remove {}", file_path), e)),
     }
 }
 
+/// Compute the hex encoded SHA-256 of a file
+pub fn file_sha256(file_path: &Path) -> Result<String> {
+    let mut file = fs::File::open(file_path)
+        .map_err(|e| Error::io(format!("Failed to open {:?}", file_path), e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", file_path), e))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
 /// This function is used to remove a directory and all its contents from the filesystem
-pub fn rm_dir_all(dir_path: &str) -> Result<bool, String> {
+pub fn rm_dir_all(dir_path: &str) -> Result<bool> {
     match fs::remove_dir_all(dir_path) {
         Ok(_) => {
             info!("Directory removed successfully");
             Ok(true)
         }
-        Err(e) => Err(format!("Failed to remove directory: {}", e)),
+        Err(e) => Err(Error::io(format!("Failed to remove {}", dir_path), e)),
     }
 }
 
-/// This function is used to copy a directory and all its contents to a new location
+/// This function is used to copy a directory and all its contents to a new location.
+/// Symbolic links are copied as links.
 pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
     fs::create_dir_all(&dst)?;
     if let Err(err) = fs::create_dir_all(&dst) {
@@ -38,6 +55,9 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<
         let ty = entry.file_type()?;
         if ty.is_dir() {
             copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
+        } else if ty.is_symlink() {
+            // the launchers of the apps link to their current version
+            symlink(fs::read_link(entry.path())?, dst.as_ref().join(entry.file_name()))?;
         } else {
             fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
         }
@@ -46,16 +66,17 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<
 }
 
 /// Find a .desktop file in the given directory
-pub fn find_desktop_file_in_dir(dir_path: &PathBuf) -> Result<PathBuf, String> {
+pub fn find_desktop_file_in_dir(dir_path: &PathBuf) -> Result<PathBuf> {
+    let read_error = |e| Error::io(format!("Failed to read {:?}", dir_path), e);
     let entries = match fs::read_dir(dir_path.as_path()) {
         Ok(entries) => entries,
-        Err(e) => return Err(format!("Failed to read directory: {}", e)),
+        Err(e) => return Err(read_error(e)),
     };
 
     for entry in entries {
         let entry = match entry {
             Ok(entry) => entry,
-            Err(e) => return Err(format!("Failed to read entry: {}", e)),
+            Err(e) => return Err(read_error(e)),
         };
         let path = entry.path();
         let extension = match path.extension() {
@@ -66,7 +87,7 @@ pub fn find_desktop_file_in_dir(dir_path: &PathBuf) -> Result<PathBuf, String> {
             return Ok(path);
         }
     }
-    Err("No desktop file found".to_string())
+    Err(Error::new(ErrorKind::InvalidDesktopEntry, "No desktop file found"))
 }
 
 /// Add executable permission to a file
@@ -87,9 +108,9 @@ pub fn is_directory_empty(dir_path: &Path) -> io::Result<bool> {
 }
 
 /// Get the file name from a path
-pub fn get_file_name(file_path: &PathBuf) -> Result<String, String> {
+pub fn get_file_name(file_path: &PathBuf) -> Result<String> {
     match file_path.file_name() {
         Some(file_name) => Ok(file_name.to_string_lossy().to_string()),
-        None => Err("Failed to get file name".to_string()),
+        None => Err(Error::other(format!("Failed to get the file name of {:?}", file_path))),
     }
 }
@@ -0,0 +1,76 @@
+use crate::error::Result;
+
+/// The kind of an entry stored in an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    Directory,
+    File,
+    Symlink(String),
+    Other,
+}
+
+/// A file, directory or symlink stored in an image
+#[derive(Debug, Clone)]
+pub struct ImageEntry {
+    /// Path relative to the image root, without a leading slash
+    pub path: String,
+    pub kind: EntryKind,
+    /// Size in bytes of the file content (0 for everything but files)
+    pub size: u64,
+}
+
+/// Read-only access to the filesystem image embedded in an AppImage
+/// (SquashFS for type 2 AppImages, ISO 9660 for type 1)
+pub trait ImagePayload {
+    /// List every entry of the image, walking the directory tree from the root
+    fn entries(&mut self) -> Result<Vec<ImageEntry>>;
+
+    /// List the entries of the directory at the given path
+    fn read_dir(&mut self, path: &str) -> Result<Vec<ImageEntry>>;
+
+    /// Read the content of the file at the given path, following symlinks
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>>;
+}
+
+/// Join a directory path and an entry name
+pub(crate) fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Split a path into its components, dropping empty and "." components and
+/// resolving ".." without ever leaving the image root
+pub(crate) fn normalize_path(path: &str) -> Vec<String> {
+    normalize_components(path.split('/').map(|c| c.to_string()).collect())
+}
+
+pub(crate) fn normalize_components(components: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for component in components {
+        match component.as_str() {
+            "" | "." => {}
+            ".." => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Resolve a symlink found at `components[index]` pointing at `target`, keeping the
+/// components after it. Absolute targets are resolved against the image root, since the
+/// payload is meant to be mounted and never points outside of it.
+pub(crate) fn resolve_symlink(components: &[String], index: usize, target: &str) -> Vec<String> {
+    let mut resolved = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        components[..index].to_vec()
+    };
+    resolved.extend(normalize_path(target));
+    resolved.extend(components[index + 1..].iter().cloned());
+    normalize_components(resolved)
+}
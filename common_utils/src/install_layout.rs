@@ -0,0 +1,89 @@
+//! Where the files of an installed app go in the installation directory. Each kept version
+//! has its own directory, `<installation dir>/<id>/<version>/<AppImage>`, and the desktop
+//! entry runs `<installation dir>/<id>/<id>.AppImage`, a link to the current version.
+
+use crate::app_id::sanitize_app_id;
+use crate::error::{Error, Result};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+/// The length of the SHA-256 prefix naming the directory of a version without a version number
+const UNVERSIONED_DIR_LENGTH: usize = 12;
+
+/// The number of versions of an app kept on disk when not told otherwise
+pub const DEFAULT_KEPT_VERSIONS: usize = 3;
+
+/// The directory holding the kept versions of an app
+pub fn app_dir(installation_dir: &Path, id: &str) -> PathBuf {
+    installation_dir.join(id)
+}
+
+/// The file name of the link to the current version
+pub fn launcher_file_name(id: &str) -> String {
+    format!("{}.AppImage", id)
+}
+
+/// The link to the current version of an app, run by the desktop entry
+pub fn launcher_path(installation_dir: &Path, id: &str) -> PathBuf {
+    app_dir(installation_dir, id).join(launcher_file_name(id))
+}
+
+/// The name of the directory of a version: its version number, or the start of the
+/// SHA-256 of its AppImage when it has none
+pub fn version_dir_name(version: Option<&str>, sha256: &str) -> String {
+    version
+        .and_then(sanitize_app_id)
+        .unwrap_or_else(|| sha256.chars().take(UNVERSIONED_DIR_LENGTH).collect())
+}
+
+/// The target of the launcher link, relative to the launcher so that the installation
+/// directory can be moved
+pub fn launcher_target(launcher: &Path, app_image_path: &Path) -> PathBuf {
+    launcher
+        .parent()
+        .and_then(|app_dir| app_image_path.strip_prefix(app_dir).ok())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| app_image_path.to_path_buf())
+}
+
+/// Point the launcher at another version. The link is replaced with a rename, so the
+/// launcher always exists.
+pub fn link_launcher(launcher: &Path, app_image_path: &Path) -> Result<()> {
+    let temp_path = launcher.with_extension("AppImage.apphub-tmp");
+    let _ = fs::remove_file(&temp_path);
+    symlink(launcher_target(launcher, app_image_path), &temp_path)
+        .and_then(|_| fs::rename(&temp_path, launcher))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            Error::io(format!("Failed to link {:?} to {:?}", launcher, app_image_path), e)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let installation_dir = Path::new("/opt/AppImages");
+        let launcher = launcher_path(installation_dir, "test");
+        assert_eq!(launcher, Path::new("/opt/AppImages/test/test.AppImage"));
+        assert_eq!(version_dir_name(Some("1.2 beta"), "abcdef0123456789"), "1.2_beta");
+        assert_eq!(version_dir_name(None, "abcdef0123456789"), "abcdef012345");
+        assert_eq!(
+            launcher_target(&launcher, Path::new("/opt/AppImages/test/1.0/Test.AppImage")),
+            Path::new("1.0/Test.AppImage")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = launcher_path(dir.path(), "test");
+        for version in ["1.0", "2.0"] {
+            let app_image_path = app_dir(dir.path(), "test").join(version).join("Test.AppImage");
+            fs::create_dir_all(app_image_path.parent().unwrap()).unwrap();
+            fs::write(&app_image_path, version).unwrap();
+            link_launcher(&launcher, &app_image_path).unwrap();
+            assert_eq!(fs::read_to_string(&launcher).unwrap(), version);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::desktop_file_helpers::find_desktop_file_location;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Who an app is installed for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstallScope {
+    /// All the users of the machine. Installing requires admin rights.
+    #[default]
+    System,
+    /// The current user only, in the XDG data directory
+    User,
+}
+
+impl InstallScope {
+    pub const ALL: [InstallScope; 2] = [InstallScope::System, InstallScope::User];
+
+    /// Whether the backend must run with admin rights for this scope
+    pub fn requires_privileges(&self) -> bool {
+        *self == InstallScope::System
+    }
+
+    /// The directory where the desktop files are written
+    pub fn applications_dir(&self) -> Result<PathBuf> {
+        match self {
+            InstallScope::System => Ok(find_desktop_file_location()?),
+            InstallScope::User => Ok(user_data_dir()?.join("applications")),
+        }
+    }
+
+    /// The hicolor icon theme next to the applications directory, where the icons
+    /// are installed
+    pub fn icon_theme_dir(&self) -> Result<PathBuf> {
+        let applications_dir = self.applications_dir()?;
+        let data_dir = applications_dir
+            .parent()
+            .ok_or_else(|| Error::other(format!("{:?} has no parent directory", applications_dir)))?;
+        Ok(data_dir.join("icons").join("hicolor"))
+    }
+
+    /// The default directory of the AppImages. For the system scope it is
+    /// chosen in the settings.
+    pub fn default_installation_dir(&self) -> Option<PathBuf> {
+        match self {
+            InstallScope::System => None,
+            InstallScope::User => user_data_dir().ok().map(|dir| dir.join("AppImages")),
+        }
+    }
+}
+
+impl fmt::Display for InstallScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallScope::System => write!(f, "system"),
+            InstallScope::User => write!(f, "user"),
+        }
+    }
+}
+
+impl FromStr for InstallScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        InstallScope::ALL
+            .into_iter()
+            .find(|scope| scope.to_string() == value)
+            .ok_or_else(|| format!("Unknown install scope: {}", value))
+    }
+}
+
+/// `$XDG_DATA_HOME`, or `~/.local/share` when it is not set
+pub fn user_data_dir() -> Result<PathBuf> {
+    dirs::data_dir().ok_or_else(|| Error::other("Failed to get the user data directory"))
+}
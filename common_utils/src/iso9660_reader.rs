@@ -0,0 +1,488 @@
+use crate::error::{Error, ErrorKind, Result};
+use crate::image_payload::{
+    join_path, normalize_path, resolve_symlink, EntryKind, ImageEntry, ImagePayload,
+};
+use flate2::read::ZlibDecoder;
+use log::debug;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+
+const SECTOR_SIZE: u64 = 2048;
+const FIRST_VOLUME_DESCRIPTOR_SECTOR: u64 = 16;
+const VOLUME_DESCRIPTOR_PRIMARY: u8 = 1;
+const VOLUME_DESCRIPTOR_TERMINATOR: u8 = 255;
+const ROOT_RECORD_OFFSET: usize = 156;
+const FLAG_DIRECTORY: u8 = 0x02;
+const MAX_SYMLINK_DEPTH: usize = 16;
+const MAX_DIRECTORY_DEPTH: usize = 256;
+/// Files are only read for the desktop entry, the icons and the AppStream metadata
+const MAX_FILE_SIZE: usize = 256 * 1024 * 1024;
+const MAX_VOLUME_DESCRIPTORS: u64 = 64;
+const ZISOFS_MAGIC: [u8; 8] = [0x37, 0xE4, 0x53, 0x96, 0xC9, 0xDB, 0xD6, 0x07];
+/// The block sizes of zisofs, from 32 KiB to 128 KiB
+const ZISOFS_BLOCK_LOGS: std::ops::RangeInclusive<u8> = 15..=17;
+
+/// A directory record, with the Rock Ridge extensions AppHub cares about already applied
+#[derive(Debug, Clone)]
+struct Record {
+    name: String,
+    extent: u32,
+    data_length: u32,
+    is_directory: bool,
+    symlink: Option<String>,
+    /// Uncompressed size when the file content is zisofs compressed
+    zisofs_size: Option<u32>,
+}
+
+/// Read-only access to the ISO 9660 image of a type 1 AppImage,
+/// with Rock Ridge names, symlinks and zisofs compressed files
+pub struct Iso9660Reader<R: Read + Seek> {
+    reader: R,
+    offset: u64,
+    block_size: u64,
+    /// The number of bytes from the start of the image to the end of the file
+    image_size: u64,
+    root: Record,
+    /// Bytes to skip at the start of each System Use area, as declared by the SUSP "SP" entry
+    susp_skip: usize,
+}
+
+impl<R: Read + Seek> Iso9660Reader<R> {
+    /// Open the ISO 9660 image starting at `offset` bytes into `reader`
+    pub fn open(mut reader: R, offset: u64) -> Result<Self> {
+        let image_size = reader
+            .seek(SeekFrom::End(0))
+            .map_err(|e| Error::io("Failed to seek ISO 9660 image", e))?
+            .saturating_sub(offset);
+        let mut iso = Iso9660Reader {
+            reader,
+            offset,
+            block_size: SECTOR_SIZE,
+            image_size,
+            root: Record {
+                name: String::new(),
+                extent: 0,
+                data_length: 0,
+                is_directory: true,
+                symlink: None,
+                zisofs_size: None,
+            },
+            susp_skip: 0,
+        };
+
+        let mut sector = FIRST_VOLUME_DESCRIPTOR_SECTOR;
+        loop {
+            let descriptor = iso.read_raw(sector * SECTOR_SIZE, SECTOR_SIZE as usize)?;
+            if &descriptor[1..6] != b"CD001" {
+                return Err(Error::new(ErrorKind::InvalidAppImage, "ISO 9660 volume descriptor not found"));
+            }
+            match descriptor[0] {
+                VOLUME_DESCRIPTOR_PRIMARY => {
+                    iso.block_size = le_u16(&descriptor, 128) as u64;
+                    let root = &descriptor[ROOT_RECORD_OFFSET..ROOT_RECORD_OFFSET + 34];
+                    iso.root.extent = le_u32(root, 2);
+                    iso.root.data_length = le_u32(root, 10);
+                    break;
+                }
+                VOLUME_DESCRIPTOR_TERMINATOR => {
+                    return Err(Error::new(ErrorKind::InvalidAppImage, "ISO 9660 primary volume descriptor not found"))
+                }
+                _ => {}
+            }
+            sector += 1;
+            if sector > FIRST_VOLUME_DESCRIPTOR_SECTOR + MAX_VOLUME_DESCRIPTORS {
+                return Err(Error::new(ErrorKind::InvalidAppImage, "ISO 9660 primary volume descriptor not found"));
+            }
+        }
+
+        if iso.block_size == 0 {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "Invalid ISO 9660 block size"));
+        }
+
+        iso.susp_skip = iso.detect_susp_skip()?;
+        debug!(
+            "ISO 9660 root at block {} ({} bytes), SUSP skip {}",
+            iso.root.extent, iso.root.data_length, iso.susp_skip
+        );
+
+        Ok(iso)
+    }
+
+    /// The "." record of the root directory holds the SUSP "SP" entry when Rock Ridge is used
+    fn detect_susp_skip(&mut self) -> Result<usize> {
+        let data = self.read_raw(
+            self.root.extent as u64 * self.block_size,
+            self.block_size as usize,
+        )?;
+        let length = data[0] as usize;
+        if length < 34 {
+            return Ok(0);
+        }
+        let system_use = system_use_area(&data[..length]);
+        if system_use.len() >= 7 && &system_use[0..2] == b"SP" && system_use[4..6] == [0xBE, 0xEF] {
+            return Ok(system_use[6] as usize);
+        }
+        Ok(0)
+    }
+
+    /// List the entries below a directory. A directory extent reached twice means the
+    /// image loops back on itself.
+    fn walk(
+        &mut self,
+        dir: &Record,
+        prefix: &str,
+        depth: usize,
+        visited: &mut HashSet<u32>,
+        entries: &mut Vec<ImageEntry>,
+    ) -> Result<()> {
+        if depth > MAX_DIRECTORY_DEPTH {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Too many levels of directories: {}", prefix)));
+        }
+
+        for child in self.dir_children(dir)? {
+            let path = join_path(prefix, &child.name);
+            entries.push(to_entry(path.clone(), &child));
+            if child.is_directory {
+                if !visited.insert(child.extent) {
+                    return Err(Error::new(ErrorKind::InvalidAppImage, format!("Directory loop in image: {}", path)));
+                }
+                self.walk(&child, &path, depth + 1, visited, entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the record for a path, following symlinks
+    fn resolve(&mut self, path: &str) -> Result<Record> {
+        let mut components = normalize_path(path);
+        let mut depth = 0;
+
+        'restart: loop {
+            let mut current = self.root.clone();
+            for (index, component) in components.iter().enumerate() {
+                current = match self
+                    .dir_children(&current)?
+                    .into_iter()
+                    .find(|child| &child.name == component)
+                {
+                    Some(child) => child,
+                    None => return Err(Error::new(ErrorKind::InvalidAppImage, format!("File not found in image: {}", path))),
+                };
+
+                if let Some(target) = &current.symlink {
+                    depth += 1;
+                    if depth > MAX_SYMLINK_DEPTH {
+                        return Err(Error::new(ErrorKind::InvalidAppImage, format!("Too many levels of symlinks: {}", path)));
+                    }
+                    components = resolve_symlink(&components, index, target);
+                    continue 'restart;
+                }
+            }
+            return Ok(current);
+        }
+    }
+
+    fn dir_children(&mut self, dir: &Record) -> Result<Vec<Record>> {
+        if !dir.is_directory {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "Not a directory"));
+        }
+
+        let data = self.read_raw(dir.extent as u64 * self.block_size, dir.data_length as usize)?;
+        let mut children = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let length = data[pos] as usize;
+            if length == 0 {
+                // Records never cross a sector boundary, the rest of the sector is padding
+                let block_size = self.block_size as usize;
+                pos = (pos / block_size + 1) * block_size;
+                continue;
+            }
+            if length < 34 || pos + length > data.len() {
+                return Err(Error::new(ErrorKind::InvalidAppImage, "Invalid ISO 9660 directory record"));
+            }
+
+            let raw = &data[pos..pos + length];
+            pos += length;
+
+            let name_length = raw[32] as usize;
+            let identifier = raw
+                .get(33..33 + name_length)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidAppImage, "Invalid ISO 9660 file identifier"))?;
+            // Skip the "." and ".." records
+            if name_length == 1 && (identifier[0] == 0 || identifier[0] == 1) {
+                continue;
+            }
+
+            let mut record = Record {
+                name: iso_name(identifier),
+                extent: le_u32(raw, 2),
+                data_length: le_u32(raw, 10),
+                is_directory: raw[25] & FLAG_DIRECTORY != 0,
+                symlink: None,
+                zisofs_size: None,
+            };
+            let system_use = system_use_area(raw);
+            if system_use.len() > self.susp_skip {
+                let system_use = system_use[self.susp_skip..].to_vec();
+                self.apply_rock_ridge(&system_use, &mut record)?;
+            }
+            children.push(record);
+        }
+
+        Ok(children)
+    }
+
+    /// Apply the Rock Ridge NM (name), SL (symlink) and ZF (zisofs) entries to a record,
+    /// following CE continuation areas
+    fn apply_rock_ridge(&mut self, system_use: &[u8], record: &mut Record) -> Result<()> {
+        let mut area = system_use.to_vec();
+        let mut name = String::new();
+        let mut has_name = false;
+        let mut symlink: Vec<String> = Vec::new();
+        let mut is_symlink = false;
+        let mut link_component = String::new();
+        let mut continuations = 0;
+
+        loop {
+            let mut continuation = None;
+            let mut pos = 0;
+            while pos + 4 <= area.len() {
+                let signature = &area[pos..pos + 2];
+                let length = area[pos + 2] as usize;
+                if length < 4 || pos + length > area.len() {
+                    break;
+                }
+                let entry = &area[pos..pos + length];
+                match signature {
+                    b"NM" if length >= 5 => {
+                        let flags = entry[4];
+                        // Flags 0x02 and 0x04 stand for "." and ".."
+                        if flags & 0x06 == 0 {
+                            name.push_str(&String::from_utf8_lossy(&entry[5..]));
+                            has_name = true;
+                        }
+                    }
+                    b"SL" if length >= 5 => {
+                        is_symlink = true;
+                        let mut component_pos = 5;
+                        while component_pos + 2 <= entry.len() {
+                            let flags = entry[component_pos];
+                            let component_length = entry[component_pos + 1] as usize;
+                            let start = component_pos + 2;
+                            let end = (start + component_length).min(entry.len());
+                            if flags & 0x08 != 0 {
+                                symlink.clear();
+                                symlink.push(String::new());
+                            } else if flags & 0x04 != 0 {
+                                symlink.push("..".to_string());
+                            } else if flags & 0x02 != 0 {
+                                symlink.push(".".to_string());
+                            } else {
+                                link_component.push_str(&String::from_utf8_lossy(&entry[start..end]));
+                                // Flag 0x01 means the component continues in the next record
+                                if flags & 0x01 == 0 {
+                                    symlink.push(std::mem::take(&mut link_component));
+                                }
+                            }
+                            component_pos = end;
+                        }
+                    }
+                    b"ZF" if length >= 16 && &entry[4..6] == b"pz" => {
+                        record.zisofs_size = Some(le_u32(entry, 8));
+                    }
+                    b"CE" if length >= 28 => {
+                        continuation = Some((le_u32(entry, 4), le_u32(entry, 12), le_u32(entry, 20)));
+                    }
+                    b"ST" => break,
+                    _ => {}
+                }
+                pos += length;
+            }
+
+            match continuation {
+                Some((block, offset, length)) if continuations < 8 => {
+                    continuations += 1;
+                    area = self.read_raw(
+                        block as u64 * self.block_size + offset as u64,
+                        length as usize,
+                    )?;
+                }
+                _ => break,
+            }
+        }
+
+        if has_name {
+            record.name = name;
+        }
+        if is_symlink {
+            let target = if symlink.len() == 1 && symlink[0].is_empty() {
+                "/".to_string()
+            } else {
+                symlink.join("/")
+            };
+            record.symlink = Some(target);
+        }
+        Ok(())
+    }
+
+    fn read_record_data(&mut self, record: &Record) -> Result<Vec<u8>> {
+        let data = self.read_raw(
+            record.extent as u64 * self.block_size,
+            record.data_length as usize,
+        )?;
+        match record.zisofs_size {
+            Some(size) => decompress_zisofs(&data, size as usize),
+            None => Ok(data),
+        }
+    }
+
+    /// Read raw bytes at a position relative to the start of the image
+    fn read_raw(&mut self, position: u64, len: usize) -> Result<Vec<u8>> {
+        if position.saturating_add(len as u64) > self.image_size {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "ISO 9660 image is truncated"));
+        }
+
+        let mut buf = vec![0u8; len];
+        self.reader
+            .seek(SeekFrom::Start(self.offset + position))
+            .map_err(|e| Error::io("Failed to seek ISO 9660 image", e))?;
+        self.reader
+            .read_exact(&mut buf)
+            .map_err(|e| Error::io("Failed to read ISO 9660 image", e))?;
+        Ok(buf)
+    }
+}
+
+impl<R: Read + Seek> ImagePayload for Iso9660Reader<R> {
+    fn entries(&mut self) -> Result<Vec<ImageEntry>> {
+        let mut entries = Vec::new();
+        let root = self.root.clone();
+        let mut visited = HashSet::from([root.extent]);
+        self.walk(&root, "", 0, &mut visited, &mut entries)?;
+        Ok(entries)
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<ImageEntry>> {
+        let dir = self.resolve(path)?;
+        let prefix = normalize_path(path).join("/");
+        Ok(self
+            .dir_children(&dir)?
+            .iter()
+            .map(|child| to_entry(join_path(&prefix, &child.name), child))
+            .collect())
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let record = self.resolve(path)?;
+        if record.is_directory {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Not a regular file: {}", path)));
+        }
+        self.read_record_data(&record)
+    }
+}
+
+fn to_entry(path: String, record: &Record) -> ImageEntry {
+    let (kind, size) = if let Some(target) = &record.symlink {
+        (EntryKind::Symlink(target.clone()), 0)
+    } else if record.is_directory {
+        (EntryKind::Directory, 0)
+    } else {
+        (
+            EntryKind::File,
+            record.zisofs_size.unwrap_or(record.data_length) as u64,
+        )
+    };
+    ImageEntry { path, kind, size }
+}
+
+/// The System Use area follows the file identifier, padded to an even offset
+fn system_use_area(record: &[u8]) -> &[u8] {
+    let name_length = record[32] as usize;
+    let mut start = 33 + name_length;
+    if start % 2 == 1 {
+        start += 1;
+    }
+    record.get(start..).unwrap_or(&[])
+}
+
+/// Plain ISO 9660 names carry a ";1" version suffix and a trailing dot when they have
+/// no extension
+fn iso_name(identifier: &[u8]) -> String {
+    let name = String::from_utf8_lossy(identifier).to_string();
+    let name = match name.rfind(';') {
+        Some(index) => name[..index].to_string(),
+        None => name,
+    };
+    name.trim_end_matches('.').to_string()
+}
+
+/// Decompress a file stored with zisofs (the "pz" transparent compression of mkzftree)
+fn decompress_zisofs(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    if data.len() < 16 || data[0..8] != ZISOFS_MAGIC {
+        // Files too small to be compressed are stored as is
+        return Ok(data.to_vec());
+    }
+
+    if size > MAX_FILE_SIZE {
+        return Err(Error::new(ErrorKind::InvalidAppImage, format!("File of {} bytes is too large to read", size)));
+    }
+    if !ZISOFS_BLOCK_LOGS.contains(&data[13]) {
+        return Err(Error::new(ErrorKind::InvalidAppImage, format!("Invalid zisofs block size: 2^{}", data[13])));
+    }
+    let header_size = data[12] as usize * 4;
+    let block_size = 1usize << data[13];
+    let block_count = size.div_ceil(block_size);
+
+    let mut content = Vec::with_capacity(size.min(data.len()));
+    for index in 0..block_count {
+        let pointer = header_size + index * 4;
+        if pointer + 8 > data.len() {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "Truncated zisofs block table"));
+        }
+        let start = le_u32(data, pointer) as usize;
+        let end = le_u32(data, pointer + 4) as usize;
+        let expected = block_size.min(size - content.len());
+        if start == end {
+            content.resize(content.len() + expected, 0);
+            continue;
+        }
+        let block = data
+            .get(start..end)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidAppImage, "Truncated zisofs block"))?;
+        let mut decompressed = Vec::with_capacity(expected);
+        ZlibDecoder::new(block)
+            .take(expected as u64)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| Error::io("Failed to decompress zisofs block", e))?;
+        content.extend_from_slice(&decompressed[..expected.min(decompressed.len())]);
+    }
+
+    Ok(content)
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_zisofs_rejects_invalid_block_size() {
+        let mut data = ZISOFS_MAGIC.to_vec();
+        data.extend(1024u32.to_le_bytes());
+        data.extend([4, 64, 0, 0]);
+
+        let error = decompress_zisofs(&data, 1024).err().unwrap();
+        assert_eq!(error.kind, ErrorKind::InvalidAppImage);
+    }
+}
@@ -0,0 +1,106 @@
+/// The background colors of the avatars, picked from the name
+const COLORS: [&str; 8] = [
+    "#3584e4", "#2190a4", "#3a944a", "#c88800", "#ed5b00", "#e62d42", "#d56199", "#9141ac",
+];
+
+/// The size of the generated SVG
+const SIZE: u32 = 256;
+
+/// The glyphs drawn on the avatars, as strokes on a grid 4 units wide and 6 units high.
+/// Each stroke is a list of `x,y` points, strokes are separated by `|`.
+/// System fonts may be missing, so the letters are drawn as paths instead of text.
+const GLYPHS: [(char, &str); 36] = [
+    ('A', "0,6 2,0 4,6|0.7,4 3.3,4"),
+    ('B', "0,3 3,3 4,4 4,5 3,6 0,6 0,0 3,0 4,1 4,2 3,3"),
+    ('C', "4,1 3,0 1,0 0,1 0,5 1,6 3,6 4,5"),
+    ('D', "0,0 0,6 2,6 4,4 4,2 2,0 0,0"),
+    ('E', "4,0 0,0 0,6 4,6|0,3 3,3"),
+    ('F', "4,0 0,0 0,6|0,3 3,3"),
+    ('G', "4,1 3,0 1,0 0,1 0,5 1,6 3,6 4,5 4,3 2,3"),
+    ('H', "0,0 0,6|4,0 4,6|0,3 4,3"),
+    ('I', "1,0 3,0|2,0 2,6|1,6 3,6"),
+    ('J', "4,0 4,5 3,6 1,6 0,5"),
+    ('K', "0,0 0,6|4,0 0,4|1.5,2.5 4,6"),
+    ('L', "0,0 0,6 4,6"),
+    ('M', "0,6 0,0 2,3 4,0 4,6"),
+    ('N', "0,6 0,0 4,6 4,0"),
+    ('O', "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0"),
+    ('P', "0,6 0,0 3,0 4,1 4,2 3,3 0,3"),
+    ('Q', "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0|2.5,4.5 4,6"),
+    ('R', "0,6 0,0 3,0 4,1 4,2 3,3 0,3|2,3 4,6"),
+    ('S', "4,1 3,0 1,0 0,1 0,2 1,3 3,3 4,4 4,5 3,6 1,6 0,5"),
+    ('T', "0,0 4,0|2,0 2,6"),
+    ('U', "0,0 0,5 1,6 3,6 4,5 4,0"),
+    ('V', "0,0 2,6 4,0"),
+    ('W', "0,0 1,6 2,3 3,6 4,0"),
+    ('X', "0,0 4,6|4,0 0,6"),
+    ('Y', "0,0 2,3 4,0|2,3 2,6"),
+    ('Z', "0,0 4,0 0,6 4,6"),
+    ('0', "1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,1 1,0|4,1 0,5"),
+    ('1', "1,1 2,0 2,6|1,6 3,6"),
+    ('2', "0,1 1,0 3,0 4,1 4,2 0,6 4,6"),
+    ('3', "0,1 1,0 3,0 4,1 4,2 3,3 1,3|3,3 4,4 4,5 3,6 1,6 0,5"),
+    ('4', "3,6 3,0 0,4 4,4"),
+    ('5', "4,0 0,0 0,3 3,3 4,4 4,5 3,6 1,6 0,5"),
+    ('6', "3,0 1,0 0,1 0,5 1,6 3,6 4,5 4,4 3,3 0,3"),
+    ('7', "0,0 4,0 1,6"),
+    ('8', "1,3 0,2 0,1 1,0 3,0 4,1 4,2 3,3 1,3 0,4 0,5 1,6 3,6 4,5 4,4 3,3"),
+    ('9', "4,3 1,3 0,2 0,1 1,0 3,0 4,1 4,5 3,6 1,6"),
+];
+
+/// An SVG icon showing the first letter or digit of the name on a colored circle,
+/// for the apps that ship no usable icon. The color depends on the name.
+pub fn letter_avatar_svg(name: &str) -> String {
+    let hash = name
+        .bytes()
+        .fold(0usize, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as usize));
+    let color = COLORS[hash % COLORS.len()];
+    let glyph = name
+        .chars()
+        .find(|c| c.is_ascii_alphanumeric())
+        .and_then(|letter| {
+            GLYPHS
+                .iter()
+                .find(|(glyph, _)| *glyph == letter.to_ascii_uppercase())
+        })
+        .map(|(_, strokes)| {
+            format!(
+                "<path d=\"{}\" fill=\"none\" stroke=\"#ffffff\" stroke-width=\"18\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                glyph_path(strokes)
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\"><circle cx=\"{1}\" cy=\"{1}\" r=\"{1}\" fill=\"{2}\"/>{3}</svg>",
+        SIZE,
+        SIZE / 2,
+        color,
+        glyph
+    )
+}
+
+/// The path data of a glyph, centered on the avatar with 20 pixels per grid unit
+fn glyph_path(strokes: &str) -> String {
+    const UNIT: f32 = 20.0;
+    let left = (SIZE as f32 - 4.0 * UNIT) / 2.0;
+    let top = (SIZE as f32 - 6.0 * UNIT) / 2.0;
+
+    strokes
+        .split('|')
+        .map(|stroke| {
+            stroke
+                .split(' ')
+                .enumerate()
+                .map(|(index, point)| {
+                    let (x, y) = point.split_once(',').unwrap_or(("0", "0"));
+                    let x = left + x.parse::<f32>().unwrap_or(0.0) * UNIT;
+                    let y = top + y.parse::<f32>().unwrap_or(0.0) * UNIT;
+                    format!("{}{} {}", if index == 0 { "M" } else { "L" }, x, y)
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
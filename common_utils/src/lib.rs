@@ -1,6 +1,28 @@
 pub mod desktop_file_builder;
 pub mod file_system_helpers;
 pub mod app_images_helpers;
+pub mod app_id;
+pub mod app_image_preview;
+pub mod app_image_reader;
+pub mod backend_event;
 pub mod desktop_file_helpers;
+pub mod desktop_file_parser;
+pub mod duplicate_policy;
 pub mod desktop_entry;
+pub mod desktop_entry_validator;
+pub mod elf_reader;
+pub mod error;
+pub mod exec_command;
+pub mod image_payload;
+pub mod install_layout;
+pub mod install_scope;
+pub mod iso9660_reader;
+pub mod letter_avatar;
 pub mod models;
+pub mod registry;
+pub mod signature;
+pub mod squashfs_reader;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod test_fixtures;
+pub mod update_info;
+pub mod zsync;
@@ -0,0 +1,13 @@
+use crate::registry::RegistryEntry;
+use std::collections::BTreeMap;
+
+/// Everything known about an installed app
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppInfo {
+    /// The registry entry of the app, with its parsed update information
+    pub app: RegistryEntry,
+    /// The keys of the `[Desktop Entry]` group of the installed desktop file,
+    /// if it can be read
+    pub desktop_entry: Option<BTreeMap<String, String>>,
+}
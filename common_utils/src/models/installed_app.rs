@@ -1,11 +1,22 @@
+use crate::install_scope::InstallScope;
+use crate::registry::InstalledVersion;
+use crate::update_info::UpdateInfo;
 use std::path::PathBuf;
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct InstalledApp {
+    pub id: String,
     pub name: String,
     pub icon_path: Option<PathBuf>,
     pub app_path: String,
+    /// Size of the AppImage in bytes
+    pub size: u64,
     pub version: Option<String>,
     pub categories: Option<String>,
+    pub update_info: Option<UpdateInfo>,
+    pub scope: InstallScope,
+    /// The versions kept on disk, the current one first
+    #[serde(default)]
+    pub versions: Vec<InstalledVersion>,
 }
@@ -1 +1,3 @@
-pub mod installed_app;
\ No newline at end of file
+pub mod app_info;
+pub mod installed_app;
+pub mod verify_report;
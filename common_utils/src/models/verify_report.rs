@@ -0,0 +1,56 @@
+use crate::install_scope::InstallScope;
+use std::fmt;
+use std::path::PathBuf;
+
+/// The result of the verification of an installed app
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub id: String,
+    pub name: String,
+    pub scope: InstallScope,
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A difference between the registry and the installed files
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum VerifyIssue {
+    AppImageMissing { path: PathBuf },
+    /// The content of the AppImage changed since it was installed
+    #[serde(rename_all = "camelCase")]
+    AppImageModified { path: PathBuf, expected_sha256: String, actual_sha256: String },
+    IconMissing { path: PathBuf },
+    DesktopEntryMissing { path: PathBuf },
+    DesktopEntryInvalid { path: PathBuf, reason: String },
+    /// The desktop entry does not start the installed AppImage
+    ExecMismatch { expected: String, actual: Option<String> },
+}
+
+impl fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyIssue::AppImageMissing { path } => write!(f, "AppImage {:?} is missing", path),
+            VerifyIssue::AppImageModified { path, .. } => {
+                write!(f, "AppImage {:?} was modified since it was installed", path)
+            }
+            VerifyIssue::IconMissing { path } => write!(f, "icon {:?} is missing", path),
+            VerifyIssue::DesktopEntryMissing { path } => {
+                write!(f, "desktop entry {:?} is missing", path)
+            }
+            VerifyIssue::DesktopEntryInvalid { path, reason } => {
+                write!(f, "desktop entry {:?} is invalid: {}", path, reason)
+            }
+            VerifyIssue::ExecMismatch { expected, actual } => match actual {
+                Some(actual) => write!(f, "desktop entry runs {} instead of {}", actual, expected),
+                None => write!(f, "desktop entry has no Exec, expected {}", expected),
+            },
+        }
+    }
+}
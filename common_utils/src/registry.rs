@@ -0,0 +1,578 @@
+use crate::app_id::sanitize_app_id;
+use crate::app_image_reader::AppImageReader;
+use crate::app_images_helpers::find_theme_icons;
+use crate::desktop_file_builder::DesktopFileBuilder;
+use crate::error::{Error, ErrorKind, Result};
+use crate::file_system_helpers::file_sha256;
+use crate::install_scope::{user_data_dir, InstallScope};
+use crate::update_info::{read_update_info, UpdateInfo};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable overriding the location of the system registry
+pub const REGISTRY_PATH_ENV: &str = "APPHUB_REGISTRY_PATH";
+const DEFAULT_REGISTRY_PATH: &str = "/var/lib/apphub/registry.json";
+const REGISTRY_VERSION: u32 = 1;
+
+/// An AppImage installed by AppHub
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryEntry {
+    pub id: String,
+    pub name: String,
+    /// File name of the AppImage the app was installed from
+    pub original_file_name: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub installed_at: u64,
+    pub version: Option<String>,
+    pub categories: Option<String>,
+    pub update_info: Option<UpdateInfo>,
+    /// The AppImage run by the desktop entry, a link to the current version when
+    /// versions are kept
+    pub app_image_path: PathBuf,
+    pub icon_paths: Vec<PathBuf>,
+    pub desktop_file_path: PathBuf,
+    /// Entries written before scopes existed are system installs
+    #[serde(default)]
+    pub scope: InstallScope,
+    /// The versions kept on disk, the current one first. Apps installed before versions
+    /// were kept have none, their AppImage is at `app_image_path`.
+    #[serde(default)]
+    pub versions: Vec<InstalledVersion>,
+}
+
+/// A version of an app kept on disk, to roll back to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledVersion {
+    pub version: Option<String>,
+    /// File name of the AppImage the version was installed from
+    pub original_file_name: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub installed_at: u64,
+    /// The AppImage of the version, in its own directory
+    pub app_image_path: PathBuf,
+}
+
+impl InstalledVersion {
+    /// The name of the directory of the version, which also designates it on the command line
+    pub fn dir_name(&self) -> String {
+        self.app_image_path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|dir_name| dir_name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Whether the version is designated by the given version number or directory name
+    pub fn matches(&self, version: &str) -> bool {
+        self.version.as_deref() == Some(version) || self.dir_name() == version
+    }
+}
+
+impl RegistryEntry {
+    /// Make the kept version at the given index the current one
+    pub fn set_current_version(&mut self, index: usize) {
+        let current = self.versions.remove(index);
+        self.version = current.version.clone();
+        self.original_file_name = current.original_file_name.clone();
+        self.sha256 = current.sha256.clone();
+        self.size = current.size;
+        self.installed_at = current.installed_at;
+        self.versions.insert(0, current);
+    }
+
+    /// Forget the kept versions but the current one and the `keep - 1` most recently
+    /// installed others, returning the forgotten versions
+    pub fn prune_versions(&mut self, keep: usize) -> Vec<InstalledVersion> {
+        if self.versions.is_empty() {
+            return Vec::new();
+        }
+        let mut others = self.versions.split_off(1);
+        others.sort_by_key(|version| std::cmp::Reverse(version.installed_at));
+        let pruned = others.split_off(others.len().min(keep.saturating_sub(1)));
+        self.versions.extend(others);
+        pruned
+    }
+}
+
+/// How an installed app is designated on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppSelector {
+    /// The stable id of the app. Names are accepted as a fallback
+    Id(String),
+    /// The display name of the app
+    Name(String),
+}
+
+impl std::fmt::Display for AppSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppSelector::Id(id) => write!(f, "{}", id),
+            AppSelector::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The list of apps installed in a scope, stored as JSON. It is written by the backend only.
+/// Each scope has its own registry, so that user installs need no admin rights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(skip)]
+    path: PathBuf,
+    version: u32,
+    apps: Vec<RegistryEntry>,
+}
+
+impl Registry {
+    /// The location of the registry of the given scope
+    pub fn location(scope: InstallScope) -> Result<PathBuf> {
+        match scope {
+            InstallScope::System => match std::env::var_os(REGISTRY_PATH_ENV) {
+                Some(path) => Ok(PathBuf::from(path)),
+                None => Ok(PathBuf::from(DEFAULT_REGISTRY_PATH)),
+            },
+            InstallScope::User => Ok(user_data_dir()?.join("apphub").join("registry.json")),
+        }
+    }
+
+    /// An empty registry stored at the given path
+    pub fn empty(path: &Path) -> Self {
+        Registry {
+            path: path.to_path_buf(),
+            version: REGISTRY_VERSION,
+            apps: Vec::new(),
+        }
+    }
+
+    /// Load the registry of the given scope
+    pub fn load(scope: InstallScope) -> Result<Self> {
+        Self::load_from(&Self::location(scope)?)
+    }
+
+    /// Load the registry of the given scope. When the registry was never written,
+    /// it is recovered from the desktop files of the apps installed by AppHub and saved,
+    /// so that the AppImages are hashed once
+    pub fn load_or_recover(scope: InstallScope) -> Result<Self> {
+        let path = Self::location(scope)?;
+        if path.exists() {
+            return Self::load_from(&path);
+        }
+
+        info!("Registry not found, recovering it from the desktop files");
+        let applications_dir = scope.applications_dir()?;
+        if !applications_dir.exists() {
+            return Ok(Self::empty(&path));
+        }
+        let registry = Self::recover(&path, &applications_dir, scope, true)?;
+        if !registry.apps.is_empty() {
+            if let Err(err) = registry.save() {
+                warn!("Failed to save the recovered registry: {}", err);
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Load the registry of the given scope for the views listing the apps. When the
+    /// registry was never written, the apps are read from their desktop files without
+    /// hashing the AppImages, and their checksums are left empty. Nothing is saved.
+    pub fn load_or_list(scope: InstallScope) -> Result<Self> {
+        let path = Self::location(scope)?;
+        if path.exists() {
+            return Self::load_from(&path);
+        }
+
+        let applications_dir = scope.applications_dir()?;
+        if !applications_dir.exists() {
+            return Ok(Self::empty(&path));
+        }
+        Self::recover(&path, &applications_dir, scope, false)
+    }
+
+    /// Load the registry from the given path. A missing file is an empty registry.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            info!("Registry not found at {:?}, starting with an empty one", path);
+            return Ok(Self::empty(path));
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| Error::new(ErrorKind::Registry, "Failed to read registry").caused_by(e))?;
+        let mut registry: Registry = serde_json::from_str(&content)
+            .map_err(|e| Error::new(ErrorKind::Registry, "Failed to parse registry").caused_by(e))?;
+        registry.path = path.to_path_buf();
+        Ok(registry)
+    }
+
+    /// Write the registry, replacing the previous file atomically
+    pub fn save(&self) -> Result<()> {
+        let registry_error = |message: &str| Error::new(ErrorKind::Registry, message);
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| registry_error("Failed to create registry directory").caused_by(e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| registry_error("Failed to serialize registry").caused_by(e))?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, content)
+            .and_then(|_| fs::rename(&temp_path, &self.path))
+            .map_err(|e| registry_error("Failed to write registry").caused_by(e))?;
+
+        info!("Registry saved to {:?}", self.path);
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn apps(&self) -> &[RegistryEntry] {
+        &self.apps
+    }
+
+    pub fn find_by_id(&self, id: &str) -> Option<&RegistryEntry> {
+        self.apps.iter().find(|app| app.id == id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&RegistryEntry> {
+        self.apps.iter().find(|app| app.name == name)
+    }
+
+    /// Find an app by id or by name
+    pub fn find(&self, selector: &AppSelector) -> Option<&RegistryEntry> {
+        match selector {
+            AppSelector::Id(id) => self.find_by_id(id).or_else(|| self.find_by_name(id)),
+            AppSelector::Name(name) => self.find_by_name(name),
+        }
+    }
+
+    /// Find an app in the registries of the given scopes, in order
+    pub fn find_in_scopes(
+        selector: &AppSelector,
+        scopes: &[InstallScope],
+    ) -> Result<Option<RegistryEntry>> {
+        for scope in scopes {
+            if let Some(app) = Self::load_or_recover(*scope)?.find(selector) {
+                return Ok(Some(app.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Add an app, replacing the entry with the same id if any
+    pub fn upsert(&mut self, entry: RegistryEntry) {
+        match self.apps.iter_mut().find(|app| app.id == entry.id) {
+            Some(app) => *app = entry,
+            None => self.apps.push(entry),
+        }
+    }
+
+    /// A free id for a version of an app installed next to the one with the given id,
+    /// like `<id>-<version>`
+    pub fn side_by_side_id(&self, id: &str, version: Option<&str>) -> String {
+        let base = version
+            .and_then(sanitize_app_id)
+            .map(|version| format!("{}-{}", id, version))
+            .unwrap_or_else(|| id.to_string());
+        std::iter::once(base.clone())
+            .chain((2..).map(|index| format!("{}-{}", base, index)))
+            .find(|candidate| self.find_by_id(candidate).is_none())
+            .unwrap_or(base)
+    }
+
+    /// Remove the app with the given id, returning its entry
+    pub fn remove(&mut self, id: &str) -> Option<RegistryEntry> {
+        let index = self.apps.iter().position(|app| app.id == id)?;
+        Some(self.apps.remove(index))
+    }
+
+    /// Recover the registry from the desktop files written by AppHub in the given
+    /// applications directory
+    pub fn rebuild_from_desktop_files(
+        path: &Path,
+        applications_dir: &Path,
+        scope: InstallScope,
+    ) -> Result<Self> {
+        Self::recover(path, applications_dir, scope, true)
+    }
+
+    /// Recover the registry, hashing the AppImages unless `with_checksums` is false
+    fn recover(
+        path: &Path,
+        applications_dir: &Path,
+        scope: InstallScope,
+        with_checksums: bool,
+    ) -> Result<Self> {
+        let mut registry = Self::empty(path);
+
+        let entries = fs::read_dir(applications_dir)
+            .map_err(|e| Error::io(format!("Failed to read {:?}", applications_dir), e))?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let desktop_file_path = entry.path();
+            if desktop_file_path.extension().and_then(|ext| ext.to_str()) != Some("desktop") {
+                continue;
+            }
+
+            let desktop_entry = match DesktopFileBuilder::from_desktop_entry_path(&desktop_file_path, true) {
+                Ok(desktop_entry) => desktop_entry,
+                Err(err) => {
+                    info!("Skipping {:?}: {}", desktop_file_path, err);
+                    continue;
+                }
+            };
+
+            match registry_entry_from_desktop_file(
+                &desktop_file_path,
+                &desktop_entry,
+                scope,
+                with_checksums,
+            ) {
+                Ok(entry) => {
+                    info!("Recovered {} from {:?}", entry.name, desktop_file_path);
+                    registry.upsert(entry);
+                }
+                Err(err) => error!("Failed to recover {:?}: {}", desktop_file_path, err),
+            }
+        }
+
+        Ok(registry)
+    }
+}
+
+fn registry_entry_from_desktop_file(
+    desktop_file_path: &Path,
+    desktop_entry: &DesktopFileBuilder,
+    scope: InstallScope,
+    with_checksums: bool,
+) -> Result<RegistryEntry> {
+    let name = desktop_entry
+        .name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidDesktopEntry, "Name is missing"))?;
+    let app_image_path = PathBuf::from(desktop_entry.exec_command()?.program);
+
+    let metadata = fs::metadata(&app_image_path)
+        .map_err(|e| Error::io(format!("AppImage {:?} not found", app_image_path), e))?;
+    // the user applications directory also holds the desktop files of other programs
+    AppImageReader::open(&app_image_path)?;
+    let installed_at = modified_at(&metadata);
+
+    let update_info = match desktop_entry.update_information() {
+        Some(value) => UpdateInfo::parse(&value).ok(),
+        None => read_update_info(&app_image_path).ok().flatten(),
+    };
+
+    // icons are named in the theme of the scope, older installs have a path
+    let icon_paths = match desktop_entry.icon() {
+        Some(icon) if Path::new(&icon).is_absolute() => vec![PathBuf::from(icon)],
+        Some(icon) => find_theme_icons(&scope.icon_theme_dir()?, &icon),
+        None => Vec::new(),
+    };
+
+    let mut entry = RegistryEntry {
+        id: desktop_file_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.clone()),
+        name,
+        original_file_name: app_image_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sha256: recovered_sha256(&app_image_path, with_checksums)?,
+        size: metadata.len(),
+        installed_at,
+        version: desktop_entry.app_image_version(),
+        categories: desktop_entry.categories(),
+        update_info,
+        app_image_path,
+        icon_paths,
+        desktop_file_path: desktop_file_path.to_path_buf(),
+        scope,
+        versions: Vec::new(),
+    };
+
+    // the desktop entry of an app keeping its versions runs a link to the current one
+    if fs::read_link(&entry.app_image_path).is_ok() {
+        entry.versions = recover_versions(&entry.app_image_path, with_checksums);
+        if !entry.versions.is_empty() {
+            entry.set_current_version(0);
+        }
+    }
+    Ok(entry)
+}
+
+/// The versions kept next to the launcher of an app, the current one first
+fn recover_versions(launcher: &Path, with_checksums: bool) -> Vec<InstalledVersion> {
+    let Some(app_dir) = launcher.parent() else {
+        return Vec::new();
+    };
+    let Ok(version_dirs) = fs::read_dir(app_dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<InstalledVersion> = version_dirs
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter_map(|version_dir| fs::read_dir(version_dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| match recover_version(&entry.path(), with_checksums) {
+            Ok(version) => Some(version),
+            Err(err) => {
+                info!("Skipping {:?}: {}", entry.path(), err);
+                None
+            }
+        })
+        .collect();
+
+    versions.sort_by_key(|version| std::cmp::Reverse(version.installed_at));
+    let current = fs::canonicalize(launcher).ok();
+    if let Some(index) = versions
+        .iter()
+        .position(|version| fs::canonicalize(&version.app_image_path).ok() == current)
+    {
+        let current = versions.remove(index);
+        versions.insert(0, current);
+    }
+    versions
+}
+
+fn recover_version(app_image_path: &Path, with_checksums: bool) -> Result<InstalledVersion> {
+    let metadata = fs::metadata(app_image_path)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", app_image_path), e))?;
+    let version = AppImageReader::open(app_image_path)?
+        .read_desktop_file()
+        .and_then(|content| DesktopFileBuilder::from_desktop_entry_content(&content, false))
+        .ok()
+        .and_then(|desktop_entry| desktop_entry.app_image_version());
+
+    Ok(InstalledVersion {
+        version,
+        original_file_name: app_image_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sha256: recovered_sha256(app_image_path, with_checksums)?,
+        size: metadata.len(),
+        installed_at: modified_at(&metadata),
+        app_image_path: app_image_path.to_path_buf(),
+    })
+}
+
+/// The SHA-256 of a recovered AppImage, empty when the AppImages are not hashed
+fn recovered_sha256(app_image_path: &Path, with_checksums: bool) -> Result<String> {
+    if with_checksums {
+        file_sha256(app_image_path)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// The time a file was last written, in seconds since the Unix epoch
+fn modified_at(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::AppImageFixture;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("registry.json");
+
+        let mut registry = Registry::load_from(&path).unwrap();
+        assert!(registry.apps().is_empty());
+
+        let entry = RegistryEntry {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            original_file_name: "Test-x86_64.AppImage".to_string(),
+            sha256: "00".repeat(32),
+            size: 42,
+            installed_at: 1_700_000_000,
+            version: Some("1.0".to_string()),
+            categories: Some("Utility;".to_string()),
+            update_info: Some(UpdateInfo::Zsync {
+                url: "https://example.com/Test.AppImage.zsync".to_string(),
+            }),
+            app_image_path: dir.path().join("Test-x86_64.AppImage"),
+            icon_paths: vec![dir.path().join("icons/test.png")],
+            desktop_file_path: dir.path().join("test.desktop"),
+            scope: InstallScope::User,
+            versions: Vec::new(),
+        };
+        registry.upsert(entry.clone());
+        registry.save().unwrap();
+
+        let mut registry = Registry::load_from(&path).unwrap();
+        assert_eq!(registry.find_by_name("Test"), Some(&entry));
+        assert_eq!(registry.find(&AppSelector::Id("test".to_string())), Some(&entry));
+        // ids given by older callers may be display names
+        assert_eq!(registry.find(&AppSelector::Id("Test".to_string())), Some(&entry));
+        assert!(registry.find(&AppSelector::Name("test".to_string())).is_none());
+        assert_eq!(registry.remove("test"), Some(entry));
+        assert!(registry.find_by_id("test").is_none());
+    }
+
+    #[test]
+    fn test_rebuild_from_desktop_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let applications_dir = dir.path().join("applications");
+        fs::create_dir(&applications_dir).unwrap();
+        let app_image_path = dir.path().join("Test.AppImage");
+        AppImageFixture::with_app("test").write_to(&app_image_path);
+
+        let mut builder = DesktopFileBuilder::new();
+        builder
+            .set_type("Application".to_string())
+            .set_name("Test".to_string())
+            .set_exec(format!("{} --no-sandbox", app_image_path.to_string_lossy()))
+            .set_icon(dir.path().join("test.png").to_string_lossy().to_string());
+        fs::write(
+            applications_dir.join("Test.desktop"),
+            builder.generate_content_string().unwrap(),
+        )
+        .unwrap();
+        // desktop files not written by AppHub are ignored
+        fs::write(
+            applications_dir.join("other.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Other\nExec=other\n",
+        )
+        .unwrap();
+        fs::write(
+            applications_dir.join("shell.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Shell\nExec=/bin/sh\n",
+        )
+        .unwrap();
+
+        let registry =
+            Registry::rebuild_from_desktop_files(
+                &dir.path().join("registry.json"),
+                &applications_dir,
+                InstallScope::System,
+            )
+            .unwrap();
+
+        assert_eq!(registry.apps().len(), 1);
+        let entry = registry.find_by_name("Test").unwrap();
+        assert_eq!(entry.app_image_path, app_image_path);
+        assert_eq!(entry.sha256, file_sha256(&app_image_path).unwrap());
+        assert_eq!(entry.desktop_file_path, applications_dir.join("Test.desktop"));
+    }
+}
@@ -0,0 +1,459 @@
+use crate::elf_reader::{read_section, read_sections};
+use crate::error::{Error, ErrorKind, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Name of the ELF section holding the detached signature of the AppImage digest
+pub const SIGNATURE_SECTION: &str = ".sha256_sig";
+/// Name of the ELF section holding the public key of the signer
+pub const SIGNATURE_KEY_SECTION: &str = ".sig_key";
+
+/// Outcome of the verification of the signature embedded in an AppImage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum SignatureStatus {
+    /// The signature is valid. `trusted` tells whether the signing key is in the
+    /// keyring of trusted publishers
+    Signed { trusted: bool, fingerprint: String },
+    /// The AppImage carries no signature
+    Unsigned,
+    /// The signature does not match the AppImage or cannot be checked
+    Invalid { reason: String },
+    /// The AppImage is signed, but the tools checking the signature are missing
+    Unverified { reason: String },
+}
+
+/// What to do with AppImages that are unsigned or signed by an untrusted key.
+/// AppImages with an invalid signature are always refused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignaturePolicy {
+    Refuse,
+    #[default]
+    Warn,
+    Allow,
+}
+
+impl SignaturePolicy {
+    pub const ALL: [SignaturePolicy; 3] = [
+        SignaturePolicy::Refuse,
+        SignaturePolicy::Warn,
+        SignaturePolicy::Allow,
+    ];
+
+    /// Check whether an AppImage with the given signature status can be installed
+    pub fn enforce(&self, status: &SignatureStatus) -> Result<()> {
+        let problem = match status {
+            SignatureStatus::Signed { trusted: true, .. } => return Ok(()),
+            SignatureStatus::Invalid { reason } => {
+                return Err(Error::new(
+                    ErrorKind::Signature,
+                    format!("Invalid AppImage signature: {}", reason),
+                ))
+            }
+            SignatureStatus::Signed {
+                trusted: false,
+                fingerprint,
+            } => format!("AppImage signed by an untrusted key: {}", fingerprint),
+            SignatureStatus::Unsigned => "AppImage is not signed".to_string(),
+            SignatureStatus::Unverified { reason } => {
+                format!("AppImage signature not verified: {}", reason)
+            }
+        };
+
+        match self {
+            SignaturePolicy::Refuse => Err(Error::new(ErrorKind::Signature, problem)),
+            SignaturePolicy::Warn => {
+                warn!("{}", problem);
+                Ok(())
+            }
+            SignaturePolicy::Allow => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for SignaturePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignaturePolicy::Refuse => write!(f, "refuse"),
+            SignaturePolicy::Warn => write!(f, "warn"),
+            SignaturePolicy::Allow => write!(f, "allow"),
+        }
+    }
+}
+
+impl FromStr for SignaturePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        SignaturePolicy::ALL
+            .into_iter()
+            .find(|policy| policy.to_string() == value)
+            .ok_or_else(|| format!("Unknown signature policy: {}", value))
+    }
+}
+
+/// The default location of the keyring of trusted publishers: a directory of public
+/// keys, armored or binary
+pub fn default_trusted_keys_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("apphub").join("trusted-keys"))
+}
+
+/// Compute the digest of an AppImage as required by the AppImage specification:
+/// the SHA-256 of the file, with the signature sections read as zeros.
+/// Returns the hex encoded digest, which is the data signed by the publisher.
+pub fn app_image_digest(path: &Path) -> Result<String> {
+    let mut file = File::open(path).map_err(|e| Error::io("Failed to open AppImage", e))?;
+    let skipped: Vec<(u64, u64)> = read_sections(&mut file)?
+        .into_iter()
+        .filter(|section| section.name == SIGNATURE_SECTION || section.name == SIGNATURE_KEY_SECTION)
+        .map(|section| (section.offset, section.offset + section.size))
+        .collect();
+
+    let mut file = File::open(path).map_err(|e| Error::io("Failed to open AppImage", e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut position = 0u64;
+    loop {
+        let read = file
+            .read(&mut buf)
+            .map_err(|e| Error::io("Failed to read AppImage", e))?;
+        if read == 0 {
+            break;
+        }
+
+        let chunk = &mut buf[..read];
+        let chunk_end = position + read as u64;
+        for (start, end) in &skipped {
+            let (start, end) = ((*start).max(position), (*end).min(chunk_end));
+            if start < end {
+                chunk[(start - position) as usize..(end - position) as usize].fill(0);
+            }
+        }
+        hasher.update(chunk);
+        position = chunk_end;
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Verify the signature embedded in an AppImage against the embedded key and the
+/// keyring of trusted publishers found in `trusted_keys_dir`.
+/// The verification is delegated to `gpgv`, and armored keys are read with `gpg`. When
+/// they are not installed, a signed AppImage is reported as unverified.
+pub fn verify_app_image_signature(
+    path: &Path,
+    trusted_keys_dir: Option<&Path>,
+) -> Result<SignatureStatus> {
+    let mut file = File::open(path).map_err(|e| Error::io("Failed to open AppImage", e))?;
+
+    let signature = match read_section(&mut file, SIGNATURE_SECTION)?.map(trim_nul) {
+        Some(signature) if !signature.is_empty() => signature,
+        _ => {
+            info!("AppImage is not signed");
+            return Ok(SignatureStatus::Unsigned);
+        }
+    };
+    let embedded_key = read_section(&mut file, SIGNATURE_KEY_SECTION)?
+        .map(trim_nul)
+        .filter(|key| !key.is_empty());
+    let trusted_keys = match trusted_keys_dir {
+        Some(dir) => read_trusted_keys(dir),
+        None => Vec::new(),
+    };
+
+    let needs_gpg = embedded_key.iter().chain(&trusted_keys).any(|key| is_armored(key));
+    let tools = ["gpgv"].into_iter().chain(needs_gpg.then_some("gpg"));
+    let search_path = env::var_os("PATH").unwrap_or_default();
+    if let Some(tool) = find_missing_tool(tools, &search_path) {
+        warn!("{} not found, the signature of the AppImage is not verified", tool);
+        return Ok(SignatureStatus::Unverified {
+            reason: format!("{} not found", tool),
+        });
+    }
+
+    let work_dir = tempfile::tempdir()
+        .map_err(|e| Error::io("Failed to create temporary directory", e))?;
+    let gnupg_home = work_dir.path().join("gnupg");
+    fs::create_dir(&gnupg_home)
+        .map_err(|e| Error::io("Failed to create temporary directory", e))?;
+    let signature_path = work_dir.path().join("digest.sig");
+    let digest_path = work_dir.path().join("digest");
+    let digest = app_image_digest(path)?;
+    fs::write(&signature_path, &signature)
+        .and_then(|_| fs::write(&digest_path, digest))
+        .map_err(|e| Error::io("Failed to write signature", e))?;
+
+    let verify = |keys: &[Vec<u8>], keyring_name: &str| -> Result<Option<String>> {
+        let keyring = work_dir.path().join(keyring_name);
+        let mut keyring_content = Vec::new();
+        for key in keys {
+            keyring_content.extend(dearmor(key, &gnupg_home)?);
+        }
+        fs::write(&keyring, keyring_content)
+            .map_err(|e| Error::io("Failed to write keyring", e))?;
+        gpgv(&keyring, &signature_path, &digest_path, &gnupg_home)
+    };
+
+    // the embedded key only proves the integrity of the image
+    let embedded_fingerprint = match &embedded_key {
+        Some(key) => match verify(std::slice::from_ref(key), "embedded.gpg")? {
+            Some(fingerprint) => Some(fingerprint),
+            None => {
+                return Ok(SignatureStatus::Invalid {
+                    reason: "The signature does not match the embedded key".to_string(),
+                })
+            }
+        },
+        None => None,
+    };
+
+    if !trusted_keys.is_empty() {
+        if let Some(fingerprint) = verify(&trusted_keys, "trusted.gpg")? {
+            info!("AppImage signed by trusted key {}", fingerprint);
+            return Ok(SignatureStatus::Signed {
+                trusted: true,
+                fingerprint,
+            });
+        }
+    }
+
+    match embedded_fingerprint {
+        Some(fingerprint) => {
+            info!("AppImage signed by untrusted key {}", fingerprint);
+            Ok(SignatureStatus::Signed {
+                trusted: false,
+                fingerprint,
+            })
+        }
+        None => Ok(SignatureStatus::Invalid {
+            reason: "No key available to verify the signature".to_string(),
+        }),
+    }
+}
+
+/// Read every public key file of the trusted keyring directory
+fn read_trusted_keys(dir: &Path) -> Vec<Vec<u8>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("No trusted keys found in {:?}: {}", dir, e);
+            return Vec::new();
+        }
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("asc" | "gpg" | "pgp" | "key")
+                )
+        })
+        .filter_map(|path| fs::read(path).ok())
+        .collect()
+}
+
+fn is_armored(key: &[u8]) -> bool {
+    key.starts_with(b"-----BEGIN PGP")
+}
+
+/// The first of the tools that is not found in the directories of `search_path`
+fn find_missing_tool<'a>(
+    tools: impl IntoIterator<Item = &'a str>,
+    search_path: &OsStr,
+) -> Option<&'a str> {
+    tools.into_iter().find(|tool| {
+        !env::split_paths(search_path).any(|dir| dir.join(tool).is_file())
+    })
+}
+
+/// Convert an armored key to the binary format understood by gpgv.
+/// Binary keys are returned unchanged.
+fn dearmor(key: &[u8], gnupg_home: &Path) -> Result<Vec<u8>> {
+    if !is_armored(key) {
+        return Ok(key.to_vec());
+    }
+
+    let mut child = Command::new("gpg")
+        .arg("--homedir")
+        .arg(gnupg_home)
+        .arg("--batch")
+        .arg("--dearmor")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::io("Failed to run gpg", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(key)
+            .map_err(|e| Error::io("Failed to write key to gpg", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::io("Failed to run gpg", e))?;
+    if !output.status.success() {
+        return Err(Error::new(ErrorKind::Signature, format!(
+            "Failed to read public key: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Verify a detached signature with gpgv, returning the fingerprint of the signing key
+fn gpgv(
+    keyring: &Path,
+    signature: &Path,
+    data: &Path,
+    gnupg_home: &Path,
+) -> Result<Option<String>> {
+    let output = Command::new("gpgv")
+        .arg("--homedir")
+        .arg(gnupg_home)
+        .arg("--status-fd")
+        .arg("1")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(signature)
+        .arg(data)
+        .output()
+        .map_err(|e| Error::io("Failed to run gpgv", e))?;
+
+    if !output.status.success() {
+        debug!("gpgv: {}", String::from_utf8_lossy(&output.stderr));
+        return Ok(None);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .and_then(|line| line.split_whitespace().next())
+        .map(|fingerprint| fingerprint.to_string()))
+}
+
+fn trim_nul(mut content: Vec<u8>) -> Vec<u8> {
+    let end = content.iter().position(|b| *b == 0).unwrap_or(content.len());
+    content.truncate(end);
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{AppImageFixture, TestSigner};
+
+    fn signed_fixture() -> AppImageFixture {
+        let mut fixture = AppImageFixture::with_app("test");
+        fixture
+            .section(SIGNATURE_SECTION, &[0u8; 1024])
+            .section(SIGNATURE_KEY_SECTION, &[0u8; 8192]);
+        fixture
+    }
+
+    #[test]
+    fn test_digest_skips_signature_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Test.AppImage");
+        signed_fixture().write_to(&path);
+        let digest = app_image_digest(&path).unwrap();
+
+        TestSigner::new().sign(&path, true);
+        assert_eq!(app_image_digest(&path).unwrap(), digest);
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let trusted_keys_dir = dir.path().join("trusted-keys");
+        fs::create_dir(&trusted_keys_dir).unwrap();
+        let path = dir.path().join("Test.AppImage");
+        let signer = TestSigner::new();
+
+        signed_fixture().write_to(&path);
+        assert_eq!(
+            verify_app_image_signature(&path, Some(&trusted_keys_dir)).unwrap(),
+            SignatureStatus::Unsigned
+        );
+
+        signer.sign(&path, true);
+        assert_eq!(
+            verify_app_image_signature(&path, Some(&trusted_keys_dir)).unwrap(),
+            SignatureStatus::Signed {
+                trusted: false,
+                fingerprint: signer.fingerprint(),
+            }
+        );
+
+        fs::write(trusted_keys_dir.join("publisher.asc"), signer.public_key()).unwrap();
+        assert_eq!(
+            verify_app_image_signature(&path, Some(&trusted_keys_dir)).unwrap(),
+            SignatureStatus::Signed {
+                trusted: true,
+                fingerprint: signer.fingerprint(),
+            }
+        );
+
+        // tamper with the payload
+        let mut content = fs::read(&path).unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        fs::write(&path, content).unwrap();
+        assert!(matches!(
+            verify_app_image_signature(&path, Some(&trusted_keys_dir)).unwrap(),
+            SignatureStatus::Invalid { .. }
+        ));
+    }
+
+    #[test]
+    fn test_enforce_policy() {
+        let untrusted = SignatureStatus::Signed {
+            trusted: false,
+            fingerprint: "ABCD".to_string(),
+        };
+        let invalid = SignatureStatus::Invalid {
+            reason: "bad signature".to_string(),
+        };
+
+        assert!(SignaturePolicy::Refuse.enforce(&SignatureStatus::Unsigned).is_err());
+        assert!(SignaturePolicy::Refuse.enforce(&untrusted).is_err());
+        assert!(SignaturePolicy::Warn.enforce(&untrusted).is_ok());
+        assert!(SignaturePolicy::Allow.enforce(&SignatureStatus::Unsigned).is_ok());
+        assert!(SignaturePolicy::Allow.enforce(&invalid).is_err());
+
+        let unverified = SignatureStatus::Unverified {
+            reason: "gpgv not found".to_string(),
+        };
+        assert!(SignaturePolicy::Refuse.enforce(&unverified).is_err());
+        assert!(SignaturePolicy::Warn.enforce(&unverified).is_ok());
+        assert!(SignaturePolicy::Allow.enforce(&unverified).is_ok());
+    }
+
+    #[test]
+    fn test_find_missing_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("gpgv"), "").unwrap();
+        let search_path = env::join_paths([Path::new("/nonexistent"), dir.path()]).unwrap();
+
+        assert_eq!(find_missing_tool(["gpgv"], &search_path), None);
+        assert_eq!(find_missing_tool(["gpgv", "gpg"], &search_path), Some("gpg"));
+        assert_eq!(find_missing_tool(["gpgv"], OsStr::new("")), Some("gpgv"));
+    }
+}
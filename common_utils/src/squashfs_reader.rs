@@ -0,0 +1,647 @@
+use crate::error::{Error, ErrorKind, Result};
+use crate::image_payload::{
+    join_path, normalize_path, resolve_symlink, EntryKind, ImageEntry, ImagePayload,
+};
+use flate2::read::ZlibDecoder;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const SQUASHFS_MAGIC: u32 = 0x7371_7368;
+const SUPERBLOCK_SIZE: usize = 96;
+const METADATA_BLOCK_SIZE: usize = 8192;
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+const DATA_BLOCK_UNCOMPRESSED: u32 = 0x0100_0000;
+const NO_FRAGMENT: u32 = 0xFFFF_FFFF;
+const FRAGMENT_ENTRY_SIZE: usize = 16;
+const MAX_SYMLINK_DEPTH: usize = 16;
+const MAX_SYMLINK_TARGET_SIZE: usize = 4096;
+const MAX_DIRECTORY_DEPTH: usize = 256;
+/// Files are only read for the desktop entry, the icons and the AppStream metadata
+const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+const MIN_BLOCK_SIZE: u32 = 4096;
+const MAX_BLOCK_SIZE: u32 = 1024 * 1024;
+
+const COMPRESSION_GZIP: u16 = 1;
+const COMPRESSION_LZMA: u16 = 2;
+const COMPRESSION_LZO: u16 = 3;
+const COMPRESSION_XZ: u16 = 4;
+const COMPRESSION_LZ4: u16 = 5;
+const COMPRESSION_ZSTD: u16 = 6;
+
+const INODE_BASIC_DIR: u16 = 1;
+const INODE_BASIC_FILE: u16 = 2;
+const INODE_BASIC_SYMLINK: u16 = 3;
+const INODE_EXT_DIR: u16 = 8;
+const INODE_EXT_FILE: u16 = 9;
+const INODE_EXT_SYMLINK: u16 = 10;
+
+/// The SquashFS 4.0 superblock
+#[derive(Debug, Clone)]
+struct SuperBlock {
+    block_size: u32,
+    fragment_count: u32,
+    compression: u16,
+    root_inode_ref: u64,
+    inode_table_start: u64,
+    directory_table_start: u64,
+    fragment_table_start: u64,
+}
+
+impl SuperBlock {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let magic = le_u32(bytes, 0);
+        if magic != SQUASHFS_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "SquashFS magic not found"));
+        }
+
+        let version_major = le_u16(bytes, 28);
+        if version_major != 4 {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Unsupported SquashFS version: {}", version_major)));
+        }
+
+        let block_size = le_u32(bytes, 12);
+        if !block_size.is_power_of_two() || !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Invalid SquashFS block size: {}", block_size)));
+        }
+        let block_log = le_u16(bytes, 22);
+        if block_log as u32 != block_size.trailing_zeros() {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("SquashFS block log {} does not match the block size", block_log)));
+        }
+
+        Ok(SuperBlock {
+            block_size,
+            fragment_count: le_u32(bytes, 16),
+            compression: le_u16(bytes, 20),
+            root_inode_ref: le_u64(bytes, 32),
+            inode_table_start: le_u64(bytes, 64),
+            directory_table_start: le_u64(bytes, 72),
+            fragment_table_start: le_u64(bytes, 80),
+        })
+    }
+}
+
+/// The inode types AppHub needs to walk and read an AppImage payload
+#[derive(Debug, Clone)]
+enum Inode {
+    Directory {
+        block_index: u32,
+        block_offset: u16,
+        file_size: u32,
+    },
+    File {
+        blocks_start: u64,
+        fragment_index: u32,
+        fragment_offset: u32,
+        file_size: u64,
+        block_sizes: Vec<u32>,
+    },
+    Symlink {
+        target: String,
+    },
+    Other,
+}
+
+/// Read-only access to a SquashFS image embedded in a file at a given offset
+pub struct SquashFsReader<R: Read + Seek> {
+    reader: R,
+    offset: u64,
+    superblock: SuperBlock,
+    /// The number of bytes from the start of the image to the end of the file
+    image_size: u64,
+    /// Decompressed metadata blocks, keyed by their position in the image
+    metadata_cache: HashMap<u64, (Vec<u8>, u64)>,
+}
+
+impl<R: Read + Seek> SquashFsReader<R> {
+    /// Open the SquashFS image starting at `offset` bytes into `reader`
+    pub fn open(mut reader: R, offset: u64) -> Result<Self> {
+        let mut buf = [0u8; SUPERBLOCK_SIZE];
+        reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| Error::io("Failed to seek SquashFS superblock", e))?;
+        reader
+            .read_exact(&mut buf)
+            .map_err(|e| Error::io("Failed to read SquashFS superblock", e))?;
+
+        let superblock = SuperBlock::parse(&buf)?;
+        debug!("SquashFS superblock: {:?}", superblock);
+
+        if superblock.compression == COMPRESSION_LZO {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "LZO compressed SquashFS images are not supported"));
+        }
+
+        let image_size = reader
+            .seek(SeekFrom::End(0))
+            .map_err(|e| Error::io("Failed to seek SquashFS image", e))?
+            .saturating_sub(offset);
+
+        Ok(SquashFsReader {
+            reader,
+            offset,
+            superblock,
+            image_size,
+            metadata_cache: HashMap::new(),
+        })
+    }
+
+    /// Look up a single entry without following a trailing symlink
+    pub fn entry(&mut self, path: &str) -> Result<ImageEntry> {
+        let inode = self.resolve(path, false)?;
+        Ok(to_entry(normalize_path(path).join("/"), &inode))
+    }
+
+    /// List the entries below a directory. Directories can't be hard linked, so a
+    /// directory reached twice means the image loops back on itself.
+    fn walk(
+        &mut self,
+        dir: &Inode,
+        prefix: &str,
+        depth: usize,
+        visited: &mut HashSet<u64>,
+        entries: &mut Vec<ImageEntry>,
+    ) -> Result<()> {
+        if depth > MAX_DIRECTORY_DEPTH {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Too many levels of directories: {}", prefix)));
+        }
+
+        for (name, inode_ref) in self.dir_children(dir)? {
+            let child = self.read_inode(inode_ref)?;
+            let path = join_path(prefix, &name);
+            entries.push(to_entry(path.clone(), &child));
+            if let Inode::Directory { .. } = child {
+                if !visited.insert(inode_ref) {
+                    return Err(Error::new(ErrorKind::InvalidAppImage, format!("Directory loop in image: {}", path)));
+                }
+                self.walk(&child, &path, depth + 1, visited, entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the inode for a path, optionally following a symlink in the last component.
+    /// Symlinks in intermediate components are always followed.
+    fn resolve(&mut self, path: &str, follow_last: bool) -> Result<Inode> {
+        let mut components = normalize_path(path);
+        let mut depth = 0;
+
+        'restart: loop {
+            let mut current = self.read_inode(self.superblock.root_inode_ref)?;
+            for (index, component) in components.iter().enumerate() {
+                let children = self.dir_children(&current)?;
+                let inode_ref = match children.iter().find(|(name, _)| name == component) {
+                    Some((_, inode_ref)) => *inode_ref,
+                    None => return Err(Error::new(ErrorKind::InvalidAppImage, format!("File not found in image: {}", path))),
+                };
+                current = self.read_inode(inode_ref)?;
+
+                let is_last = index == components.len() - 1;
+                if let Inode::Symlink { target } = &current {
+                    if is_last && !follow_last {
+                        break;
+                    }
+                    depth += 1;
+                    if depth > MAX_SYMLINK_DEPTH {
+                        return Err(Error::new(ErrorKind::InvalidAppImage, format!("Too many levels of symlinks: {}", path)));
+                    }
+                    components = resolve_symlink(&components, index, target);
+                    continue 'restart;
+                }
+            }
+            return Ok(current);
+        }
+    }
+
+    /// Read the (name, inode reference) pairs of a directory inode
+    fn dir_children(&mut self, dir: &Inode) -> Result<Vec<(String, u64)>> {
+        let (block_index, block_offset, file_size) = match dir {
+            Inode::Directory {
+                block_index,
+                block_offset,
+                file_size,
+            } => (*block_index, *block_offset, *file_size),
+            _ => return Err(Error::new(ErrorKind::InvalidAppImage, "Not a directory")),
+        };
+
+        // The stored size accounts for the implicit "." and ".." entries
+        let listing_size = (file_size as usize).saturating_sub(3);
+        if listing_size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let data = self.read_metadata(
+            self.superblock.directory_table_start + block_index as u64,
+            block_offset as usize,
+            listing_size,
+        )?;
+
+        let mut children = Vec::new();
+        let mut pos = 0;
+        while pos + 12 <= data.len() {
+            let count = le_u32(&data, pos) as usize + 1;
+            let start = le_u32(&data, pos + 4) as u64;
+            pos += 12;
+            for _ in 0..count {
+                if pos + 8 > data.len() {
+                    return Err(Error::new(ErrorKind::InvalidAppImage, "Truncated directory entry"));
+                }
+                let offset = le_u16(&data, pos) as u64;
+                let name_size = le_u16(&data, pos + 6) as usize + 1;
+                pos += 8;
+                let name = data
+                    .get(pos..pos + name_size)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidAppImage, "Truncated directory entry name"))?;
+                children.push((String::from_utf8_lossy(name).to_string(), (start << 16) | offset));
+                pos += name_size;
+            }
+        }
+
+        Ok(children)
+    }
+
+    fn read_inode(&mut self, inode_ref: u64) -> Result<Inode> {
+        let location = self.superblock.inode_table_start + (inode_ref >> 16);
+        let offset = (inode_ref & 0xFFFF) as usize;
+
+        let header = self.read_metadata(location, offset, 16)?;
+        let inode_type = le_u16(&header, 0);
+
+        match inode_type {
+            INODE_BASIC_DIR => {
+                let data = self.read_metadata(location, offset, 32)?;
+                Ok(Inode::Directory {
+                    block_index: le_u32(&data, 16),
+                    file_size: le_u16(&data, 24) as u32,
+                    block_offset: le_u16(&data, 26),
+                })
+            }
+            INODE_EXT_DIR => {
+                let data = self.read_metadata(location, offset, 40)?;
+                Ok(Inode::Directory {
+                    file_size: le_u32(&data, 20),
+                    block_index: le_u32(&data, 24),
+                    block_offset: le_u16(&data, 34),
+                })
+            }
+            INODE_BASIC_FILE => {
+                let data = self.read_metadata(location, offset, 32)?;
+                let blocks_start = le_u32(&data, 16) as u64;
+                let fragment_index = le_u32(&data, 20);
+                let fragment_offset = le_u32(&data, 24);
+                let file_size = le_u32(&data, 28) as u64;
+                let count = self.block_count(file_size, fragment_index)?;
+                let data = self.read_metadata(location, offset, 32 + count * 4)?;
+                Ok(Inode::File {
+                    blocks_start,
+                    fragment_index,
+                    fragment_offset,
+                    file_size,
+                    block_sizes: (0..count).map(|i| le_u32(&data, 32 + i * 4)).collect(),
+                })
+            }
+            INODE_EXT_FILE => {
+                let data = self.read_metadata(location, offset, 56)?;
+                let blocks_start = le_u64(&data, 16);
+                let file_size = le_u64(&data, 24);
+                let fragment_index = le_u32(&data, 44);
+                let fragment_offset = le_u32(&data, 48);
+                let count = self.block_count(file_size, fragment_index)?;
+                let data = self.read_metadata(location, offset, 56 + count * 4)?;
+                Ok(Inode::File {
+                    blocks_start,
+                    fragment_index,
+                    fragment_offset,
+                    file_size,
+                    block_sizes: (0..count).map(|i| le_u32(&data, 56 + i * 4)).collect(),
+                })
+            }
+            INODE_BASIC_SYMLINK | INODE_EXT_SYMLINK => {
+                let data = self.read_metadata(location, offset, 24)?;
+                let target_size = le_u32(&data, 20) as usize;
+                if target_size > MAX_SYMLINK_TARGET_SIZE {
+                    return Err(Error::new(ErrorKind::InvalidAppImage, format!("Symlink target is too long: {} bytes", target_size)));
+                }
+                let data = self.read_metadata(location, offset, 24 + target_size)?;
+                Ok(Inode::Symlink {
+                    target: String::from_utf8_lossy(&data[24..]).to_string(),
+                })
+            }
+            _ => Ok(Inode::Other),
+        }
+    }
+
+    /// The number of data blocks of a file. The block list is stored in the image, so a
+    /// list larger than the whole image comes from a crafted inode.
+    fn block_count(&self, file_size: u64, fragment_index: u32) -> Result<usize> {
+        let block_size = self.superblock.block_size as u64;
+        let count = if fragment_index == NO_FRAGMENT {
+            file_size.div_ceil(block_size)
+        } else {
+            file_size / block_size
+        };
+        if count.saturating_mul(4) > self.image_size {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Block list of {} blocks is larger than the image", count)));
+        }
+        Ok(count as usize)
+    }
+
+    fn read_file_data(&mut self, inode: &Inode) -> Result<Vec<u8>> {
+        let (blocks_start, fragment_index, fragment_offset, file_size, block_sizes) = match inode {
+            Inode::File {
+                blocks_start,
+                fragment_index,
+                fragment_offset,
+                file_size,
+                block_sizes,
+            } => (
+                *blocks_start,
+                *fragment_index,
+                *fragment_offset,
+                *file_size as usize,
+                block_sizes,
+            ),
+            _ => return Err(Error::new(ErrorKind::InvalidAppImage, "Not a regular file")),
+        };
+        if file_size as u64 > MAX_FILE_SIZE {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("File of {} bytes is too large to read", file_size)));
+        }
+
+        let block_size = self.superblock.block_size as usize;
+        let mut content = Vec::with_capacity(file_size.min(self.image_size as usize));
+        let mut position = blocks_start;
+
+        for block in block_sizes {
+            let expected = block_size.min(file_size - content.len());
+            let on_disk_size = block & !DATA_BLOCK_UNCOMPRESSED;
+            if on_disk_size == 0 {
+                // Sparse block
+                content.resize(content.len() + expected, 0);
+                continue;
+            }
+
+            let raw = self.read_raw(position, on_disk_size as usize)?;
+            position += on_disk_size as u64;
+            let data = if block & DATA_BLOCK_UNCOMPRESSED != 0 {
+                raw
+            } else {
+                self.decompress(&raw, block_size)?
+            };
+            content.extend_from_slice(&data[..expected.min(data.len())]);
+        }
+
+        if fragment_index != NO_FRAGMENT && content.len() < file_size {
+            let fragment = self.read_fragment(fragment_index)?;
+            let start = fragment_offset as usize;
+            let end = start + (file_size - content.len());
+            let tail = fragment
+                .get(start..end)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidAppImage, "Fragment is smaller than expected"))?;
+            content.extend_from_slice(tail);
+        }
+
+        Ok(content)
+    }
+
+    fn read_fragment(&mut self, index: u32) -> Result<Vec<u8>> {
+        if index >= self.superblock.fragment_count {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Invalid fragment index: {}", index)));
+        }
+
+        let entries_per_block = (METADATA_BLOCK_SIZE / FRAGMENT_ENTRY_SIZE) as u64;
+        let pointer_location = self.superblock.fragment_table_start + (index as u64 / entries_per_block) * 8;
+        let pointer = self.read_raw(pointer_location, 8)?;
+        let block_location = le_u64(&pointer, 0);
+
+        let entry_offset = (index as u64 % entries_per_block) as usize * FRAGMENT_ENTRY_SIZE;
+        let entry = self.read_metadata(block_location, entry_offset, FRAGMENT_ENTRY_SIZE)?;
+        let start = le_u64(&entry, 0);
+        let size = le_u32(&entry, 8);
+
+        let raw = self.read_raw(start, (size & !DATA_BLOCK_UNCOMPRESSED) as usize)?;
+        if size & DATA_BLOCK_UNCOMPRESSED != 0 {
+            Ok(raw)
+        } else {
+            self.decompress(&raw, self.superblock.block_size as usize)
+        }
+    }
+
+    /// Read `len` bytes of metadata starting `offset` bytes into the block at `location`,
+    /// continuing into the following blocks when needed
+    fn read_metadata(&mut self, location: u64, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(len.min(METADATA_BLOCK_SIZE));
+        let mut location = location;
+        let mut offset = offset;
+
+        while result.len() < len {
+            let (block, next) = self.metadata_block(location)?;
+            if offset > block.len() {
+                return Err(Error::new(ErrorKind::InvalidAppImage, "Metadata offset out of bounds"));
+            }
+            let available = &block[offset..];
+            let needed = len - result.len();
+            result.extend_from_slice(&available[..needed.min(available.len())]);
+            location = next;
+            offset = 0;
+        }
+
+        Ok(result)
+    }
+
+    /// Decompress the metadata block at `location`, returning it along with the position
+    /// of the next block
+    fn metadata_block(&mut self, location: u64) -> Result<(Vec<u8>, u64)> {
+        if let Some(cached) = self.metadata_cache.get(&location) {
+            return Ok(cached.clone());
+        }
+
+        let header = self.read_raw(location, 2)?;
+        let header = le_u16(&header, 0);
+        let size = (header & !METADATA_UNCOMPRESSED) as usize;
+        let raw = self.read_raw(location + 2, size)?;
+        let data = if header & METADATA_UNCOMPRESSED != 0 {
+            raw
+        } else {
+            self.decompress(&raw, METADATA_BLOCK_SIZE)?
+        };
+
+        let next = location + 2 + size as u64;
+        self.metadata_cache.insert(location, (data.clone(), next));
+        Ok((data, next))
+    }
+
+    /// Read raw bytes at a position relative to the start of the image
+    fn read_raw(&mut self, position: u64, len: usize) -> Result<Vec<u8>> {
+        if position.saturating_add(len as u64) > self.image_size {
+            return Err(Error::new(ErrorKind::InvalidAppImage, "SquashFS image is truncated"));
+        }
+
+        let mut buf = vec![0u8; len];
+        self.reader
+            .seek(SeekFrom::Start(self.offset + position))
+            .map_err(|e| Error::io("Failed to seek SquashFS image", e))?;
+        self.reader
+            .read_exact(&mut buf)
+            .map_err(|e| Error::io("Failed to read SquashFS image", e))?;
+        Ok(buf)
+    }
+
+    /// Decompress a block, refusing any block that expands past `max_size` bytes
+    fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+        let limit = max_size as u64 + 1;
+        let mut output = Vec::with_capacity(max_size);
+        match self.superblock.compression {
+            COMPRESSION_GZIP => {
+                ZlibDecoder::new(data)
+                    .take(limit)
+                    .read_to_end(&mut output)
+                    .map_err(|e| Error::io("Failed to decompress gzip block", e))?;
+            }
+            COMPRESSION_XZ => {
+                let mut writer = BoundedWriter::new(&mut output, max_size);
+                lzma_rs::xz_decompress(&mut &data[..], &mut writer)
+                    .map_err(|e| Error::new(ErrorKind::InvalidAppImage, "Failed to decompress xz block").caused_by(e))?;
+            }
+            COMPRESSION_LZMA => {
+                let mut writer = BoundedWriter::new(&mut output, max_size);
+                lzma_rs::lzma_decompress(&mut &data[..], &mut writer)
+                    .map_err(|e| Error::new(ErrorKind::InvalidAppImage, "Failed to decompress lzma block").caused_by(e))?;
+            }
+            COMPRESSION_ZSTD => {
+                let decoder = ruzstd::StreamingDecoder::new(data)
+                    .map_err(|e| Error::new(ErrorKind::InvalidAppImage, "Failed to decompress zstd block").caused_by(e))?;
+                decoder
+                    .take(limit)
+                    .read_to_end(&mut output)
+                    .map_err(|e| Error::io("Failed to decompress zstd block", e))?;
+            }
+            COMPRESSION_LZ4 => {
+                output.resize(max_size, 0);
+                let size = lz4_flex::block::decompress_into(data, &mut output)
+                    .map_err(|e| Error::new(ErrorKind::InvalidAppImage, "Failed to decompress lz4 block").caused_by(e))?;
+                output.truncate(size);
+            }
+            compression => {
+                return Err(Error::new(ErrorKind::InvalidAppImage, format!("Unsupported SquashFS compression: {}", compression)))
+            }
+        }
+        if output.len() > max_size {
+            return Err(Error::new(ErrorKind::InvalidAppImage, format!("Decompressed block is larger than {} bytes", max_size)));
+        }
+        Ok(output)
+    }
+}
+
+/// A writer for the decoders that only take an output, failing once the output
+/// grows past `limit` bytes
+struct BoundedWriter<'a> {
+    output: &'a mut Vec<u8>,
+    limit: usize,
+}
+
+impl<'a> BoundedWriter<'a> {
+    fn new(output: &'a mut Vec<u8>, limit: usize) -> Self {
+        BoundedWriter { output, limit }
+    }
+}
+
+impl Write for BoundedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.output.len() + buf.len() > self.limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Decompressed block is too large"));
+        }
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> ImagePayload for SquashFsReader<R> {
+    /// List every entry of the image, walking the directory tree from the root
+    fn entries(&mut self) -> Result<Vec<ImageEntry>> {
+        let mut entries = Vec::new();
+        let root = self.read_inode(self.superblock.root_inode_ref)?;
+        let mut visited = HashSet::from([self.superblock.root_inode_ref]);
+        self.walk(&root, "", 0, &mut visited, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// List the entries of the directory at the given path
+    fn read_dir(&mut self, path: &str) -> Result<Vec<ImageEntry>> {
+        let inode = self.resolve(path, true)?;
+        let prefix = normalize_path(path).join("/");
+        let mut entries = Vec::new();
+        for (name, inode_ref) in self.dir_children(&inode)? {
+            let child = self.read_inode(inode_ref)?;
+            entries.push(to_entry(join_path(&prefix, &name), &child));
+        }
+        Ok(entries)
+    }
+
+    /// Read the content of the file at the given path, following symlinks
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>> {
+        let inode = self.resolve(path, true)?;
+        match inode {
+            Inode::File { .. } => self.read_file_data(&inode),
+            _ => Err(Error::new(ErrorKind::InvalidAppImage, format!("Not a regular file: {}", path))),
+        }
+    }
+}
+
+fn to_entry(path: String, inode: &Inode) -> ImageEntry {
+    let (kind, size) = match inode {
+        Inode::Directory { .. } => (EntryKind::Directory, 0),
+        Inode::File { file_size, .. } => (EntryKind::File, *file_size),
+        Inode::Symlink { target } => (EntryKind::Symlink(target.clone()), 0),
+        Inode::Other => (EntryKind::Other, 0),
+    };
+    ImageEntry { path, kind, size }
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::AppImageFixture;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_open_rejects_invalid_block_size() {
+        let mut image = AppImageFixture::with_app("test").squashfs();
+        image[12..16].copy_from_slice(&0u32.to_le_bytes());
+
+        let error = SquashFsReader::open(Cursor::new(image), 0).err().unwrap();
+        assert_eq!(error.kind, ErrorKind::InvalidAppImage);
+    }
+
+    #[test]
+    fn test_decompress_rejects_oversized_block() {
+        let image = AppImageFixture::with_app("test").squashfs();
+        let reader = SquashFsReader::open(Cursor::new(image), 0).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![0u8; 2 * METADATA_BLOCK_SIZE]).unwrap();
+        let block = encoder.finish().unwrap();
+
+        assert!(reader.decompress(&block, METADATA_BLOCK_SIZE).is_err());
+        assert_eq!(reader.decompress(&block, 2 * METADATA_BLOCK_SIZE).unwrap().len(), 2 * METADATA_BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,808 @@
+//! Builders for small but valid AppImage files, used by the tests of this crate and of the backend.
+
+use crate::{elf_reader, signature, zsync};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 131072;
+const BLOCK_LOG: u16 = 17;
+const METADATA_BLOCK_SIZE: usize = 8192;
+const SECTOR_SIZE: usize = 2048;
+const ISO_SYSTEM_AREA_SIZE: usize = 16 * SECTOR_SIZE;
+const ZISOFS_BLOCK_LOG: u8 = 15;
+
+enum Node {
+    Directory(BTreeMap<String, Node>),
+    File(Vec<u8>),
+    Symlink(String),
+}
+
+/// Builds AppImages: by default a type 2 AppImage, a minimal ELF "runtime" followed by a
+/// SquashFS payload
+pub struct AppImageFixture {
+    root: BTreeMap<String, Node>,
+    sections: Vec<(String, Vec<u8>)>,
+}
+
+impl Default for AppImageFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppImageFixture {
+    pub fn new() -> Self {
+        Self {
+            root: BTreeMap::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// A fixture with a desktop entry, an icon and the `.DirIcon` symlink, like most AppImages
+    pub fn with_app(name: &str) -> Self {
+        let mut fixture = Self::new();
+        fixture
+            .file(
+                &format!("{}.desktop", name),
+                format!(
+                    "[Desktop Entry]\nType=Application\nName={}\nExec=AppRun\nIcon={}\nCategories=Utility;\n",
+                    name, name
+                )
+                .as_bytes(),
+            )
+            .file(&format!("{}.png", name), &png_icon(64))
+            .file("AppRun", b"#!/bin/sh\necho AppRun must never be executed\nexit 1\n")
+            .symlink(".DirIcon", &format!("{}.png", name));
+        fixture
+    }
+
+    /// Add a regular file, creating its parent directories
+    pub fn file(&mut self, path: &str, content: &[u8]) -> &mut Self {
+        self.insert(path, Node::File(content.to_vec()));
+        self
+    }
+
+    /// Add a symlink, creating its parent directories
+    pub fn symlink(&mut self, path: &str, target: &str) -> &mut Self {
+        self.insert(path, Node::Symlink(target.to_string()));
+        self
+    }
+
+    /// Add an ELF section to the runtime
+    pub fn section(&mut self, name: &str, content: &[u8]) -> &mut Self {
+        self.sections.push((name.to_string(), content.to_vec()));
+        self
+    }
+
+    /// The SquashFS payload on its own
+    pub fn squashfs(&self) -> Vec<u8> {
+        SquashFsWriter::default().write(&self.root)
+    }
+
+    /// The complete AppImage
+    pub fn build(&self) -> Vec<u8> {
+        let mut image = elf_runtime(&self.sections, *b"AI\x02");
+        image.extend(self.squashfs());
+        image
+    }
+
+    /// The same content packaged as a type 1 AppImage: an ISO 9660 image with Rock Ridge
+    /// extensions whose system area holds the ELF runtime. Files bigger than a sector are
+    /// zisofs compressed.
+    pub fn build_type1(&self) -> Vec<u8> {
+        let runtime = elf_runtime(&self.sections, *b"AI\x01");
+        assert!(runtime.len() <= ISO_SYSTEM_AREA_SIZE, "Runtime does not fit the system area");
+        Iso9660Writer::default().write(&self.root, &runtime)
+    }
+
+    /// Write the AppImage to the given path
+    pub fn write_to(&self, path: &Path) {
+        std::fs::write(path, self.build()).expect("Failed to write AppImage fixture");
+    }
+
+    /// Write the type 1 AppImage to the given path
+    pub fn write_type1_to(&self, path: &Path) {
+        std::fs::write(path, self.build_type1()).expect("Failed to write AppImage fixture");
+    }
+
+    fn insert(&mut self, path: &str, node: Node) {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let (name, parents) = components.split_last().expect("Empty fixture path");
+        let mut dir = &mut self.root;
+        for parent in parents {
+            let entry = dir
+                .entry(parent.to_string())
+                .or_insert_with(|| Node::Directory(BTreeMap::new()));
+            dir = match entry {
+                Node::Directory(children) => children,
+                _ => panic!("{} is not a directory", parent),
+            };
+        }
+        dir.insert(name.to_string(), node);
+    }
+}
+
+/// Bytes starting with the PNG signature, padded to the given size
+pub fn png_stub(size: usize) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.resize(size.max(png.len()), 0);
+    png
+}
+
+/// A square PNG image of the given size, filled with a single color
+pub fn png_icon(size: u32) -> Vec<u8> {
+    let image = image::RgbaImage::from_pixel(size, size, image::Rgba([53, 132, 228, 255]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+    png.into_inner()
+}
+
+/// Deterministic incompressible bytes, different for each seed
+pub fn pseudo_random_data(size: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9e3779b97f4a7c15) | 1;
+    (0..size)
+        .map(|_| {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+/// A `.zsync` control file describing the given data
+pub fn zsync_control_file(data: &[u8], block_size: usize, url: &str, filename: &str) -> Vec<u8> {
+    let (seq_matches, rsum_bytes, checksum_bytes) = (2, 3, 8);
+    let mut content = format!(
+        "zsync: 0.6.2\nFilename: {}\nBlocksize: {}\nLength: {}\nHash-Lengths: {},{},{}\nURL: {}\nSHA-1: {}\n\n",
+        filename,
+        block_size,
+        data.len(),
+        seq_matches,
+        rsum_bytes,
+        checksum_bytes,
+        url,
+        zsync::sha1_hex(data)
+    )
+    .into_bytes();
+
+    for block in data.chunks(block_size) {
+        let mut block = block.to_vec();
+        block.resize(block_size, 0);
+        content.extend_from_slice(&zsync::rsum(&block).to_be_bytes()[4 - rsum_bytes..]);
+        content.extend_from_slice(&zsync::block_checksum(&block)[..checksum_bytes]);
+    }
+    content
+}
+
+/// Signs AppImages with a throwaway GPG key, the way appimagetool does
+pub struct TestSigner {
+    gnupg_home: tempfile::TempDir,
+}
+
+impl Default for TestSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestSigner {
+    pub fn new() -> Self {
+        let gnupg_home = tempfile::tempdir().unwrap();
+        let signer = TestSigner { gnupg_home };
+        signer.gpg(&[
+            "--passphrase",
+            "",
+            "--quick-gen-key",
+            "AppHub Test <test@example.com>",
+            "ed25519",
+            "sign",
+            "never",
+        ]);
+        signer
+    }
+
+    /// The armored public key
+    pub fn public_key(&self) -> Vec<u8> {
+        self.gpg(&["--armor", "--export"])
+    }
+
+    pub fn fingerprint(&self) -> String {
+        String::from_utf8(self.gpg(&["--with-colons", "--list-keys"]))
+            .unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix("fpr:"))
+            .and_then(|line| line.split(':').find(|field| !field.is_empty()))
+            .unwrap()
+            .to_string()
+    }
+
+    /// Write the signature of the AppImage digest, and optionally the public key,
+    /// into the placeholder signature sections of the AppImage
+    pub fn sign(&self, path: &Path, embed_key: bool) {
+        let digest = signature::app_image_digest(path).unwrap();
+        let digest_path = self.gnupg_home.path().join("digest");
+        std::fs::write(&digest_path, digest).unwrap();
+        let signature = self.gpg(&[
+            "--armor",
+            "--detach-sign",
+            "--output",
+            "-",
+            digest_path.to_str().unwrap(),
+        ]);
+
+        let mut content = std::fs::read(path).unwrap();
+        let mut file = std::fs::File::open(path).unwrap();
+        for section in elf_reader::read_sections(&mut file).unwrap() {
+            let data = match section.name.as_str() {
+                signature::SIGNATURE_SECTION => signature.clone(),
+                signature::SIGNATURE_KEY_SECTION if embed_key => self.public_key(),
+                _ => continue,
+            };
+            assert!(data.len() <= section.size as usize, "section too small");
+            let start = section.offset as usize;
+            content[start..start + data.len()].copy_from_slice(&data);
+        }
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn gpg(&self, args: &[&str]) -> Vec<u8> {
+        let output = std::process::Command::new("gpg")
+            .arg("--homedir")
+            .arg(self.gnupg_home.path())
+            .arg("--batch")
+            .arg("--yes")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    }
+}
+
+/// A 64-bit little endian ELF header followed by the given sections and the section
+/// header table, which marks the end of the runtime
+pub fn elf_runtime(sections: &[(String, Vec<u8>)], magic: [u8; 3]) -> Vec<u8> {
+    let mut elf = vec![0u8; 64];
+    elf[0..4].copy_from_slice(b"\x7fELF");
+    elf[4] = 2; // 64-bit
+    elf[5] = 1; // little endian
+    elf[6] = 1; // ELF version
+    elf[8..11].copy_from_slice(&magic);
+    elf[16..18].copy_from_slice(&2u16.to_le_bytes()); // executable
+    elf[18..20].copy_from_slice(&62u16.to_le_bytes()); // x86_64
+    elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+    elf[52..54].copy_from_slice(&64u16.to_le_bytes());
+    elf[54..56].copy_from_slice(&56u16.to_le_bytes());
+
+    let mut names = vec![0u8];
+    let mut headers = vec![[0u8; 64]];
+    for (name, content) in sections {
+        headers.push(section_header(names.len() as u32, 1, elf.len() as u64, content.len() as u64));
+        names.extend(name.as_bytes());
+        names.push(0);
+        elf.extend(content);
+    }
+    headers.push(section_header(names.len() as u32, 3, elf.len() as u64, 0));
+    names.extend(b".shstrtab\0");
+    let names_header = headers.last_mut().unwrap();
+    names_header[32..40].copy_from_slice(&(names.len() as u64).to_le_bytes());
+    elf.extend(&names);
+
+    let section_header_offset = elf.len() as u64;
+    elf[40..48].copy_from_slice(&section_header_offset.to_le_bytes());
+    elf[58..60].copy_from_slice(&64u16.to_le_bytes());
+    elf[60..62].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+    elf[62..64].copy_from_slice(&(headers.len() as u16 - 1).to_le_bytes());
+    for header in headers {
+        elf.extend(header);
+    }
+    elf
+}
+
+fn section_header(name: u32, section_type: u32, offset: u64, size: u64) -> [u8; 64] {
+    let mut header = [0u8; 64];
+    header[0..4].copy_from_slice(&name.to_le_bytes());
+    header[4..8].copy_from_slice(&section_type.to_le_bytes());
+    header[24..32].copy_from_slice(&offset.to_le_bytes());
+    header[32..40].copy_from_slice(&size.to_le_bytes());
+    header[48..56].copy_from_slice(&1u64.to_le_bytes());
+    header
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Accumulates a metadata table, compressing it in 8 KiB blocks as it grows
+#[derive(Default)]
+struct MetadataWriter {
+    disk: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl MetadataWriter {
+    /// Reference (block position << 16 | offset) of the next byte written
+    fn reference(&self) -> u64 {
+        ((self.disk.len() as u64) << 16) | self.pending.len() as u64
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.pending.push(*byte);
+            if self.pending.len() == METADATA_BLOCK_SIZE {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let compressed = compress(&self.pending);
+        if compressed.len() < self.pending.len() {
+            self.disk.extend((compressed.len() as u16).to_le_bytes());
+            self.disk.extend(compressed);
+        } else {
+            self.disk.extend((0x8000 | self.pending.len() as u16).to_le_bytes());
+            self.disk.extend(&self.pending);
+        }
+        self.pending.clear();
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        self.disk
+    }
+}
+
+#[derive(Default)]
+struct SquashFsWriter {
+    image: Vec<u8>,
+    inodes: MetadataWriter,
+    directories: MetadataWriter,
+    inode_count: u32,
+}
+
+impl SquashFsWriter {
+    fn write(mut self, root: &BTreeMap<String, Node>) -> Vec<u8> {
+        self.image = vec![0u8; 96];
+        let root_number = self.next_inode_number();
+        let root_ref = self.write_directory(root, root_number, root_number + 1);
+
+        let inode_table_start = self.image.len() as u64;
+        let inodes = std::mem::take(&mut self.inodes).finish();
+        self.image.extend(inodes);
+
+        let directory_table_start = self.image.len() as u64;
+        let directories = std::mem::take(&mut self.directories).finish();
+        self.image.extend(directories);
+
+        // A single id (root) in one uncompressed metadata block, plus its lookup table
+        let id_block = self.image.len() as u64;
+        self.image.extend((0x8000u16 | 4).to_le_bytes());
+        self.image.extend(0u32.to_le_bytes());
+        let id_table_start = self.image.len() as u64;
+        self.image.extend(id_block.to_le_bytes());
+
+        let bytes_used = self.image.len() as u64;
+        let mut superblock = Vec::with_capacity(96);
+        superblock.extend(0x7371_7368u32.to_le_bytes());
+        superblock.extend(self.inode_count.to_le_bytes());
+        superblock.extend(0u32.to_le_bytes());
+        superblock.extend((BLOCK_SIZE as u32).to_le_bytes());
+        superblock.extend(0u32.to_le_bytes());
+        superblock.extend(1u16.to_le_bytes()); // gzip
+        superblock.extend(BLOCK_LOG.to_le_bytes());
+        superblock.extend((0x0010u16 | 0x0200).to_le_bytes()); // no fragments, no xattrs
+        superblock.extend(1u16.to_le_bytes());
+        superblock.extend(4u16.to_le_bytes());
+        superblock.extend(0u16.to_le_bytes());
+        superblock.extend(root_ref.to_le_bytes());
+        superblock.extend(bytes_used.to_le_bytes());
+        superblock.extend(id_table_start.to_le_bytes());
+        superblock.extend(u64::MAX.to_le_bytes());
+        superblock.extend(inode_table_start.to_le_bytes());
+        superblock.extend(directory_table_start.to_le_bytes());
+        superblock.extend(u64::MAX.to_le_bytes());
+        superblock.extend(u64::MAX.to_le_bytes());
+        self.image[0..96].copy_from_slice(&superblock);
+
+        // Images are padded to 4 KiB like mksquashfs does
+        let padded = self.image.len().div_ceil(4096) * 4096;
+        self.image.resize(padded, 0);
+        self.image
+    }
+
+    fn next_inode_number(&mut self) -> u32 {
+        self.inode_count += 1;
+        self.inode_count
+    }
+
+    /// Write the children of a directory, its listing and its inode, returning the inode reference
+    fn write_directory(&mut self, children: &BTreeMap<String, Node>, number: u32, parent: u32) -> u64 {
+        let numbers: Vec<u32> = children.keys().map(|_| self.next_inode_number()).collect();
+
+        let mut listing = Vec::new();
+        let mut subdirectories = 0;
+        for ((name, node), child_number) in children.iter().zip(numbers) {
+            let (reference, inode_type) = match node {
+                Node::Directory(grandchildren) => {
+                    subdirectories += 1;
+                    (self.write_directory(grandchildren, child_number, number), 1u16)
+                }
+                Node::File(content) => (self.write_file(content, child_number), 2),
+                Node::Symlink(target) => (self.write_symlink(target, child_number), 3),
+            };
+            listing.push((name.clone(), reference, inode_type, child_number));
+        }
+
+        let listing_ref = self.directories.reference();
+        let mut listing_size = 0;
+        let mut index = 0;
+        while index < listing.len() {
+            let block = listing[index].1 >> 16;
+            let base = listing[index].3;
+            let group: Vec<_> = listing[index..]
+                .iter()
+                .take_while(|entry| entry.1 >> 16 == block)
+                .take(256)
+                .collect();
+
+            let mut data = Vec::new();
+            data.extend((group.len() as u32 - 1).to_le_bytes());
+            data.extend((block as u32).to_le_bytes());
+            data.extend(base.to_le_bytes());
+            for (name, reference, inode_type, child_number) in &group {
+                data.extend(((reference & 0xFFFF) as u16).to_le_bytes());
+                data.extend(((*child_number as i32 - base as i32) as i16).to_le_bytes());
+                data.extend(inode_type.to_le_bytes());
+                data.extend((name.len() as u16 - 1).to_le_bytes());
+                data.extend(name.as_bytes());
+            }
+            listing_size += data.len();
+            self.directories.write(&data);
+            index += group.len();
+        }
+
+        let reference = self.inodes.reference();
+        let mut inode = inode_header(1, 0o755, number);
+        inode.extend(((listing_ref >> 16) as u32).to_le_bytes());
+        inode.extend((2u32 + subdirectories).to_le_bytes());
+        inode.extend((listing_size as u16 + 3).to_le_bytes());
+        inode.extend(((listing_ref & 0xFFFF) as u16).to_le_bytes());
+        inode.extend(parent.to_le_bytes());
+        self.inodes.write(&inode);
+        reference
+    }
+
+    fn write_file(&mut self, content: &[u8], number: u32) -> u64 {
+        let blocks_start = self.image.len() as u32;
+        let mut block_sizes = Vec::new();
+        for chunk in content.chunks(BLOCK_SIZE) {
+            let compressed = compress(chunk);
+            if compressed.len() < chunk.len() {
+                block_sizes.push(compressed.len() as u32);
+                self.image.extend(compressed);
+            } else {
+                block_sizes.push(0x0100_0000 | chunk.len() as u32);
+                self.image.extend(chunk);
+            }
+        }
+
+        let reference = self.inodes.reference();
+        let mut inode = inode_header(2, 0o644, number);
+        inode.extend(blocks_start.to_le_bytes());
+        inode.extend(u32::MAX.to_le_bytes());
+        inode.extend(0u32.to_le_bytes());
+        inode.extend((content.len() as u32).to_le_bytes());
+        for size in block_sizes {
+            inode.extend(size.to_le_bytes());
+        }
+        self.inodes.write(&inode);
+        reference
+    }
+
+    fn write_symlink(&mut self, target: &str, number: u32) -> u64 {
+        let reference = self.inodes.reference();
+        let mut inode = inode_header(3, 0o777, number);
+        inode.extend(1u32.to_le_bytes());
+        inode.extend((target.len() as u32).to_le_bytes());
+        inode.extend(target.as_bytes());
+        self.inodes.write(&inode);
+        reference
+    }
+}
+
+fn inode_header(inode_type: u16, permissions: u16, number: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    header.extend(inode_type.to_le_bytes());
+    header.extend(permissions.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes());
+    header.extend(0u32.to_le_bytes());
+    header.extend(number.to_le_bytes());
+    header
+}
+
+/// A directory record to be written, before extents are known
+struct IsoRecord<'a> {
+    identifier: Vec<u8>,
+    system_use: Vec<u8>,
+    node: Option<&'a Node>,
+}
+
+impl IsoRecord<'_> {
+    fn len(&self) -> usize {
+        let mut len = 33 + self.identifier.len();
+        if len % 2 == 1 {
+            len += 1;
+        }
+        len += self.system_use.len();
+        len + len % 2
+    }
+}
+
+#[derive(Default)]
+struct Iso9660Writer {
+    image: Vec<u8>,
+}
+
+impl Iso9660Writer {
+    fn write(mut self, root: &BTreeMap<String, Node>, runtime: &[u8]) -> Vec<u8> {
+        // Collect the directories breadth first; the root is the first one
+        let mut directories: Vec<(&BTreeMap<String, Node>, usize)> = vec![(root, 0)];
+        let mut index = 0;
+        while index < directories.len() {
+            let (children, _) = directories[index];
+            for node in children.values() {
+                if let Node::Directory(grandchildren) = node {
+                    directories.push((grandchildren, index));
+                }
+            }
+            index += 1;
+        }
+
+        let listings: Vec<Vec<IsoRecord>> = directories
+            .iter()
+            .enumerate()
+            .map(|(index, (children, _))| directory_records(children, index == 0))
+            .collect();
+        let sizes: Vec<usize> = listings.iter().map(|records| listing_size(records)).collect();
+
+        // Directories start right after the primary volume descriptor and the terminator
+        let mut next_sector = 18;
+        let mut directory_extents = Vec::new();
+        for size in &sizes {
+            directory_extents.push(next_sector);
+            next_sector += size / SECTOR_SIZE;
+        }
+
+        // File contents follow the directories
+        let mut file_data: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut file_extents: Vec<Vec<(usize, usize)>> = Vec::new();
+        for listing in &listings {
+            let mut extents = Vec::new();
+            for record in listing {
+                match record.node {
+                    Some(Node::File(content)) => {
+                        let data = zisofs(content).unwrap_or_else(|| content.clone());
+                        extents.push((next_sector, data.len()));
+                        next_sector += data.len().div_ceil(SECTOR_SIZE);
+                        file_data.push((extents.last().unwrap().0, data));
+                    }
+                    _ => extents.push((0, 0)),
+                }
+            }
+            file_extents.push(extents);
+        }
+
+        self.image = vec![0u8; next_sector * SECTOR_SIZE];
+        self.image[..runtime.len()].copy_from_slice(runtime);
+
+        // Primary volume descriptor and terminator
+        let pvd = 16 * SECTOR_SIZE;
+        self.image[pvd] = 1;
+        self.image[pvd + 1..pvd + 6].copy_from_slice(b"CD001");
+        self.image[pvd + 6] = 1;
+        both_endian_u32(&mut self.image[pvd + 80..pvd + 88], next_sector as u32);
+        both_endian_u16(&mut self.image[pvd + 120..pvd + 124], 1);
+        both_endian_u16(&mut self.image[pvd + 124..pvd + 128], 1);
+        both_endian_u16(&mut self.image[pvd + 128..pvd + 132], SECTOR_SIZE as u16);
+        let root_record = directory_record(&[0], &[], directory_extents[0], sizes[0], true);
+        self.image[pvd + 156..pvd + 156 + 34].copy_from_slice(&root_record);
+        self.image[pvd + 881] = 1;
+        let terminator = 17 * SECTOR_SIZE;
+        self.image[terminator] = 255;
+        self.image[terminator + 1..terminator + 6].copy_from_slice(b"CD001");
+        self.image[terminator + 6] = 1;
+
+        // Directory listings
+        let mut subdirectory = 1;
+        for (index, listing) in listings.iter().enumerate() {
+            let parent = directories[index].1;
+            let mut position = directory_extents[index] * SECTOR_SIZE;
+            let start = position;
+            for (record_index, record) in listing.iter().enumerate() {
+                let (extent, size, is_directory) = match record_index {
+                    0 => (directory_extents[index], sizes[index], true),
+                    1 => (directory_extents[parent], sizes[parent], true),
+                    _ => match record.node {
+                        Some(Node::Directory(_)) => {
+                            let child = (directory_extents[subdirectory], sizes[subdirectory], true);
+                            subdirectory += 1;
+                            child
+                        }
+                        _ => {
+                            let (extent, size) = file_extents[index][record_index];
+                            (extent, size, false)
+                        }
+                    },
+                };
+                let bytes = directory_record(&record.identifier, &record.system_use, extent, size, is_directory);
+                if (position - start) % SECTOR_SIZE + bytes.len() > SECTOR_SIZE {
+                    position = start + (position - start).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+                }
+                self.image[position..position + bytes.len()].copy_from_slice(&bytes);
+                position += bytes.len();
+            }
+        }
+
+        for (extent, data) in file_data {
+            let start = extent * SECTOR_SIZE;
+            self.image[start..start + data.len()].copy_from_slice(&data);
+        }
+
+        self.image
+    }
+}
+
+fn directory_records(children: &BTreeMap<String, Node>, is_root: bool) -> Vec<IsoRecord<'_>> {
+    // The "." record of the root announces the System Use Sharing Protocol
+    let susp = if is_root {
+        vec![b'S', b'P', 7, 1, 0xBE, 0xEF, 0]
+    } else {
+        Vec::new()
+    };
+    let mut records = vec![
+        IsoRecord {
+            identifier: vec![0],
+            system_use: susp,
+            node: None,
+        },
+        IsoRecord {
+            identifier: vec![1],
+            system_use: Vec::new(),
+            node: None,
+        },
+    ];
+
+    // Directories are listed in the same breadth first order used to assign their extents
+    for (name, node) in children {
+        let mut identifier = name.to_uppercase().into_bytes();
+        let mut system_use = vec![b'N', b'M', 5 + name.len() as u8, 1, 0];
+        system_use.extend(name.as_bytes());
+        match node {
+            Node::Directory(_) => {}
+            Node::File(content) => {
+                identifier.extend(b";1");
+                if content.len() > SECTOR_SIZE {
+                    system_use.extend([b'Z', b'F', 16, 1, b'p', b'z', 4, ZISOFS_BLOCK_LOG]);
+                    let mut size = [0u8; 8];
+                    both_endian_u32(&mut size, content.len() as u32);
+                    system_use.extend(size);
+                }
+            }
+            Node::Symlink(target) => {
+                identifier.extend(b";1");
+                let mut components = Vec::new();
+                if target.starts_with('/') {
+                    components.extend([0x08, 0]);
+                }
+                for component in target.split('/').filter(|c| !c.is_empty()) {
+                    match component {
+                        "." => components.extend([0x02, 0]),
+                        ".." => components.extend([0x04, 0]),
+                        _ => {
+                            components.extend([0, component.len() as u8]);
+                            components.extend(component.as_bytes());
+                        }
+                    }
+                }
+                system_use.extend([b'S', b'L', 5 + components.len() as u8, 1, 0]);
+                system_use.extend(components);
+            }
+        }
+        records.push(IsoRecord {
+            identifier,
+            system_use,
+            node: Some(node),
+        });
+    }
+    records
+}
+
+/// Size of a directory listing, padded to whole sectors since records never cross them
+fn listing_size(records: &[IsoRecord]) -> usize {
+    let mut position = 0;
+    for record in records {
+        if position % SECTOR_SIZE + record.len() > SECTOR_SIZE {
+            position = position.div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+        }
+        position += record.len();
+    }
+    position.div_ceil(SECTOR_SIZE).max(1) * SECTOR_SIZE
+}
+
+fn directory_record(
+    identifier: &[u8],
+    system_use: &[u8],
+    extent: usize,
+    size: usize,
+    is_directory: bool,
+) -> Vec<u8> {
+    let record = IsoRecord {
+        identifier: identifier.to_vec(),
+        system_use: system_use.to_vec(),
+        node: None,
+    };
+    let mut bytes = vec![0u8; record.len()];
+    bytes[0] = bytes.len() as u8;
+    both_endian_u32(&mut bytes[2..10], extent as u32);
+    both_endian_u32(&mut bytes[10..18], size as u32);
+    bytes[25] = if is_directory { 0x02 } else { 0 };
+    both_endian_u16(&mut bytes[28..32], 1);
+    bytes[32] = identifier.len() as u8;
+    bytes[33..33 + identifier.len()].copy_from_slice(identifier);
+    let mut start = 33 + identifier.len();
+    if start % 2 == 1 {
+        start += 1;
+    }
+    bytes[start..start + system_use.len()].copy_from_slice(system_use);
+    bytes
+}
+
+/// Compress a file the way mkzftree does, leaving files that fit in a sector alone
+fn zisofs(content: &[u8]) -> Option<Vec<u8>> {
+    if content.len() <= SECTOR_SIZE {
+        return None;
+    }
+
+    let block_size = 1usize << ZISOFS_BLOCK_LOG;
+    let blocks: Vec<Vec<u8>> = content.chunks(block_size).map(compress).collect();
+
+    let mut data = vec![0x37, 0xE4, 0x53, 0x96, 0xC9, 0xDB, 0xD6, 0x07];
+    data.extend((content.len() as u32).to_le_bytes());
+    data.extend([4, ZISOFS_BLOCK_LOG, 0, 0]);
+    let mut position = data.len() + (blocks.len() + 1) * 4;
+    data.extend((position as u32).to_le_bytes());
+    for block in &blocks {
+        position += block.len();
+        data.extend((position as u32).to_le_bytes());
+    }
+    for block in blocks {
+        data.extend(block);
+    }
+    Some(data)
+}
+
+fn both_endian_u16(bytes: &mut [u8], value: u16) {
+    bytes[0..2].copy_from_slice(&value.to_le_bytes());
+    bytes[2..4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn both_endian_u32(bytes: &mut [u8], value: u32) {
+    bytes[0..4].copy_from_slice(&value.to_le_bytes());
+    bytes[4..8].copy_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,194 @@
+use crate::app_image_reader::{detect_app_image_type, AppImageType};
+use crate::elf_reader::read_section;
+use crate::error::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Name of the ELF section holding the update information of type 2 AppImages
+pub const UPDATE_INFO_SECTION: &str = ".upd_info";
+/// Type 1 AppImages store the update information in the application use area of
+/// the ISO 9660 primary volume descriptor
+const TYPE1_UPDATE_INFO_OFFSET: u64 = 33651;
+const TYPE1_UPDATE_INFO_SIZE: usize = 512;
+
+/// The update information embedded in an AppImage, as described by the AppImage specification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "kebab-case")]
+pub enum UpdateInfo {
+    /// `zsync|<url of the .zsync file>`
+    Zsync { url: String },
+    /// `gh-releases-zsync|<owner>|<repo>|<release>|<.zsync file name pattern>`
+    GhReleasesZsync {
+        owner: String,
+        repo: String,
+        release: String,
+        filename: String,
+    },
+    /// `pling-v1-zsync|<product id>|<.zsync file name pattern>`
+    PlingV1Zsync { product_id: String, filename: String },
+}
+
+impl UpdateInfo {
+    /// Parse an update information string
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        let fields: Vec<&str> = value.split('|').collect();
+
+        let check_fields = |count: usize| {
+            if fields.len() != count || fields.iter().any(|field| field.is_empty()) {
+                Err(Error::new(ErrorKind::InvalidAppImage, format!("Invalid update information: {}", value)))
+            } else {
+                Ok(())
+            }
+        };
+
+        match fields[0] {
+            "zsync" => {
+                check_fields(2)?;
+                Ok(UpdateInfo::Zsync {
+                    url: fields[1].to_string(),
+                })
+            }
+            "gh-releases-zsync" => {
+                check_fields(5)?;
+                Ok(UpdateInfo::GhReleasesZsync {
+                    owner: fields[1].to_string(),
+                    repo: fields[2].to_string(),
+                    release: fields[3].to_string(),
+                    filename: fields[4].to_string(),
+                })
+            }
+            "pling-v1-zsync" => {
+                check_fields(3)?;
+                Ok(UpdateInfo::PlingV1Zsync {
+                    product_id: fields[1].to_string(),
+                    filename: fields[2].to_string(),
+                })
+            }
+            "" => Err(Error::new(ErrorKind::InvalidAppImage, "Empty update information")),
+            transport => Err(Error::new(ErrorKind::InvalidAppImage, format!("Unsupported update transport: {}", transport))),
+        }
+    }
+
+    /// Short human readable description of where updates come from
+    pub fn description(&self) -> String {
+        match self {
+            UpdateInfo::Zsync { url } => format!("zsync ({})", url),
+            UpdateInfo::GhReleasesZsync { owner, repo, .. } => {
+                format!("GitHub releases ({}/{})", owner, repo)
+            }
+            UpdateInfo::PlingV1Zsync { product_id, .. } => format!("Pling ({})", product_id),
+        }
+    }
+}
+
+impl fmt::Display for UpdateInfo {
+    /// Format the update information back to the string embedded in the AppImage
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateInfo::Zsync { url } => write!(f, "zsync|{}", url),
+            UpdateInfo::GhReleasesZsync {
+                owner,
+                repo,
+                release,
+                filename,
+            } => write!(
+                f,
+                "gh-releases-zsync|{}|{}|{}|{}",
+                owner, repo, release, filename
+            ),
+            UpdateInfo::PlingV1Zsync {
+                product_id,
+                filename,
+            } => write!(f, "pling-v1-zsync|{}|{}", product_id, filename),
+        }
+    }
+}
+
+/// Read the update information embedded in an AppImage.
+/// Returns None when the AppImage carries no update information.
+pub fn read_update_info(app_image_path: &Path) -> Result<Option<UpdateInfo>> {
+    let mut file =
+        File::open(app_image_path).map_err(|e| Error::io("Failed to open AppImage", e))?;
+
+    let raw = match detect_app_image_type(&mut file)? {
+        AppImageType::Type1 => {
+            let mut buf = vec![0u8; TYPE1_UPDATE_INFO_SIZE];
+            file.seek(SeekFrom::Start(TYPE1_UPDATE_INFO_OFFSET))
+                .and_then(|_| file.read_exact(&mut buf))
+                .map_err(|e| Error::io("Failed to read update information", e))?;
+            buf
+        }
+        AppImageType::Type2 => match read_section(&mut file, UPDATE_INFO_SECTION)? {
+            Some(section) => section,
+            None => return Ok(None),
+        },
+    };
+
+    // The section is zero padded, the string ends at the first NUL byte
+    let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+    let value = String::from_utf8_lossy(&raw[..end]).trim().to_string();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    UpdateInfo::parse(&value).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::AppImageFixture;
+
+    #[test]
+    fn test_parse_update_info() {
+        assert_eq!(
+            UpdateInfo::parse("zsync|https://example.com/App-latest-x86_64.AppImage.zsync").unwrap(),
+            UpdateInfo::Zsync {
+                url: "https://example.com/App-latest-x86_64.AppImage.zsync".to_string()
+            }
+        );
+        assert_eq!(
+            UpdateInfo::parse("gh-releases-zsync|owner|repo|latest|App-*x86_64.AppImage.zsync")
+                .unwrap(),
+            UpdateInfo::GhReleasesZsync {
+                owner: "owner".to_string(),
+                repo: "repo".to_string(),
+                release: "latest".to_string(),
+                filename: "App-*x86_64.AppImage.zsync".to_string(),
+            }
+        );
+        assert!(UpdateInfo::parse("gh-releases-zsync|owner|repo").is_err());
+        assert!(UpdateInfo::parse("bintray-zsync|a|b|c|d").is_err());
+    }
+
+    #[test]
+    fn test_update_info_round_trip() {
+        let value = "gh-releases-zsync|owner|repo|latest|App-*x86_64.AppImage.zsync";
+        assert_eq!(UpdateInfo::parse(value).unwrap().to_string(), value);
+    }
+
+    #[test]
+    fn test_read_update_info_from_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Test.AppImage");
+        let mut section = b"zsync|https://example.com/Test.AppImage.zsync".to_vec();
+        section.resize(1024, 0);
+        AppImageFixture::with_app("test")
+            .section(UPDATE_INFO_SECTION, &section)
+            .write_to(&path);
+
+        assert_eq!(
+            read_update_info(&path).unwrap(),
+            Some(UpdateInfo::Zsync {
+                url: "https://example.com/Test.AppImage.zsync".to_string()
+            })
+        );
+
+        AppImageFixture::with_app("test").write_to(&path);
+        assert_eq!(read_update_info(&path).unwrap(), None);
+    }
+}
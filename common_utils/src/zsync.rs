@@ -0,0 +1,406 @@
+use crate::error::{Error, ErrorKind, Result};
+use md4::{Digest, Md4};
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// The largest target file accepted, the `Length` header is checked against it
+/// before anything is downloaded
+pub const MAX_LENGTH: u64 = 8 * 1024 * 1024 * 1024;
+
+/// The size of the chunks read from the local file while matching blocks
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The checksums of a single block of the target file, truncated to the lengths
+/// declared in the `Hash-Lengths` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockChecksum {
+    pub rsum: u32,
+    pub checksum: Vec<u8>,
+}
+
+/// A parsed `.zsync` control file: the description of the target file and the
+/// checksums of each of its blocks.
+#[derive(Debug, Clone)]
+pub struct ZsyncControl {
+    pub filename: Option<String>,
+    pub block_size: usize,
+    pub length: u64,
+    pub seq_matches: usize,
+    pub rsum_bytes: usize,
+    pub checksum_bytes: usize,
+    /// URLs of the target file, possibly relative to the control file URL
+    pub urls: Vec<String>,
+    /// Hex encoded SHA-1 of the whole target file
+    pub sha1: Option<String>,
+    pub blocks: Vec<BlockChecksum>,
+}
+
+impl ZsyncControl {
+    /// Parse a `.zsync` control file: a list of `Key: value` headers terminated by an
+    /// empty line, followed by the checksums of every block.
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let header_end = content
+            .windows(2)
+            .position(|window| window == b"\n\n")
+            .ok_or_else(|| Error::new(ErrorKind::Network, "Invalid zsync file: header not terminated"))?;
+        let header = String::from_utf8_lossy(&content[..header_end]);
+
+        let mut control = ZsyncControl {
+            filename: None,
+            block_size: 0,
+            length: 0,
+            seq_matches: 1,
+            rsum_bytes: 4,
+            checksum_bytes: 16,
+            urls: Vec::new(),
+            sha1: None,
+            blocks: Vec::new(),
+        };
+
+        for line in header.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(Error::new(ErrorKind::Network, format!("Invalid zsync header line: {}", line))),
+            };
+            match key {
+                "Filename" => control.filename = Some(value.to_string()),
+                "Blocksize" => {
+                    control.block_size = value
+                        .parse()
+                        .map_err(|_| Error::new(ErrorKind::Network, format!("Invalid zsync block size: {}", value)))?
+                }
+                "Length" => {
+                    control.length = value
+                        .parse()
+                        .map_err(|_| Error::new(ErrorKind::Network, format!("Invalid zsync length: {}", value)))?
+                }
+                "Hash-Lengths" => {
+                    let lengths: Vec<usize> = value
+                        .split(',')
+                        .map(|length| length.trim().parse())
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| Error::new(ErrorKind::Network, format!("Invalid zsync hash lengths: {}", value)))?;
+                    if lengths.len() != 3
+                        || !(1..=2).contains(&lengths[0])
+                        || !(1..=4).contains(&lengths[1])
+                        || !(3..=16).contains(&lengths[2])
+                    {
+                        return Err(Error::new(ErrorKind::Network, format!("Invalid zsync hash lengths: {}", value)));
+                    }
+                    control.seq_matches = lengths[0];
+                    control.rsum_bytes = lengths[1];
+                    control.checksum_bytes = lengths[2];
+                }
+                "URL" => control.urls.push(value.to_string()),
+                "SHA-1" => control.sha1 = Some(value.to_lowercase()),
+                _ => {}
+            }
+        }
+
+        if control.block_size == 0 || !control.block_size.is_power_of_two() {
+            return Err(Error::new(ErrorKind::Network, format!("Invalid zsync block size: {}", control.block_size)));
+        }
+        if control.urls.is_empty() {
+            return Err(Error::new(ErrorKind::Network, "The zsync file does not declare any URL"));
+        }
+        if control.length > MAX_LENGTH {
+            return Err(Error::new(ErrorKind::Network, format!("Invalid zsync length: {}", control.length)));
+        }
+
+        // every block of the target file has its checksums in the control file
+        let entry_size = control.rsum_bytes + control.checksum_bytes;
+        let checksums = &content[header_end + 2..];
+        let block_count = control.block_count();
+        let checksums_size = entry_size.checked_mul(block_count);
+        if checksums_size.filter(|size| *size <= checksums.len()).is_none() {
+            return Err(Error::new(ErrorKind::Network, "Invalid zsync file: truncated block checksums"));
+        }
+
+        control.blocks = checksums
+            .chunks(entry_size)
+            .take(block_count)
+            .map(|entry| {
+                let rsum = entry[..control.rsum_bytes]
+                    .iter()
+                    .fold(0u32, |rsum, byte| (rsum << 8) | *byte as u32);
+                BlockChecksum {
+                    rsum,
+                    checksum: entry[control.rsum_bytes..].to_vec(),
+                }
+            })
+            .collect();
+
+        Ok(control)
+    }
+
+    /// Number of blocks of the target file
+    pub fn block_count(&self) -> usize {
+        self.length.div_ceil(self.block_size as u64) as usize
+    }
+
+    /// Find the blocks of the target file that are already present in the given data,
+    /// read once from start to end. Returns, for each block, the offset in `data` where
+    /// the block can be copied from.
+    ///
+    /// A block is only reused when both its rolling checksum and its strong checksum
+    /// match; the SHA-1 of the assembled file must still be checked, as truncated
+    /// checksums can collide.
+    pub fn match_blocks(&self, data: impl Read) -> io::Result<Vec<Option<u64>>> {
+        let block_size = self.block_size;
+        let mask = rsum_mask(self.rsum_bytes);
+        let mut matches = vec![None; self.blocks.len()];
+
+        let mut table: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            table.entry(block.rsum).or_default().push(index);
+        }
+
+        // the target file is zero padded to a multiple of the block size,
+        // the local data is padded in the same way
+        let mut data = LocalData::new(data);
+
+        let mut position = 0;
+        let (mut a, mut b) = rsum_parts(&data.block(0, block_size)?);
+        while data.has_byte(position)? {
+            data.discard_before(position);
+            if let Some(candidates) = table.get(&(pack_rsum(a, b) & mask)) {
+                let checksum = block_checksum(&data.block(position, block_size)?);
+                let mut matched = false;
+                for &index in candidates {
+                    if self.blocks[index].checksum == checksum[..self.checksum_bytes] {
+                        if matches[index].is_none() {
+                            matches[index] = Some(position);
+                        }
+                        matched = true;
+                    }
+                }
+
+                if matched {
+                    position += block_size as u64;
+                    (a, b) = rsum_parts(&data.block(position, block_size)?);
+                    continue;
+                }
+            }
+
+            let old = data.byte(position)? as u16;
+            let new = data.byte(position + block_size as u64)? as u16;
+            a = a.wrapping_sub(old).wrapping_add(new);
+            b = b
+                .wrapping_sub((block_size as u16).wrapping_mul(old))
+                .wrapping_add(a);
+            position += 1;
+        }
+
+        Ok(matches)
+    }
+
+    /// Byte ranges (start, end exclusive) of the target file that could not be found locally
+    pub fn missing_ranges(&self, matches: &[Option<u64>]) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for (index, block) in matches.iter().enumerate() {
+            if block.is_some() {
+                continue;
+            }
+            let start = (index * self.block_size) as u64;
+            let end = (start + self.block_size as u64).min(self.length);
+            match ranges.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+        ranges
+    }
+}
+
+/// The rolling checksum used by zsync, packed as `a << 16 | b`
+pub fn rsum(block: &[u8]) -> u32 {
+    let (a, b) = rsum_parts(block);
+    pack_rsum(a, b)
+}
+
+/// The MD4 strong checksum of a block
+pub fn block_checksum(block: &[u8]) -> Vec<u8> {
+    Md4::digest(block).to_vec()
+}
+
+/// Hex encoded SHA-1 of the given data, as found in the `SHA-1` header
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hex encoded SHA-1 of a file, read in chunks
+pub fn file_sha1_hex(path: &Path) -> Result<String> {
+    let file = File::open(path).map_err(|e| Error::io(format!("Failed to open {:?}", path), e))?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut BufReader::new(file), &mut hasher)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", path), e))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Keep only the bytes of the rolling checksum stored in the control file
+pub fn rsum_mask(rsum_bytes: usize) -> u32 {
+    if rsum_bytes >= 4 {
+        u32::MAX
+    } else {
+        (1u32 << (8 * rsum_bytes)) - 1
+    }
+}
+
+fn rsum_parts(block: &[u8]) -> (u16, u16) {
+    let length = block.len() as u16;
+    block
+        .iter()
+        .enumerate()
+        .fold((0u16, 0u16), |(a, b), (index, byte)| {
+            (
+                a.wrapping_add(*byte as u16),
+                b.wrapping_add((length.wrapping_sub(index as u16)).wrapping_mul(*byte as u16)),
+            )
+        })
+}
+
+fn pack_rsum(a: u16, b: u16) -> u32 {
+    ((a as u32) << 16) | b as u32
+}
+
+/// The local data, read as the matching moves forward. Only the bytes from the
+/// current position on are kept, and the bytes past the end of the data read as zeros.
+struct LocalData<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// The offset in the data of the first byte of the buffer
+    offset: u64,
+    at_end: bool,
+}
+
+impl<R: Read> LocalData<R> {
+    fn new(reader: R) -> Self {
+        LocalData {
+            reader,
+            buffer: Vec::new(),
+            offset: 0,
+            at_end: false,
+        }
+    }
+
+    /// Read until the buffer holds the bytes before `end`, or the data ends
+    fn fill(&mut self, end: u64) -> io::Result<()> {
+        while !self.at_end && self.offset + (self.buffer.len() as u64) < end {
+            let length = self.buffer.len();
+            self.buffer.resize(length + READ_CHUNK_SIZE, 0);
+            let read = self.reader.read(&mut self.buffer[length..]);
+            self.buffer.truncate(length + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(read) => self.at_end = read == 0,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the bytes before the given position, once there are enough of them
+    fn discard_before(&mut self, position: u64) {
+        let discarded = (position.saturating_sub(self.offset) as usize).min(self.buffer.len());
+        if discarded >= READ_CHUNK_SIZE {
+            self.buffer.drain(..discarded);
+            self.offset += discarded as u64;
+        }
+    }
+
+    fn has_byte(&mut self, position: u64) -> io::Result<bool> {
+        self.fill(position + 1)?;
+        Ok(position < self.offset + self.buffer.len() as u64)
+    }
+
+    fn byte(&mut self, position: u64) -> io::Result<u8> {
+        self.fill(position + 1)?;
+        Ok(self.buffer.get((position - self.offset) as usize).copied().unwrap_or(0))
+    }
+
+    /// The block starting at `position`, zero padded to the block size
+    fn block(&mut self, position: u64, block_size: usize) -> io::Result<Vec<u8>> {
+        self.fill(position + block_size as u64)?;
+        let mut block = vec![0u8; block_size];
+        let start = ((position - self.offset) as usize).min(self.buffer.len());
+        let end = (start + block_size).min(self.buffer.len());
+        block[..end - start].copy_from_slice(&self.buffer[start..end]);
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{pseudo_random_data, zsync_control_file};
+
+    #[test]
+    fn test_parse_control_file() {
+        let data = pseudo_random_data(10_000, 1);
+        let content = zsync_control_file(&data, 2048, "Test.AppImage", "Test.AppImage");
+        let control = ZsyncControl::parse(&content).unwrap();
+
+        assert_eq!(control.filename, Some("Test.AppImage".to_string()));
+        assert_eq!(control.block_size, 2048);
+        assert_eq!(control.length, 10_000);
+        assert_eq!(control.urls, vec!["Test.AppImage".to_string()]);
+        assert_eq!(control.sha1, Some(sha1_hex(&data)));
+        assert_eq!(control.blocks.len(), 5);
+    }
+
+    #[test]
+    fn test_match_shifted_blocks() {
+        let target = pseudo_random_data(16 * 1024, 1);
+        // the local file has some bytes inserted at the start and a modified block
+        let mut local = b"inserted bytes".to_vec();
+        local.extend_from_slice(&target);
+        local[5000] ^= 0xff;
+
+        let content = zsync_control_file(&target, 1024, "Test.AppImage", "Test.AppImage");
+        let control = ZsyncControl::parse(&content).unwrap();
+        let matches = control.match_blocks(&local[..]).unwrap();
+
+        assert_eq!(matches[0], Some(14));
+        assert_eq!(matches[4], None);
+        assert_eq!(matches.iter().filter(|block| block.is_some()).count(), 15);
+        assert_eq!(control.missing_ranges(&matches), vec![(4096, 5120)]);
+    }
+
+    #[test]
+    fn test_match_blocks_past_the_read_chunks() {
+        let target = pseudo_random_data(3 * READ_CHUNK_SIZE, 1);
+        let mut local = b"inserted bytes".to_vec();
+        local.extend_from_slice(&target);
+
+        let content = zsync_control_file(&target, 4096, "Test.AppImage", "Test.AppImage");
+        let control = ZsyncControl::parse(&content).unwrap();
+        let matches = control.match_blocks(&local[..]).unwrap();
+
+        assert!(matches
+            .iter()
+            .enumerate()
+            .all(|(index, offset)| *offset == Some(14 + index as u64 * 4096)));
+    }
+
+    #[test]
+    fn test_refuse_oversized_control_file() {
+        let oversized = format!("Blocksize: 2048\nLength: {}\nURL: Test.AppImage\n\n", MAX_LENGTH + 1);
+        let error = ZsyncControl::parse(oversized.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("Invalid zsync length"), "{}", error);
+
+        // the checksums of the declared blocks must be in the file
+        let truncated = format!("Blocksize: 2048\nLength: {}\nURL: Test.AppImage\n\n", MAX_LENGTH);
+        let error = ZsyncControl::parse(truncated.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("truncated block checksums"), "{}", error);
+    }
+}
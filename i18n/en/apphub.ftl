@@ -8,11 +8,84 @@ install-from-file = Install from file
 choose-file = Choose file
 installing = Installing app...
 installation-completed = Installation completed!
+installation-failed = Failed to install { $name }
+installation-cancelled = Installation cancelled
+install-phase-hashing = Checking the AppImage...
+install-phase-inspecting = Reading the app details...
+install-phase-copying = Copying { $done } of { $total } MB...
+install-phase-icons = Installing icons...
+install-phase-desktop-entry = Writing the desktop entry...
+install-phase-committing = Finishing...
+cancel = Cancel
+cancelling = Cancelling...
+install-for = Install for
+install-scope-system = All users
+install-scope-user = Only me
+file-chooser-failed = Unable to open the file chooser
+invalid-file = Unable to open the selected file
+install-running = Another AppImage is being installed
+reading-app-image = Reading the AppImage...
+preview-failed = Unable to read { $name }
+version = Version
+categories = Categories
+file-size = File size
+megabytes = { $size } MB
+sha256 = SHA-256
+signature = Signature
+signature-trusted = Signed by a trusted publisher ({ $fingerprint })
+signature-untrusted = Signed by an unknown publisher ({ $fingerprint })
+signature-unsigned = Not signed
+signature-invalid = Invalid signature: { $reason }
+signature-unverified = Signed, not verified: { $reason }
+updates = Updates
+already-installed = Already installed
+not-installed = No
+installed-version = Version { $version } for { $scope }
+exec-line = Command
+no-sandbox = No sandbox
+back = Back
+install = Install
+upgrade = Upgrade
+reinstall = Reinstall
+keep-both = Keep both
+batch-title = { $count } AppImages
+batch-waiting = Waiting
+batch-installed = Installed as { $name }
+batch-summary = { $installed } installed, { $failed } failed
+batch-failed = Failed to install the { $count } AppImages
+replace-installed = Replace the apps already installed
+install-all = Install all
+done = Done
 
 installed-list = Installed app list
+loading-apps = Loading apps...
+uninstalling = Uninstalling...
+updating = Updating...
+rolling-back = Rolling back...
 uninstall = Uninstall
 run-app = Run
+update-app = Update
+roll-back = Roll back
+updates-from = Updates from { $source }
+no-update-info = No update information
+uninstallation-failed = Failed to uninstall { $name }
+update-failed = Failed to update { $name }
+loading-apps-failed = Failed to load the installed apps
+run-app-failed = Failed to start { $name }
+up-to-date = { $name } is up to date
+rollback-failed = Failed to roll back { $name }
+rolled-back = { $name } now runs version { $version }
+version-history = Versions: { $versions }
+current-version = { $version } (current)
+
+show-details = Show details
+hide-details = Hide details
+dismiss = Dismiss
 
 settings = Settings
 installation-dir = Installation dir
+signature-policy = Unsigned or untrusted AppImages
+signature-policy-refuse = Refuse
+signature-policy-warn = Warn
+signature-policy-allow = Allow
 save-settings = Save settings
@@ -14,6 +14,7 @@ use cosmic::widget::{self, menu, nav_bar};
 use cosmic::{cosmic_theme, theme, Application, ApplicationExt, Element};
 use futures_util::SinkExt;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 const REPOSITORY: &str = "https://github.com/cosmic-utils/app-hub";
@@ -39,6 +40,13 @@ pub struct AppModel {
     pub settings: pages::settings::Settings,
 }
 
+/// What the application is started with
+#[derive(Clone, Debug, Default)]
+pub struct Flags {
+    /// The AppImages to install, given on the command line
+    pub files: Vec<Url>,
+}
+
 /// Messages emitted by the application and its widgets.
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -48,7 +56,8 @@ pub enum Message {
     UpdateConfig(Config),
 
     InstallFromFile(pages::install_from_file::InstallFromFileMessage),
-    ChooseFile(Url),
+    OpenFiles(Vec<Url>),
+    FileDropped(PathBuf),
     InstallationSuccessful,
     Cancelled,
     OpenError(Arc<file_chooser::Error>),
@@ -67,7 +76,7 @@ impl Application for AppModel {
     type Executor = cosmic::executor::Default;
 
     /// Data that your application receives to its init method.
-    type Flags = ();
+    type Flags = Flags;
 
     /// Messages which the application and its widgets will emit.
     type Message = Message;
@@ -84,7 +93,7 @@ impl Application for AppModel {
     }
 
     /// Initializes the application with any given flags and startup commands.
-    fn init(core: Core, _flags: Self::Flags) -> (Self, Command<Self::Message>) {
+    fn init(core: Core, flags: Self::Flags) -> (Self, Command<Self::Message>) {
         // Create a nav bar with three page items.
         let mut nav = nav_bar::Model::default();
 
@@ -127,8 +136,19 @@ impl Application for AppModel {
 
         // Create a startup command that sets the window title.
         let command = app.update_title();
+        // the installed apps are read in the background
+        let load_apps = app
+            .installed_list
+            .update(InstalledListMessage::LoadApps)
+            .map(cosmic::app::Message::App);
+
+        let mut commands = vec![command, load_apps];
+        if !flags.files.is_empty() {
+            let open_files = InstallFromFileMessage::FilesSelected(flags.files);
+            commands.push(app.open_on_install_page(open_files));
+        }
 
-        (app, command)
+        (app, Command::batch(commands))
     }
 
     /// Elements to pack at the start of the header bar.
@@ -206,6 +226,17 @@ impl Application for AppModel {
 
                     Message::UpdateConfig(update.config)
                 }),
+            // Install the files dropped on the window.
+            cosmic::iced::event::listen_with(|event, _status| match event {
+                cosmic::iced::Event::Window(_, cosmic::iced::window::Event::FileDropped(path)) => {
+                    Some(Message::FileDropped(path))
+                }
+                _ => None,
+            }),
+            // Follow the running install.
+            self.install_from_file
+                .subscription()
+                .map(Message::InstallFromFile),
         ])
     }
 
@@ -254,19 +285,25 @@ impl Application for AppModel {
             Message::Settings(message) => {
                 commands.push(self.settings.update(message).map(cosmic::app::Message::App))
             }
-            Message::ChooseFile(message) => commands.push(
-                self.install_from_file
-                    .update(InstallFromFileMessage::FileSelected(message))
-                    .map(cosmic::app::Message::App),
+            Message::OpenFiles(files) => commands.push(
+                self.open_on_install_page(InstallFromFileMessage::FilesSelected(files)),
             ),
+            Message::FileDropped(path) => match Url::from_file_path(&path) {
+                Ok(url) => commands.push(
+                    self.open_on_install_page(InstallFromFileMessage::FileDropped(url)),
+                ),
+                Err(()) => log::error!("dropped file is not an absolute path: {:?}", path),
+            },
             Message::Cancelled => commands.push(
                 self.install_from_file
                     .update(InstallFromFileMessage::Cancelled)
                     .map(cosmic::app::Message::App),
             ),
-            Message::OpenError(arc_err) => {
-                log::error!("Error happened: {}", arc_err);
-            }
+            Message::OpenError(error) => commands.push(
+                self.install_from_file
+                    .update(InstallFromFileMessage::OpenError(error))
+                    .map(cosmic::app::Message::App),
+            ),
             Message::InstallationSuccessful => commands.push(
                 self.install_from_file
                     .update(InstallFromFileMessage::InstallationSuccessful)
@@ -322,6 +359,25 @@ impl AppModel {
             .into()
     }
 
+    /// Show the install page, passing it the AppImages chosen outside of it
+    fn open_on_install_page(&mut self, message: InstallFromFileMessage) -> Command<Message> {
+        let mut commands = vec![];
+        let install_page = self
+            .nav
+            .iter()
+            .find(|&id| self.nav.data::<NavPage>(id) == Some(&NavPage::InstallFromFile));
+        if let Some(id) = install_page {
+            self.nav.activate(id);
+            commands.push(self.update_title());
+        }
+        commands.push(
+            self.install_from_file
+                .update(message)
+                .map(cosmic::app::Message::App),
+        );
+        Command::batch(commands)
+    }
+
     /// Updates the header and window titles.
     pub fn update_title(&mut self) -> Command<Message> {
         let mut window_title = fl!("app-title");
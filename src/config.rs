@@ -2,6 +2,7 @@
 
 use std::{any::TypeId, path::PathBuf};
 
+use common_utils::signature::SignaturePolicy;
 use cosmic::Application;
 use cosmic::{
     cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry},
@@ -14,6 +15,7 @@ pub const CONFIG_VERSION: u64 = 1;
 #[derive(Clone, CosmicConfigEntry, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
     pub installation_dir: PathBuf,
+    pub signature_policy: SignaturePolicy,
 }
 
 impl Default for Config {
@@ -23,7 +25,10 @@ impl Default for Config {
             dirs::home_dir().unwrap().to_string_lossy()
         ));
 
-        Self { installation_dir }
+        Self {
+            installation_dir,
+            signature_policy: SignaturePolicy::default(),
+        }
     }
 }
 
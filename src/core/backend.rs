@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+
+use common_utils::backend_event::{ActionResult, BackendEvent, ErrorCode, CANCEL_REQUEST};
+use common_utils::error::Error;
+use common_utils::install_scope::InstallScope;
+use cosmic::iced::{subscription, Subscription};
+use futures_util::SinkExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Build the command running `app_hub_backend` for the given scope.
+/// System installs go through pkexec, user installs run as the current user.
+pub fn backend_command(scope: InstallScope) -> Result<Command, Error> {
+    let current_exe = std::env::current_exe()
+        .map_err(|e| Error::io("Unable to find the AppHub executable", e))?;
+    let backend = current_exe
+        .parent()
+        .ok_or_else(|| Error::other("Unable to find the directory of the AppHub executable"))?
+        .join("app_hub_backend");
+
+    let mut cmd = if scope.requires_privileges() {
+        let mut cmd = Command::new("pkexec");
+        cmd.arg(backend);
+        cmd
+    } else {
+        Command::new(backend)
+    };
+    cmd.arg("--scope")
+        .arg(scope.to_string())
+        .arg("--output")
+        .arg("json");
+    Ok(cmd)
+}
+
+/// What the backend reported while running an action
+#[derive(Debug, Clone, Default)]
+pub struct BackendOutcome {
+    pub warnings: Vec<String>,
+    pub result: Option<ActionResult>,
+}
+
+/// A failed backend run
+#[derive(Debug, Clone)]
+pub struct BackendFailure {
+    pub code: ErrorCode,
+    pub error: Error,
+    /// The last lines logged by the backend, to show with the error
+    pub log: Vec<String>,
+}
+
+impl BackendFailure {
+    pub fn new(code: ErrorCode, error: Error) -> Self {
+        BackendFailure {
+            code,
+            error,
+            log: Vec::new(),
+        }
+    }
+}
+
+/// The number of lines of the backend log kept for the failures
+const LOG_LINES: usize = 200;
+
+/// The tail of the log the backend writes on stderr
+#[derive(Default)]
+struct BackendLog {
+    lines: VecDeque<String>,
+}
+
+impl BackendLog {
+    /// Keep a line, and forward it to the log of the app
+    fn push(&mut self, line: String) {
+        log::debug!("app_hub_backend output: {}", line);
+        if self.lines.len() == LOG_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Read the log until the backend closes its stderr
+    fn read(stderr: impl Read) -> Vec<String> {
+        let mut log = BackendLog::default();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            log.push(line);
+        }
+        Vec::from(log.lines)
+    }
+}
+
+/// Collects the events written by the backend into its outcome
+#[derive(Default)]
+struct EventCollector {
+    outcome: BackendOutcome,
+    failure: Option<BackendFailure>,
+}
+
+impl EventCollector {
+    fn handle(&mut self, event: &BackendEvent) {
+        match event {
+            BackendEvent::Progress { message } => log::info!("app_hub_backend: {}", message),
+            BackendEvent::InstallPhase(phase) => log::debug!("app_hub_backend: {:?}", phase),
+            BackendEvent::Warning { message } => {
+                log::warn!("app_hub_backend: {}", message);
+                self.outcome.warnings.push(message.clone());
+            }
+            BackendEvent::BatchFile(file) => {
+                log::info!("app_hub_backend: {}: {:?}", file.file_name(), file.status)
+            }
+            BackendEvent::Error { code, error } => {
+                log::error!("app_hub_backend: {} ({})", error, code);
+                self.failure = Some(BackendFailure::new(*code, error.clone()));
+            }
+            BackendEvent::Result { result } => self.outcome.result = Some(result.clone()),
+        }
+    }
+
+    fn finish(
+        self,
+        status: ExitStatus,
+        log: Vec<String>,
+    ) -> Result<BackendOutcome, BackendFailure> {
+        if status.success() {
+            return Ok(self.outcome);
+        }
+
+        // pkexec exits with 126 or 127 when the authentication is dismissed or fails
+        let mut failure = self.failure.unwrap_or_else(|| {
+            BackendFailure::new(
+                status
+                    .code()
+                    .and_then(ErrorCode::from_exit_code)
+                    .unwrap_or(ErrorCode::Failure),
+                Error::other(format!("app_hub_backend failed with {}", status)),
+            )
+        });
+        failure.log = log;
+        Err(failure)
+    }
+}
+
+fn spawn_failure(e: std::io::Error) -> BackendFailure {
+    BackendFailure::new(
+        ErrorCode::Failure,
+        Error::io("Failed to start app_hub_backend", e),
+    )
+}
+
+fn wait_failure(e: std::io::Error) -> BackendFailure {
+    BackendFailure::new(
+        ErrorCode::Failure,
+        Error::io("Failed to wait on app_hub_backend", e),
+    )
+}
+
+/// Run the backend and collect the events it writes on stdout.
+/// The logs of the backend are kept for the failures and forwarded to the log of the app.
+pub fn run_backend(mut cmd: Command) -> Result<BackendOutcome, BackendFailure> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_failure)?;
+    let log_reader = child
+        .stderr
+        .take()
+        .map(|stderr| std::thread::spawn(move || BackendLog::read(stderr)));
+
+    let mut collector = EventCollector::default();
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            match BackendEvent::parse(&line) {
+                Some(event) => collector.handle(&event),
+                None => log::debug!("app_hub_backend output: {}", line),
+            }
+        }
+    }
+
+    let status = child.wait().map_err(wait_failure)?;
+    let log = log_reader
+        .and_then(|log_reader| log_reader.join().ok())
+        .unwrap_or_default();
+    collector.finish(status, log)
+}
+
+/// Run the backend on a blocking thread, so that the GUI is not blocked while pkexec
+/// prompts for a password or the backend works
+pub async fn run_backend_task(cmd: Command) -> Result<BackendOutcome, BackendFailure> {
+    tokio::task::spawn_blocking(move || run_backend(cmd))
+        .await
+        .unwrap_or_else(|e| {
+            Err(BackendFailure::new(
+                ErrorCode::Failure,
+                Error::other("app_hub_backend task failed").caused_by(e),
+            ))
+        })
+}
+
+/// A backend run driven by [`backend_subscription`], so that the GUI is not blocked
+/// while it runs. Each request needs its own id for the run to start again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BackendRequest {
+    pub id: u64,
+    pub scope: InstallScope,
+    pub args: Vec<OsString>,
+}
+
+/// What a backend run started by [`backend_subscription`] reports
+#[derive(Debug, Clone)]
+pub enum BackendUpdate {
+    /// The backend started, it can be cancelled with the handle
+    Started(BackendCanceller),
+    /// An event written by the backend
+    Event(BackendEvent),
+    Finished(Result<BackendOutcome, BackendFailure>),
+}
+
+/// Asks a running backend to cancel its action
+#[derive(Debug, Clone)]
+pub struct BackendCanceller(mpsc::UnboundedSender<()>);
+
+impl BackendCanceller {
+    pub fn cancel(&self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// Run the backend in the background, reporting its events as they are written.
+/// The subscription stays idle once the backend finished, until it is dropped.
+pub fn backend_subscription(request: BackendRequest) -> Subscription<BackendUpdate> {
+    subscription::channel(request.clone(), 100, move |mut output| async move {
+        let result = run_backend_async(request, &mut output).await;
+        let _ = output.send(BackendUpdate::Finished(result)).await;
+
+        futures_util::future::pending().await
+    })
+}
+
+async fn run_backend_async(
+    request: BackendRequest,
+    output: &mut cosmic::iced::futures::channel::mpsc::Sender<BackendUpdate>,
+) -> Result<BackendOutcome, BackendFailure> {
+    let mut cmd = backend_command(request.scope)
+        .map_err(|error| BackendFailure::new(ErrorCode::Failure, error))?;
+    cmd.args(&request.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = tokio::process::Command::from(cmd)
+        .spawn()
+        .map_err(spawn_failure)?;
+    let log_reader = child.stderr.take().map(|stderr| {
+        tokio::spawn(async move {
+            let mut log = BackendLog::default();
+            let mut lines = tokio::io::BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                log.push(line);
+            }
+            Vec::from(log.lines)
+        })
+    });
+
+    let (cancel_sender, mut cancel_receiver) = mpsc::unbounded_channel();
+    let _ = output
+        .send(BackendUpdate::Started(BackendCanceller(cancel_sender)))
+        .await;
+
+    // the backend may run as root through pkexec, it is asked to stop on stdin
+    // instead of being killed
+    let mut stdin = child.stdin.take();
+    let mut collector = EventCollector::default();
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = tokio::io::BufReader::new(stdout).lines();
+        loop {
+            tokio::select! {
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => match BackendEvent::parse(&line) {
+                        Some(event) => {
+                            collector.handle(&event);
+                            let _ = output.send(BackendUpdate::Event(event)).await;
+                        }
+                        None => log::debug!("app_hub_backend output: {}", line),
+                    },
+                    _ => break,
+                },
+                Some(()) = cancel_receiver.recv() => {
+                    if let Some(stdin) = stdin.as_mut() {
+                        log::info!("Cancelling app_hub_backend");
+                        let request = format!("{}\n", CANCEL_REQUEST);
+                        if let Err(e) = stdin.write_all(request.as_bytes()).await {
+                            log::error!("Failed to cancel app_hub_backend: {}", e);
+                        }
+                        let _ = stdin.flush().await;
+                    }
+                }
+            }
+        }
+    }
+
+    let status = child.wait().await.map_err(wait_failure)?;
+    let log = match log_reader {
+        Some(log_reader) => log_reader.await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+    collector.finish(status, log)
+}
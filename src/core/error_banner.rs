@@ -0,0 +1,98 @@
+use common_utils::error::Error;
+use cosmic::iced::{self, Length, Padding};
+use cosmic::{widget, Element};
+
+use crate::core::backend::BackendFailure;
+use crate::fl;
+
+/// An inline banner telling what failed and why, with the details behind an expander
+#[derive(Debug, Clone)]
+pub struct ErrorBanner {
+    title: String,
+    /// The error with its causes
+    reason: String,
+    /// The log of the backend
+    details: Vec<String>,
+    expanded: bool,
+}
+
+impl ErrorBanner {
+    pub fn new(title: String, error: &Error) -> Self {
+        ErrorBanner {
+            title,
+            reason: error.to_string(),
+            details: Vec::new(),
+            expanded: false,
+        }
+    }
+
+    /// A failed backend run, with the log of the backend in the details
+    pub fn from_failure(title: String, failure: &BackendFailure) -> Self {
+        ErrorBanner {
+            details: failure.log.clone(),
+            ..ErrorBanner::new(title, &failure.error)
+        }
+    }
+
+    pub fn toggle_details(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    pub fn view<'a, Message: Clone + 'static>(
+        &'a self,
+        on_toggle_details: Message,
+        on_dismiss: Message,
+    ) -> Element<'a, Message> {
+        let mut buttons = widget::row();
+        if !self.details.is_empty() {
+            let label = if self.expanded {
+                fl!("hide-details")
+            } else {
+                fl!("show-details")
+            };
+            buttons = buttons
+                .push(
+                    cosmic::widget::button::text(label)
+                        .on_press(on_toggle_details)
+                        .style(widget::button::Style::Standard),
+                )
+                .push(widget::horizontal_space(Length::from(10)));
+        }
+        buttons = buttons.push(
+            cosmic::widget::button::text(fl!("dismiss"))
+                .on_press(on_dismiss)
+                .style(widget::button::Style::Standard),
+        );
+
+        let mut col = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::heading(self.title.as_str()))
+                            .push(widget::text::text(self.reason.as_str()))
+                            .width(Length::Fill),
+                    )
+                    .push(buttons)
+                    .align_items(iced::Alignment::Center),
+            );
+
+        if self.expanded {
+            let details = self
+                .details
+                .iter()
+                .fold(widget::column(), |details, line| {
+                    details.push(widget::text::caption(line.as_str()))
+                });
+            col = col
+                .push(widget::vertical_space(Length::from(10)))
+                .push(widget::scrollable(details).height(Length::from(200)));
+        }
+
+        widget::container(col)
+            .padding(Padding::from(10))
+            .style(cosmic::theme::Container::Card)
+            .width(iced::Length::Fill)
+            .into()
+    }
+}
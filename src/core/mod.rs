@@ -1 +1,3 @@
+pub mod backend;
+pub mod error_banner;
 pub mod nav;
\ No newline at end of file
@@ -6,6 +6,18 @@ mod i18n;
 mod core;
 mod pages;
 
+use clap::Parser;
+use cosmic::dialog::ashpd::url::Url;
+use std::path::Path;
+
+/// Install and manage AppImages
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// AppImages to install, as paths or file:// URLs
+    files: Vec<String>,
+}
+
 fn main() -> cosmic::iced::Result {
     env_logger::init();
     // Get the system's preferred languages.
@@ -17,6 +29,28 @@ fn main() -> cosmic::iced::Result {
     // Settings for configuring the application window and iced runtime.
     let settings = cosmic::app::Settings::default();
 
-    // Starts the application's event loop with `()` as the application's flags.
-    cosmic::app::run::<app::AppModel>(settings, ())
+    let flags = app::Flags {
+        files: Cli::parse().files.iter().filter_map(|file| file_url(file)).collect(),
+    };
+
+    // Starts the application's event loop with the files to install as its flags.
+    cosmic::app::run::<app::AppModel>(settings, flags)
+}
+
+/// The URL of a command line file, which is either a URL or a path relative to the
+/// working directory
+fn file_url(file: &str) -> Option<Url> {
+    if let Ok(url) = Url::parse(file) {
+        return Some(url);
+    }
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(Path::new(file)))
+        .unwrap_or_else(|_| Path::new(file).to_path_buf());
+    match Url::from_file_path(&path) {
+        Ok(url) => Some(url),
+        Err(()) => {
+            log::error!("ignoring {:?}, which is not a valid path", file);
+            None
+        }
+    }
 }
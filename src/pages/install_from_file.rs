@@ -1,33 +1,98 @@
 use cosmic::dialog::ashpd::url::Url;
 use cosmic::dialog::file_chooser::{self, FileFilter};
-use cosmic::iced::Length;
+use cosmic::iced::{Length, Padding, Subscription};
 use cosmic::widget::vertical_space;
 use cosmic::{
     iced::{self},
     widget::{self, column},
     Command, Element,
 };
-use std::io::{BufRead, BufReader};
-use std::process::Stdio;
+use std::ffi::OsString;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use common_utils::app_image_preview::{preview_app_image, AppImagePreview};
+use common_utils::backend_event::{BackendEvent, BatchFileStatus, ErrorCode, InstallPhase};
+use common_utils::duplicate_policy::DuplicatePolicy;
+use common_utils::error::Error;
+use common_utils::install_scope::InstallScope;
+use common_utils::signature::{default_trusted_keys_dir, SignatureStatus};
+
 use crate::config::Config;
+use crate::core::backend::{backend_subscription, BackendCanceller, BackendRequest, BackendUpdate};
+use crate::core::error_banner::ErrorBanner;
 use crate::{app, fl};
 
 #[derive(Debug, Clone)]
 pub enum InstallFromFileMessage {
     ChooseFile,
-    FileSelected(Url),
+    /// AppImages to install, one is reviewed first and several are installed in a batch
+    FilesSelected(Vec<Url>),
+    FileDropped(Url),
     InstallationSuccessful,
     Cancelled,
     OpenError(Arc<file_chooser::Error>),
+    PreviewLoaded(Result<Box<AppImagePreview>, Error>),
+    ConfirmInstall(DuplicatePolicy),
+    DiscardPreview,
+    ConfirmBatchInstall,
+    DiscardBatch,
+    ReplaceInstalledSelected(bool),
     NoSandboxSelected(bool),
+    ScopeSelected(usize),
+    Backend(BackendUpdate),
+    Cancel,
+    ToggleErrorDetails,
+    DismissError,
 }
 
 pub struct InstallFromFile {
     loading: bool,
+    /// Whether the chosen AppImage is being inspected
+    inspecting: bool,
+    /// The AppImage being inspected
+    inspected_path: Option<PathBuf>,
+    /// The AppImage to review before installing it
+    preview: Option<AppImagePreview>,
+    /// The Exec line of the reviewed AppImage with the chosen options
+    exec_line: Option<String>,
     successful: bool,
+    cancelled: bool,
+    /// The running install, followed by a subscription
+    request: Option<BackendRequest>,
+    /// The number of installs started, to tell the requests apart
+    requests_started: u64,
+    phase: Option<InstallPhase>,
+    canceller: Option<BackendCanceller>,
+    cancelling: bool,
     no_sandbox: bool,
+    scope_options: Vec<String>,
+    scope: usize,
+    /// The file of the running or last install, named in the errors
+    file_name: Option<String>,
+    error: Option<ErrorBanner>,
+    warnings: Vec<String>,
+    /// The AppImages installed together, in the order they were chosen
+    batch: Vec<BatchEntry>,
+    /// Whether the apps of the batch already installed in the scope are replaced
+    replace_installed: bool,
+}
+
+/// An AppImage of the batch, with how far its install went
+struct BatchEntry {
+    file_path: PathBuf,
+    /// None until the backend gets to the AppImage
+    status: Option<BatchFileStatus>,
+}
+
+impl BatchEntry {
+    fn file_name(&self) -> String {
+        self.file_path
+            .file_name()
+            .unwrap_or(self.file_path.as_os_str())
+            .to_string_lossy()
+            .to_string()
+    }
 }
 
 impl Default for InstallFromFile {
@@ -36,14 +101,31 @@ impl Default for InstallFromFile {
         log::info!("creating a default page");
         Self {
             loading: false,
+            inspecting: false,
+            inspected_path: None,
+            preview: None,
+            exec_line: None,
             successful: false,
+            cancelled: false,
+            request: None,
+            requests_started: 0,
+            phase: None,
+            canceller: None,
+            cancelling: false,
             no_sandbox: false,
+            scope_options: vec![fl!("install-scope-system"), fl!("install-scope-user")],
+            scope: scope_index(InstallScope::default()),
+            file_name: None,
+            error: None,
+            warnings: Vec::new(),
+            batch: Vec::new(),
+            replace_installed: false,
         }
     }
 }
 
 impl InstallFromFile {
-    pub fn view<'a>(&self) -> Element<'a, InstallFromFileMessage> {
+    pub fn view<'a>(&'a self) -> Element<'a, InstallFromFileMessage> {
         let mut col = column::<InstallFromFileMessage>().push(
             widget::container(widget::text::title1(fl!("install-from-file")))
                 .width(iced::Length::Fill)
@@ -51,7 +133,53 @@ impl InstallFromFile {
                 .align_y(iced::alignment::Vertical::Center),
         );
 
-        if self.loading {
+        if !self.batch.is_empty() {
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(self.batch_view());
+        } else if self.request.is_some() {
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(cosmic::widget::text::text(self.phase_label()))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(widget::vertical_space(Length::from(10)));
+            col = col.push(
+                widget::container(
+                    iced::widget::progress_bar(
+                        0.0..=1.0,
+                        self.phase.map(|phase| phase.fraction()).unwrap_or_default(),
+                    )
+                    .width(Length::from(400)),
+                )
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+            col = col.push(widget::vertical_space(Length::from(10)));
+            // the files are being moved into place, it is too late to cancel
+            let can_cancel = self.canceller.is_some()
+                && !self.cancelling
+                && self.phase != Some(InstallPhase::Committing);
+            col = col.push(
+                widget::container(
+                    cosmic::widget::button::text(fl!("cancel"))
+                        .style(widget::button::Style::Destructive)
+                        .on_press_maybe(can_cancel.then_some(InstallFromFileMessage::Cancel)),
+                )
+                .width(iced::Length::Fill)
+                .align_x(iced::alignment::Horizontal::Center),
+            );
+        } else if self.inspecting {
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(
+                widget::container(cosmic::widget::text::text(fl!("reading-app-image")))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+        } else if let Some(preview) = &self.preview {
+            col = col.push(widget::vertical_space(Length::from(30)));
+            col = col.push(self.preview_view(preview));
+        } else if self.loading {
             col = col.push(
                 widget::container(cosmic::widget::text::text(fl!("installing")))
                     .width(iced::Length::Fill)
@@ -68,16 +196,6 @@ impl InstallFromFile {
                 .width(iced::Length::Fill)
                 .align_x(iced::alignment::Horizontal::Center),
             );
-            col = col.push(widget::vertical_space(Length::from(30)));
-            col = col.push(
-                widget::container(cosmic::widget::checkbox(
-                    "No sandbox",
-                    self.no_sandbox,
-                    InstallFromFileMessage::NoSandboxSelected,
-                ))
-                .width(iced::Length::Fill)
-                .align_x(iced::alignment::Horizontal::Center),
-            );
         }
 
         if self.successful {
@@ -88,6 +206,29 @@ impl InstallFromFile {
             );
         }
 
+        if self.cancelled {
+            col = col.push(vertical_space(Length::from(30))).push(
+                widget::container(cosmic::widget::text::text(fl!("installation-cancelled")))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+        }
+
+        if let Some(error) = &self.error {
+            col = col.push(vertical_space(Length::from(30))).push(error.view(
+                InstallFromFileMessage::ToggleErrorDetails,
+                InstallFromFileMessage::DismissError,
+            ));
+        }
+
+        for warning in &self.warnings {
+            col = col.push(
+                widget::container(widget::text::caption(warning.as_str()))
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Center),
+            );
+        }
+
         widget::container(col)
             .width(iced::Length::Fill)
             .height(iced::Length::Fill)
@@ -96,6 +237,258 @@ impl InstallFromFile {
             .into()
     }
 
+    /// The details of the chosen AppImage, with the install options
+    fn preview_view<'a>(
+        &'a self,
+        preview: &'a AppImagePreview,
+    ) -> Element<'a, InstallFromFileMessage> {
+        let mut header = widget::row();
+        if let Some(icon_png) = &preview.icon_png {
+            header = header
+                .push(
+                    widget::image::Image::new(widget::image::Handle::from_memory(
+                        icon_png.clone(),
+                    ))
+                    .width(Length::from(64)),
+                )
+                .push(widget::horizontal_space(Length::from(10)));
+        }
+        let mut title = widget::column().push(widget::text::title3(preview.name.as_str()));
+        if let Some(comment) = &preview.comment {
+            title = title.push(widget::text::text(comment.as_str()));
+        }
+        header = header.push(title).align_items(iced::Alignment::Center);
+
+        let installed = if preview.installed.is_empty() {
+            fl!("not-installed")
+        } else {
+            preview
+                .installed
+                .iter()
+                .map(|app| {
+                    fl!(
+                        "installed-version",
+                        version = app.version.as_deref().unwrap_or("-"),
+                        scope = self.scope_options[scope_index(app.scope)].as_str()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let details = widget::column()
+            .push(detail_row(
+                fl!("version"),
+                preview.version.clone().unwrap_or_else(|| "-".to_string()),
+            ))
+            .push(detail_row(fl!("categories"), preview.categories.join(", ")))
+            .push(detail_row(
+                fl!("file-size"),
+                fl!("megabytes", size = megabytes(preview.size)),
+            ))
+            .push(detail_row(fl!("sha256"), preview.sha256.clone()))
+            .push(detail_row(fl!("signature"), signature_label(&preview.signature)))
+            .push(detail_row(
+                fl!("updates"),
+                match &preview.update_info {
+                    Some(update_info) => update_info.description(),
+                    None => fl!("no-update-info"),
+                },
+            ))
+            .push(detail_row(fl!("already-installed"), installed))
+            .push(detail_row(
+                fl!("exec-line"),
+                self.exec_line.clone().unwrap_or_else(|| "-".to_string()),
+            ))
+            .spacing(5);
+
+        let mut buttons = widget::row()
+            .push(
+                cosmic::widget::button::text(fl!("back"))
+                    .style(widget::button::Style::Standard)
+                    .on_press(InstallFromFileMessage::DiscardPreview),
+            )
+            .push(widget::horizontal_space(Length::from(10)));
+        // an app installed in the chosen scope is upgraded, reinstalled or kept next to the new one
+        let scope = InstallScope::ALL[self.scope];
+        buttons = match preview.installed.iter().find(|app| app.scope == scope) {
+            Some(installed) => {
+                let replace_label = if installed.version == preview.version {
+                    fl!("reinstall")
+                } else {
+                    fl!("upgrade")
+                };
+                buttons
+                    .push(
+                        cosmic::widget::button::text(fl!("keep-both"))
+                            .style(widget::button::Style::Standard)
+                            .on_press(InstallFromFileMessage::ConfirmInstall(
+                                DuplicatePolicy::KeepBoth,
+                            )),
+                    )
+                    .push(widget::horizontal_space(Length::from(10)))
+                    .push(
+                        cosmic::widget::button::text(replace_label)
+                            .style(widget::button::Style::Suggested)
+                            .on_press(InstallFromFileMessage::ConfirmInstall(
+                                DuplicatePolicy::Replace,
+                            )),
+                    )
+            }
+            None => buttons.push(
+                cosmic::widget::button::text(fl!("install"))
+                    .style(widget::button::Style::Suggested)
+                    .on_press(InstallFromFileMessage::ConfirmInstall(DuplicatePolicy::Refuse)),
+            ),
+        };
+
+        widget::container(
+            widget::column()
+                .push(header)
+                .push(widget::vertical_space(Length::from(20)))
+                .push(details)
+                .push(widget::vertical_space(Length::from(20)))
+                .push(self.options_view())
+                .push(widget::vertical_space(Length::from(20)))
+                .push(
+                    widget::container(buttons)
+                        .width(iced::Length::Fill)
+                        .align_x(iced::alignment::Horizontal::Right),
+                ),
+        )
+        .padding(Padding::from(20))
+        .style(cosmic::theme::Container::Card)
+        .max_width(700.0)
+        .into()
+    }
+
+    /// The install options shared by a single AppImage and a batch
+    fn options_view(&self) -> Element<'_, InstallFromFileMessage> {
+        widget::column()
+            .push(cosmic::widget::checkbox(
+                fl!("no-sandbox"),
+                self.no_sandbox,
+                InstallFromFileMessage::NoSandboxSelected,
+            ))
+            .push(widget::vertical_space(Length::from(10)))
+            .push(
+                widget::row()
+                    .push(widget::text::text(fl!("install-for")))
+                    .push(widget::horizontal_space(Length::from(10)))
+                    .push(widget::dropdown(
+                        &self.scope_options,
+                        Some(self.scope),
+                        InstallFromFileMessage::ScopeSelected,
+                    ))
+                    .align_items(iced::Alignment::Center),
+            )
+            .into()
+    }
+
+    /// The AppImages of the batch with the status of each, followed by the install
+    /// options before the batch starts and by a summary once it is done
+    fn batch_view(&self) -> Element<'_, InstallFromFileMessage> {
+        let mut files = widget::column().spacing(5);
+        for entry in &self.batch {
+            let status = match &entry.status {
+                None => fl!("batch-waiting"),
+                Some(BatchFileStatus::Installing) => self.phase_label(),
+                Some(BatchFileStatus::Installed { name, .. }) => {
+                    fl!("batch-installed", name = name.as_str())
+                }
+                Some(BatchFileStatus::Failed { error }) => error.to_string(),
+            };
+            files = files.push(detail_row(entry.file_name(), status));
+        }
+
+        let mut content = widget::column()
+            .push(widget::text::title3(fl!("batch-title", count = self.batch.len())))
+            .push(widget::vertical_space(Length::from(20)))
+            .push(files)
+            .push(widget::vertical_space(Length::from(20)));
+
+        let buttons = if self.request.is_some() {
+            let can_cancel = self.canceller.is_some() && !self.cancelling;
+            widget::row().push(
+                cosmic::widget::button::text(fl!("cancel"))
+                    .style(widget::button::Style::Destructive)
+                    .on_press_maybe(can_cancel.then_some(InstallFromFileMessage::Cancel)),
+            )
+        } else if self.batch_started() {
+            let installed = self
+                .batch
+                .iter()
+                .filter(|entry| matches!(entry.status, Some(BatchFileStatus::Installed { .. })))
+                .count();
+            content = content
+                .push(widget::text::text(fl!(
+                    "batch-summary",
+                    installed = installed,
+                    failed = self.batch.len() - installed
+                )))
+                .push(widget::vertical_space(Length::from(20)));
+            widget::row().push(
+                cosmic::widget::button::text(fl!("done"))
+                    .style(widget::button::Style::Suggested)
+                    .on_press(InstallFromFileMessage::DiscardBatch),
+            )
+        } else {
+            content = content
+                .push(self.options_view())
+                .push(widget::vertical_space(Length::from(10)))
+                .push(cosmic::widget::checkbox(
+                    fl!("replace-installed"),
+                    self.replace_installed,
+                    InstallFromFileMessage::ReplaceInstalledSelected,
+                ))
+                .push(widget::vertical_space(Length::from(20)));
+            widget::row()
+                .push(
+                    cosmic::widget::button::text(fl!("back"))
+                        .style(widget::button::Style::Standard)
+                        .on_press(InstallFromFileMessage::DiscardBatch),
+                )
+                .push(widget::horizontal_space(Length::from(10)))
+                .push(
+                    cosmic::widget::button::text(fl!("install-all"))
+                        .style(widget::button::Style::Suggested)
+                        .on_press(InstallFromFileMessage::ConfirmBatchInstall),
+                )
+        };
+
+        widget::container(
+            content.push(
+                widget::container(buttons)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Right),
+            ),
+        )
+        .padding(Padding::from(20))
+        .style(cosmic::theme::Container::Card)
+        .max_width(700.0)
+        .into()
+    }
+
+    /// Whether the backend got to the AppImages of the batch
+    fn batch_started(&self) -> bool {
+        self.batch.iter().any(|entry| entry.status.is_some())
+    }
+
+    /// Where the AppImage is installed for the chosen scope
+    fn installation_dir(&self) -> PathBuf {
+        // user installs go to a directory owned by the user
+        InstallScope::ALL[self.scope]
+            .default_installation_dir()
+            .unwrap_or_else(|| Config::load().1.installation_dir)
+    }
+
+    fn refresh_exec_line(&mut self) {
+        self.exec_line = self
+            .preview
+            .as_ref()
+            .and_then(|preview| preview.exec_line(&self.installation_dir(), self.no_sandbox));
+    }
+
     pub fn update(&mut self, message: InstallFromFileMessage) -> Command<crate::app::Message> {
         let mut commands = vec![];
         match message {
@@ -118,12 +511,8 @@ impl InstallFromFile {
                         .title("Choose a file")
                         .filter(filter);
 
-                    match dialog.open_file().await {
-                        Ok(response) => {
-                            //InstallFromFileMessage::FileSelected(response.url().to_owned())
-
-                            app::Message::ChooseFile(response.url().clone())
-                        }
+                    match dialog.open_files().await {
+                        Ok(response) => app::Message::OpenFiles(response.urls().to_vec()),
 
                         Err(file_chooser::Error::Cancelled) => app::Message::Cancelled,
 
@@ -131,102 +520,122 @@ impl InstallFromFile {
                     }
                 }));
             }
-            InstallFromFileMessage::FileSelected(url) => {
-                log::info!("requested to install {}", url);
+            InstallFromFileMessage::FilesSelected(urls) => {
+                log::info!("requested to install {:?}", urls);
+                self.loading = false;
+                if self.request.is_some() {
+                    // files can be dropped on the window while an install runs
+                    log::warn!("an install is running, ignoring {:?}", urls);
+                    self.error = Some(ErrorBanner::new(
+                        fl!("install-running"),
+                        &Error::other("Wait for the running install to end"),
+                    ));
+                    return Command::batch(commands);
+                }
 
+                self.successful = false;
+                self.cancelled = false;
+                self.error = None;
+                self.warnings.clear();
+                self.batch.clear();
+                self.preview = None;
+                self.exec_line = None;
+                let mut paths = Vec::new();
+                for url in urls {
+                    match url.to_file_path() {
+                        Ok(path) => paths.push(path),
+                        Err(e) => {
+                            log::error!("error converting url to file path: {:?}", e);
+                            self.error = Some(ErrorBanner::new(
+                                fl!("invalid-file"),
+                                &Error::other(format!("{} is not a local file", url)),
+                            ));
+                        }
+                    }
+                }
+                if paths.len() > 1 {
+                    self.start_batch(paths);
+                } else if let Some(path) = paths.pop() {
+                    commands.push(self.inspect(path));
+                }
+            }
+            InstallFromFileMessage::FileDropped(url) => {
+                // the files dropped together arrive one by one, they are gathered in a batch
+                let mut paths = self.pending_paths();
                 match url.to_file_path() {
-                    Ok(path) => {
-                        let config = Config::load();
-                        let installation_dir = config.1.installation_dir;
-                        log::info!("Using installation dir : {:?}", installation_dir);
-                        //let result = install_app_image(path, installation_dir, false);
-                        //log::info!("result from install: {:?}", result);
-
-                        let current_exe =
-                            std::env::current_exe().map_err(|_| "unable to get current exe");
-                        match current_exe {
-                            Ok(current_exe) => {
-                                let cmd = std::process::Command::new("pkexec")
-                                    .arg(current_exe.parent().unwrap().join("app_hub_backend"))
-                                    .arg("--action")
-                                    .arg("install")
-                                    .arg("--file-path")
-                                    .arg(path)
-                                    .arg("--install-dir")
-                                    .arg(installation_dir)
-                                    .arg("--no-sandbox")
-                                    .arg(self.no_sandbox.to_string())
-                                    .stdout(Stdio::piped())
-                                    .stderr(Stdio::piped())
-                                    .spawn();
-
-                                match cmd {
-                                    Ok(mut child) => {
-                                        // Read stdout
-                                        if let Some(stdout) = child.stdout.take() {
-                                            let reader = BufReader::new(stdout);
-                                            for line in reader.lines() {
-                                                if let Ok(line) = line {
-                                                    log::debug!("app_hub_backend output: {}", line);
-                                                } else {
-                                                    log::error!("Failed to read line from stdout");
-                                                }
-                                            }
-                                        }
-
-                                        // Read stderr
-                                        if let Some(stderr) = child.stderr.take() {
-                                            let reader = BufReader::new(stderr);
-                                            for line in reader.lines() {
-                                                if let Ok(line) = line {
-                                                    log::error!("app_hub_backend error: {}", line);
-                                                } else {
-                                                    log::error!("Failed to read line from stderr");
-                                                }
-                                            }
-                                        }
-
-                                        let output = child
-                                            .wait_with_output()
-                                            .expect("Failed to wait on child");
-                                        log::debug!("output: {:?}", output);
-                                        if output.status.success() {
-                                            log::info!("Installation successful");
-                                            self.successful = true;
-                                            commands.push(Command::perform(async {}, |_| {
-                                                app::Message::InstallationSuccessful
-                                            }));
-                                            commands.push(Command::perform(async {}, |_| {
-                                                app::Message::LoadApps
-                                            }));
-                                        } else {
-                                            log::error!("Installation failed");
-                                            //return Err("Installation failed".to_string());
-                                        }
-                                    }
-                                    Err(error) => {
-                                        log::error!("error: {:?}", error);
-                                        //return Err("Failed to install AppImage".to_string());
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("current_exe error: {:?}", e);
-                            }
-                        };
+                    Ok(path) if !paths.is_empty() => {
+                        log::info!("adding {:?} to the batch", path);
+                        paths.push(path);
+                        self.start_batch(paths);
                     }
-                    Err(e) => {
-                        log::error!("error converting url to file path: {:?}", e);
-                        //TODO show error message
+                    _ => {
+                        commands.push(self.update(InstallFromFileMessage::FilesSelected(vec![url])))
                     }
+                }
+            }
+            InstallFromFileMessage::PreviewLoaded(preview) => {
+                if !self.inspecting {
+                    log::debug!("ignoring the preview of a file that is no longer reviewed");
+                    return Command::batch(commands);
+                }
+                self.inspecting = false;
+                self.inspected_path = None;
+                match preview {
+                    Ok(preview) => {
+                        log::info!("Previewing {} ({})", preview.name, preview.id);
+                        self.preview = Some(*preview);
+                        self.refresh_exec_line();
+                    }
+                    Err(error) => {
+                        log::error!("Failed to read the AppImage: {}", error);
+                        self.error = Some(ErrorBanner::new(
+                            fl!(
+                                "preview-failed",
+                                name = self.file_name.clone().unwrap_or_default()
+                            ),
+                            &error,
+                        ));
+                    }
+                }
+            }
+            InstallFromFileMessage::DiscardPreview => {
+                self.preview = None;
+                self.exec_line = None;
+            }
+            InstallFromFileMessage::ConfirmInstall(on_duplicate) => {
+                if let Some(preview) = self.preview.take() {
+                    self.exec_line = None;
+                    self.start_install(vec![preview.file_path], on_duplicate);
+                }
+            }
+            InstallFromFileMessage::ConfirmBatchInstall => {
+                let file_paths = self.batch.iter().map(|entry| entry.file_path.clone()).collect();
+                let on_duplicate = if self.replace_installed {
+                    DuplicatePolicy::Replace
+                } else {
+                    DuplicatePolicy::Refuse
                 };
+                self.error = None;
+                self.warnings.clear();
+                self.start_install(file_paths, on_duplicate);
+            }
+            InstallFromFileMessage::DiscardBatch => {
+                self.batch.clear();
+                self.cancelled = false;
+            }
+            InstallFromFileMessage::ReplaceInstalledSelected(selected) => {
+                self.replace_installed = selected;
             }
             InstallFromFileMessage::Cancelled => {
                 self.loading = false;
             }
-            InstallFromFileMessage::OpenError(arc) => {
-                //TODO sho error message
+            InstallFromFileMessage::OpenError(error) => {
+                log::error!("Failed to open the file chooser: {}", error);
                 self.loading = false;
+                self.error = Some(ErrorBanner::new(
+                    fl!("file-chooser-failed"),
+                    &Error::other(error.to_string()),
+                ));
             }
             InstallFromFileMessage::InstallationSuccessful => {
                 self.loading = false;
@@ -234,8 +643,243 @@ impl InstallFromFile {
             InstallFromFileMessage::NoSandboxSelected(selected) => {
                 self.no_sandbox = selected;
                 log::info!("no sandbox: {}", self.no_sandbox);
+                self.refresh_exec_line();
+            }
+            InstallFromFileMessage::ScopeSelected(scope) => {
+                self.scope = scope;
+                log::info!("install scope: {}", InstallScope::ALL[scope]);
+                self.refresh_exec_line();
+            }
+            InstallFromFileMessage::Backend(update) => match update {
+                BackendUpdate::Started(canceller) => self.canceller = Some(canceller),
+                BackendUpdate::Event(BackendEvent::InstallPhase(phase)) => self.phase = Some(phase),
+                BackendUpdate::Event(BackendEvent::BatchFile(file)) => {
+                    if let Some(entry) = self.batch.get_mut(file.index) {
+                        if file.status == BatchFileStatus::Installing {
+                            self.phase = None;
+                        }
+                        entry.status = Some(file.status);
+                    }
+                }
+                BackendUpdate::Event(_) => {}
+                BackendUpdate::Finished(result) => {
+                    self.request = None;
+                    self.canceller = None;
+                    self.cancelling = false;
+                    self.loading = false;
+                    match result {
+                        // the AppImages of the batch show their own outcome
+                        Ok(outcome) if !self.batch.is_empty() => {
+                            log::info!("Batch installation successful");
+                            self.warnings = outcome.warnings;
+                            commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                        }
+                        Err(failure) if self.batch_started() => {
+                            log::error!("Batch installation failed: {}", failure.error);
+                            self.cancelled = failure.code == ErrorCode::Cancelled;
+                            commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                        }
+                        Ok(outcome) => {
+                            log::info!("Installation successful: {:?}", outcome.result);
+                            self.successful = true;
+                            self.warnings = outcome.warnings;
+                            commands.push(Command::perform(async {}, |_| {
+                                app::Message::InstallationSuccessful
+                            }));
+                            commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                        }
+                        Err(failure) if failure.code == ErrorCode::Cancelled => {
+                            log::info!("Installation cancelled");
+                            self.cancelled = true;
+                        }
+                        Err(failure) => {
+                            log::error!("Installation failed: {:?}", failure);
+                            let title = if self.batch.is_empty() {
+                                let name = failure
+                                    .error
+                                    .app
+                                    .clone()
+                                    .or_else(|| self.file_name.clone())
+                                    .unwrap_or_default();
+                                fl!("installation-failed", name = name)
+                            } else {
+                                fl!("batch-failed", count = self.batch.len())
+                            };
+                            self.error = Some(ErrorBanner::from_failure(title, &failure));
+                        }
+                    }
+                }
+            },
+            InstallFromFileMessage::Cancel => {
+                if let Some(canceller) = &self.canceller {
+                    canceller.cancel();
+                    self.cancelling = true;
+                }
+            }
+            InstallFromFileMessage::ToggleErrorDetails => {
+                if let Some(error) = &mut self.error {
+                    error.toggle_details();
+                }
             }
+            InstallFromFileMessage::DismissError => self.error = None,
         }
         Command::batch(commands)
     }
+
+    /// Read an AppImage on a blocking thread to review it, without installing it
+    fn inspect(&mut self, path: PathBuf) -> Command<crate::app::Message> {
+        self.file_name = path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string());
+        self.inspecting = true;
+        self.inspected_path = Some(path.clone());
+        cosmic::command::future(async move {
+            let preview = tokio::task::spawn_blocking(move || {
+                preview_app_image(&path, default_trusted_keys_dir().as_deref())
+            })
+            .await
+            .unwrap_or_else(|e| Err(Error::other("Failed to read the AppImage").caused_by(e)))
+            .map(Box::new);
+            app::Message::InstallFromFile(InstallFromFileMessage::PreviewLoaded(preview))
+        })
+    }
+
+    /// List the AppImages to install together, in place of the review of a single one
+    fn start_batch(&mut self, file_paths: Vec<PathBuf>) {
+        log::info!("Installing a batch of {} AppImages", file_paths.len());
+        self.inspecting = false;
+        self.inspected_path = None;
+        self.preview = None;
+        self.exec_line = None;
+        self.batch = file_paths
+            .into_iter()
+            .map(|file_path| BatchEntry {
+                file_path,
+                status: None,
+            })
+            .collect();
+    }
+
+    /// The AppImages chosen but not reviewed or installed yet, that a dropped file joins
+    fn pending_paths(&self) -> Vec<PathBuf> {
+        if self.request.is_some() {
+            Vec::new()
+        } else if !self.batch.is_empty() && !self.batch_started() {
+            self.batch.iter().map(|entry| entry.file_path.clone()).collect()
+        } else {
+            self.inspected_path.iter().cloned().collect()
+        }
+    }
+
+    /// Run the backend on the AppImages, a single run installs all of them so that a
+    /// system install asks for the password once
+    fn start_install(&mut self, file_paths: Vec<PathBuf>, on_duplicate: DuplicatePolicy) {
+        self.phase = None;
+        self.cancelling = false;
+        let config = Config::load();
+        let scope = InstallScope::ALL[self.scope];
+        let installation_dir = self.installation_dir();
+        log::info!("Using installation dir : {:?}", installation_dir);
+        let signature_policy = config.1.signature_policy;
+
+        let mut args: Vec<OsString> = vec!["--action".into(), "install".into()];
+        for file_path in file_paths {
+            args.push("--file-path".into());
+            args.push(file_path.into());
+        }
+        args.extend([
+            "--install-dir".into(),
+            installation_dir.into(),
+            "--no-sandbox".into(),
+            self.no_sandbox.to_string().into(),
+            "--signature-policy".into(),
+            signature_policy.to_string().into(),
+            "--on-duplicate".into(),
+            on_duplicate.to_string().into(),
+        ]);
+        // the backend may run as root, pass the keyring of the current user
+        if let Some(trusted_keys_dir) = default_trusted_keys_dir() {
+            args.push("--trusted-keys-dir".into());
+            args.push(trusted_keys_dir.into());
+        }
+
+        // the subscription runs the backend and reports its progress
+        self.requests_started += 1;
+        self.request = Some(BackendRequest {
+            id: self.requests_started,
+            scope,
+            args,
+        });
+    }
+
+    /// Runs the backend while an install is in progress
+    pub fn subscription(&self) -> Subscription<InstallFromFileMessage> {
+        match &self.request {
+            Some(request) => {
+                backend_subscription(request.clone()).map(InstallFromFileMessage::Backend)
+            }
+            None => Subscription::none(),
+        }
+    }
+
+    /// What the running install is doing
+    fn phase_label(&self) -> String {
+        if self.cancelling {
+            return fl!("cancelling");
+        }
+        match self.phase {
+            None | Some(InstallPhase::Hashing) => fl!("install-phase-hashing"),
+            Some(InstallPhase::Copying {
+                done_bytes,
+                total_bytes,
+            }) => fl!(
+                "install-phase-copying",
+                done = megabytes(done_bytes),
+                total = megabytes(total_bytes)
+            ),
+            Some(InstallPhase::Inspecting) => fl!("install-phase-inspecting"),
+            Some(InstallPhase::Icons) => fl!("install-phase-icons"),
+            Some(InstallPhase::DesktopEntry) => fl!("install-phase-desktop-entry"),
+            Some(InstallPhase::Committing) => fl!("install-phase-committing"),
+        }
+    }
+}
+
+/// A row of the details of the reviewed AppImage
+fn detail_row<'a>(label: String, value: String) -> Element<'a, InstallFromFileMessage> {
+    widget::row()
+        .push(widget::text::text(label).width(Length::from(160)))
+        .push(widget::text::text(value).width(Length::Fill))
+        .into()
+}
+
+fn signature_label(signature: &SignatureStatus) -> String {
+    match signature {
+        SignatureStatus::Signed {
+            trusted: true,
+            fingerprint,
+        } => fl!("signature-trusted", fingerprint = fingerprint.as_str()),
+        SignatureStatus::Signed {
+            trusted: false,
+            fingerprint,
+        } => fl!("signature-untrusted", fingerprint = fingerprint.as_str()),
+        SignatureStatus::Unsigned => fl!("signature-unsigned"),
+        SignatureStatus::Invalid { reason } => {
+            fl!("signature-invalid", reason = reason.as_str())
+        }
+        SignatureStatus::Unverified { reason } => {
+            fl!("signature-unverified", reason = reason.as_str())
+        }
+    }
+}
+
+fn scope_index(scope: InstallScope) -> usize {
+    InstallScope::ALL
+        .iter()
+        .position(|candidate| *candidate == scope)
+        .unwrap_or_default()
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1}", bytes as f64 / 1_000_000.0)
 }
@@ -1,5 +1,11 @@
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
 
+use common_utils::backend_event::{ActionResult, ErrorCode};
+use common_utils::error::Error;
+use common_utils::install_scope::InstallScope;
+use common_utils::letter_avatar::letter_avatar_svg;
+use common_utils::signature::default_trusted_keys_dir;
 use common_utils::{app_images_helpers::read_all_app, models::installed_app::InstalledApp};
 use cosmic::{
     iced::{self, Length, Padding},
@@ -7,42 +13,90 @@ use cosmic::{
     Command, Element,
 };
 
+use crate::config::Config;
+use crate::core::backend::{backend_command, run_backend_task, BackendFailure, BackendOutcome};
+use crate::core::error_banner::ErrorBanner;
 use crate::{app, fl};
 
 #[derive(Debug, Clone)]
 pub enum InstalledListMessage {
     UninstallApp(InstalledApp),
+    UninstallFinished(InstalledApp, Result<BackendOutcome, BackendFailure>),
     UninstallationComplete,
     LoadApps,
+    AppsLoaded(Result<Vec<InstalledApp>, Error>),
     RunApp(InstalledApp),
+    UpdateApp(InstalledApp),
+    UpdateFinished(InstalledApp, Result<BackendOutcome, BackendFailure>),
+    RollbackApp(InstalledApp),
+    RollbackFinished(InstalledApp, Result<BackendOutcome, BackendFailure>),
+    ToggleErrorDetails,
+    DismissError,
 }
 
+/// What the backend is doing with an installed app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowAction {
+    Uninstalling,
+    Updating,
+    RollingBack,
+}
+
+/// The apps are loaded in the background by a `LoadApps` message
+#[derive(Default)]
 pub struct InstalledList {
     installed_apps: Vec<InstalledApp>,
+    /// Whether the installed apps are being read
+    loading: bool,
+    /// The apps the backend is working on, by scope and id
+    busy: HashMap<(InstallScope, String), RowAction>,
+    /// The outcome of the last action, when it needs to be told
+    notice: Option<String>,
+    /// The failure of the last action
+    error: Option<ErrorBanner>,
 }
 
-impl Default for InstalledList {
-    // Initialize default
-    fn default() -> Self {
-        let apps = read_all_app();
-        Self {
-            installed_apps: if apps.is_ok() {
-                apps.unwrap()
-            } else {
-                Vec::new()
-            },
-        }
+impl InstalledList {
+    fn row_action(&self, app: &InstalledApp) -> Option<RowAction> {
+        self.busy.get(&(app.scope, app.id.clone())).copied()
     }
-}
 
-impl InstalledList {
-    pub fn load_apps(&mut self) {
-        let apps = read_all_app();
-        self.installed_apps = if apps.is_ok() {
-            apps.unwrap()
-        } else {
-            Vec::new()
-        }
+    /// Run a backend action on an app in the background, the row is busy until it finishes
+    fn run_app_action(
+        &mut self,
+        app: InstalledApp,
+        action: RowAction,
+        on_finish: fn(InstalledApp, Result<BackendOutcome, BackendFailure>) -> InstalledListMessage,
+    ) -> Command<crate::app::Message> {
+        let command = backend_command(app.scope).map(|mut cmd| {
+            let action_arg = match action {
+                RowAction::Uninstalling => "uninstall",
+                RowAction::Updating => "update-app",
+                RowAction::RollingBack => "rollback",
+            };
+            cmd.arg("--action")
+                .arg(action_arg)
+                .arg("--app-id")
+                .arg(&app.id);
+            // an update is installed with the same checks as an install
+            if action == RowAction::Updating {
+                cmd.arg("--signature-policy")
+                    .arg(Config::load().1.signature_policy.to_string());
+                if let Some(trusted_keys_dir) = default_trusted_keys_dir() {
+                    cmd.arg("--trusted-keys-dir").arg(trusted_keys_dir);
+                }
+            }
+            cmd
+        });
+
+        self.busy.insert((app.scope, app.id.clone()), action);
+        cosmic::command::future(async move {
+            let result = match command {
+                Ok(cmd) => run_backend_task(cmd).await,
+                Err(error) => Err(BackendFailure::new(ErrorCode::Failure, error)),
+            };
+            app::Message::InstalledList(on_finish(app, result))
+        })
     }
 
     pub fn view<'a>(&'a self) -> Element<'a, InstalledListMessage> {
@@ -51,59 +105,126 @@ impl InstalledList {
 
         col = col.push(widget::vertical_space(Length::from(20)));
 
+        if let Some(notice) = &self.notice {
+            col = col
+                .push(widget::text::text(notice.as_str()))
+                .push(widget::vertical_space(Length::from(20)));
+        }
+
+        if let Some(error) = &self.error {
+            col = col
+                .push(error.view(
+                    InstalledListMessage::ToggleErrorDetails,
+                    InstalledListMessage::DismissError,
+                ))
+                .push(widget::vertical_space(Length::from(20)));
+        }
+
+        if self.loading && self.installed_apps.is_empty() {
+            col = col.push(widget::text::text(fl!("loading-apps")));
+        }
+
         for app in &self.installed_apps {
-            col = col.push(
-                widget::container(
-                    widget::row()
-                        .push(
-                            widget::column()
-                                .push(
-                                    widget::row()
-                                        .push(
-                                            widget::image::Image::new(
-                                                app.icon_path.clone().unwrap(),
-                                            )
-                                            .width(Length::from(50)),
-                                        )
-                                        .push(widget::horizontal_space(Length::from(10)))
-                                        .push(
-                                            widget::text::title3(&app.name)
-                                                .vertical_alignment(
-                                                    iced::alignment::Vertical::Center,
-                                                )
-                                                .height(Length::Fill),
+            let row_action = self.row_action(app);
+            let idle = row_action.is_none();
+            let mut card = widget::column().push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::row()
+                                    .push(app_icon(app))
+                                    .push(widget::horizontal_space(Length::from(10)))
+                                    .push(
+                                        widget::column()
+                                            .push(widget::text::title3(&app.name))
+                                            .push(widget::text::caption(
+                                                match (row_action, &app.update_info) {
+                                                    (Some(RowAction::Uninstalling), _) => {
+                                                        fl!("uninstalling")
+                                                    }
+                                                    (Some(RowAction::Updating), _) => {
+                                                        fl!("updating")
+                                                    }
+                                                    (Some(RowAction::RollingBack), _) => {
+                                                        fl!("rolling-back")
+                                                    }
+                                                    (None, Some(update_info)) => fl!(
+                                                        "updates-from",
+                                                        source = update_info.description()
+                                                    ),
+                                                    (None, None) => fl!("no-update-info"),
+                                                },
+                                            ))
+                                            .height(Length::Fill),
+                                    ),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::row()
+                                    .push(match &app.update_info {
+                                        Some(_) => Element::from(
+                                            cosmic::widget::button::text(fl!("update-app"))
+                                                .on_press_maybe(idle.then(|| {
+                                                    InstalledListMessage::UpdateApp(app.clone())
+                                                }))
+                                                .style(widget::button::Style::Standard),
                                         ),
-                                )
-                                .width(Length::Fill),
-                        )
-                        .push(
-                            widget::column()
-                                .push(
-                                    widget::row()
-                                        .push(
-                                            cosmic::widget::button::text(fl!("uninstall"))
-                                                .on_press(InstalledListMessage::UninstallApp(
-                                                    app.clone(),
-                                                ))
-                                                .style(widget::button::Style::Destructive),
+                                        None => Element::from(widget::horizontal_space(
+                                            Length::from(0),
+                                        )),
+                                    })
+                                    .push(widget::horizontal_space(Length::from(10)))
+                                    .push(if app.versions.len() > 1 {
+                                        Element::from(
+                                            cosmic::widget::button::text(fl!("roll-back"))
+                                                .on_press_maybe(idle.then(|| {
+                                                    InstalledListMessage::RollbackApp(app.clone())
+                                                }))
+                                                .style(widget::button::Style::Standard),
                                         )
-                                        .push(widget::horizontal_space(Length::from(10)))
-                                        .push(
-                                            cosmic::widget::button::text(fl!("run-app"))
-                                                .on_press(InstalledListMessage::RunApp(app.clone()))
-                                                .style(widget::button::Style::Suggested),
-                                        ),
-                                )
-                                .width(Length::Fill)
-                                .height(Length::Fill)
-                                .align_items(iced::Alignment::End),
-                        )
-                        .height(Length::from(50)),
-                )
-                .padding(Padding::from(10))
-                .style(cosmic::theme::Container::Card)
-                .width(iced::Length::Fill)
-                .align_x(iced::alignment::Horizontal::Left),
+                                    } else {
+                                        Element::from(widget::horizontal_space(Length::from(0)))
+                                    })
+                                    .push(widget::horizontal_space(Length::from(10)))
+                                    .push(
+                                        cosmic::widget::button::text(fl!("uninstall"))
+                                            .on_press_maybe(idle.then(|| {
+                                                InstalledListMessage::UninstallApp(app.clone())
+                                            }))
+                                            .style(widget::button::Style::Destructive),
+                                    )
+                                    .push(widget::horizontal_space(Length::from(10)))
+                                    .push(
+                                        cosmic::widget::button::text(fl!("run-app"))
+                                            .on_press(InstalledListMessage::RunApp(app.clone()))
+                                            .style(widget::button::Style::Suggested),
+                                    ),
+                            )
+                            .width(Length::Fill)
+                            .height(Length::Fill)
+                            .align_items(iced::Alignment::End),
+                    )
+                    .height(Length::from(50)),
+            );
+            // the versions kept on disk, to roll back to them
+            if app.versions.len() > 1 {
+                card = card
+                    .push(widget::vertical_space(Length::from(5)))
+                    .push(widget::text::caption(fl!(
+                        "version-history",
+                        versions = version_history(app)
+                    )));
+            }
+            col = col.push(
+                widget::container(card)
+                    .padding(Padding::from(10))
+                    .style(cosmic::theme::Container::Card)
+                    .width(iced::Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Left),
             );
             col = col.push(widget::vertical_space(Length::from(10)));
         }
@@ -120,64 +241,125 @@ impl InstalledList {
         match message {
             InstalledListMessage::UninstallApp(app) => {
                 log::info!("uninstall app: {:?}", app);
+                self.notice = None;
+                self.error = None;
+                commands.push(self.run_app_action(
+                    app,
+                    RowAction::Uninstalling,
+                    InstalledListMessage::UninstallFinished,
+                ));
+            }
+            InstalledListMessage::UninstallFinished(app, result) => {
+                self.busy.remove(&(app.scope, app.id.clone()));
+                match result {
+                    Ok(_) => {
+                        log::info!("Uninstallation successful");
 
-                let current_exe = std::env::current_exe().map_err(|_| "unable to get current exe");
-                match current_exe {
-                    Ok(current_exe) => {
-                        let cmd = std::process::Command::new("pkexec")
-                            .arg(current_exe.parent().unwrap().join("app_hub_backend"))
-                            .arg("--action")
-                            .arg("uninstall")
-                            .arg("--uninstall-app-name")
-                            .arg(app.name)
-                            .spawn();
-                        match cmd {
-                            Ok(mut res) => {
-                                if let Some(stdout) = res.stdout.take() {
-                                    let reader = BufReader::new(stdout);
-                                    for line in reader.lines() {
-                                        if let Ok(line) = line {
-                                            log::debug!("app_hub_backend output: {}", line);
-                                        }
-                                    }
-                                }
-
-                                if let Some(stderr) = res.stderr.take() {
-                                    let reader = BufReader::new(stderr);
-                                    for line in reader.lines() {
-                                        if let Ok(line) = line {
-                                            log::error!("app_hub_backend error: {}", line);
-                                        }
-                                    }
-                                }
-
-                                let output =
-                                    res.wait_with_output().expect("Failed to wait on child");
-                                if output.status.success() {
-                                    log::info!("Uninstallation successful");
-
-                                    commands.push(Command::perform(async {}, |_| {
-                                        app::Message::UninstallationComplete
-                                    }));
-                                } else {
-                                    log::error!("Uninstallation failed");
-                                    //return Err("Uninstallation failed".to_string());
-                                }
-                            }
-                            Err(error) => {
-                                log::error!("error: {:?}", error);
-                            }
+                        commands.push(Command::perform(async {}, |_| {
+                            app::Message::UninstallationComplete
+                        }));
+                    }
+                    Err(failure) => {
+                        log::error!("Uninstallation failed: {:?}", failure);
+                        self.error = Some(ErrorBanner::from_failure(
+                            fl!("uninstallation-failed", name = app.name.as_str()),
+                            &failure,
+                        ));
+                    }
+                }
+            }
+            InstalledListMessage::UpdateApp(app) => {
+                log::info!("update app: {:?}", app);
+                self.notice = None;
+                self.error = None;
+                commands.push(self.run_app_action(
+                    app,
+                    RowAction::Updating,
+                    InstalledListMessage::UpdateFinished,
+                ));
+            }
+            InstalledListMessage::UpdateFinished(app, result) => {
+                self.busy.remove(&(app.scope, app.id.clone()));
+                match result {
+                    Ok(outcome) => {
+                        log::info!("Update successful: {:?}", outcome.result);
+                        if let Some(ActionResult::UpToDate { .. }) = outcome.result {
+                            self.notice = Some(fl!("up-to-date", name = app.name.as_str()));
                         }
+                        commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                    }
+                    Err(failure) => {
+                        log::error!("Update failed: {:?}", failure);
+                        self.error = Some(ErrorBanner::from_failure(
+                            fl!("update-failed", name = app.name.as_str()),
+                            &failure,
+                        ));
+                    }
+                }
+            }
+            InstalledListMessage::RollbackApp(app) => {
+                log::info!("roll back app: {:?}", app);
+                self.notice = None;
+                self.error = None;
+                commands.push(self.run_app_action(
+                    app,
+                    RowAction::RollingBack,
+                    InstalledListMessage::RollbackFinished,
+                ));
+            }
+            InstalledListMessage::RollbackFinished(app, result) => {
+                self.busy.remove(&(app.scope, app.id.clone()));
+                match result {
+                    Ok(outcome) => {
+                        log::info!("Rollback successful: {:?}", outcome.result);
+                        if let Some(ActionResult::RolledBack {
+                            version: Some(version),
+                            ..
+                        }) = outcome.result
+                        {
+                            self.notice = Some(fl!(
+                                "rolled-back",
+                                name = app.name.as_str(),
+                                version = version.as_str()
+                            ));
+                        }
+                        commands.push(Command::perform(async {}, |_| app::Message::LoadApps));
+                    }
+                    Err(failure) => {
+                        log::error!("Rollback failed: {:?}", failure);
+                        self.error = Some(ErrorBanner::from_failure(
+                            fl!("rollback-failed", name = app.name.as_str()),
+                            &failure,
+                        ));
                     }
-                    Err(_) => todo!(),
                 }
             }
             InstalledListMessage::UninstallationComplete | InstalledListMessage::LoadApps => {
-                self.load_apps();
+                // the registries are read on a blocking thread
+                self.loading = true;
+                commands.push(cosmic::command::future(async {
+                    let apps = tokio::task::spawn_blocking(read_all_app)
+                        .await
+                        .unwrap_or_else(|e| {
+                            Err(Error::other("Failed to read the installed apps").caused_by(e))
+                        });
+                    app::Message::InstalledList(InstalledListMessage::AppsLoaded(apps))
+                }));
+            }
+            InstalledListMessage::AppsLoaded(apps) => {
+                self.loading = false;
+                match apps {
+                    Ok(apps) => self.installed_apps = apps,
+                    Err(e) => {
+                        log::error!("Failed to load the installed apps: {}", e);
+                        self.installed_apps = Vec::new();
+                        self.error = Some(ErrorBanner::new(fl!("loading-apps-failed"), &e));
+                    }
+                }
             }
             InstalledListMessage::RunApp(installed_app) => {
                 log::info!("running app: {:?}", installed_app);
-                let cmd = std::process::Command::new(installed_app.app_path).spawn();
+                let cmd = std::process::Command::new(&installed_app.app_path).spawn();
                 match cmd {
                     Ok(mut res) => {
                         if let Some(stderr) = res.stderr.take() {
@@ -191,10 +373,51 @@ impl InstalledList {
                     }
                     Err(error) => {
                         log::error!("error: {:?}", error);
+                        self.error = Some(ErrorBanner::new(
+                            fl!("run-app-failed", name = installed_app.name.as_str()),
+                            &Error::io(format!("Failed to run {}", installed_app.app_path), error),
+                        ));
                     }
                 }
             }
+            InstalledListMessage::ToggleErrorDetails => {
+                if let Some(error) = &mut self.error {
+                    error.toggle_details();
+                }
+            }
+            InstalledListMessage::DismissError => self.error = None,
         }
         Command::batch(commands)
     }
 }
+
+/// The icon of the app, or a letter avatar when it has none
+fn app_icon<'a>(app: &InstalledApp) -> Element<'a, InstalledListMessage> {
+    match &app.icon_path {
+        Some(icon_path) => widget::image::Image::new(icon_path.clone())
+            .width(Length::from(50))
+            .into(),
+        None => widget::svg(widget::svg::Handle::from_memory(
+            letter_avatar_svg(&app.name).into_bytes(),
+        ))
+        .width(Length::from(50))
+        .into(),
+    }
+}
+
+/// The kept versions of an app, the current one first
+fn version_history(app: &InstalledApp) -> String {
+    app.versions
+        .iter()
+        .enumerate()
+        .map(|(index, version)| {
+            let label = version.version.clone().unwrap_or_else(|| version.dir_name());
+            if index == 0 {
+                fl!("current-version", version = label.as_str())
+            } else {
+                label
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
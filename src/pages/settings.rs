@@ -6,16 +6,21 @@ use cosmic::{
     Command, Element,
 };
 
+use common_utils::signature::SignaturePolicy;
+
 use crate::{config::Config, fl};
 
 #[derive(Debug, Clone)]
 pub enum SettingsMessage {
     InstallationDirChanged(String),
+    SignaturePolicySelected(usize),
     Submitted,
 }
 
 pub struct Settings {
     installation_dir_value: String,
+    signature_policy_options: Vec<String>,
+    signature_policy: usize,
 }
 
 impl Default for Settings {
@@ -24,6 +29,15 @@ impl Default for Settings {
         let config = Config::load();
         Self {
             installation_dir_value: config.1.installation_dir.to_string_lossy().to_string(),
+            signature_policy_options: vec![
+                fl!("signature-policy-refuse"),
+                fl!("signature-policy-warn"),
+                fl!("signature-policy-allow"),
+            ],
+            signature_policy: SignaturePolicy::ALL
+                .iter()
+                .position(|policy| *policy == config.1.signature_policy)
+                .unwrap_or_default(),
         }
     }
 }
@@ -47,6 +61,16 @@ impl Settings {
 
         col = col.push(widget::vertical_space(Length::from(20)));
 
+        col = col.push(widget::text::text(fl!("signature-policy")));
+
+        col = col.push(widget::dropdown(
+            &self.signature_policy_options,
+            Some(self.signature_policy),
+            SettingsMessage::SignaturePolicySelected,
+        ));
+
+        col = col.push(widget::vertical_space(Length::from(20)));
+
         col = col.push(
             widget::container(
                 cosmic::widget::button::text(fl!("save-settings"))
@@ -70,13 +94,22 @@ impl Settings {
                 self.installation_dir_value = installation_dir;
                 log::info!("new installation dir: {}", self.installation_dir_value);
             }
+            SettingsMessage::SignaturePolicySelected(signature_policy) => {
+                self.signature_policy = signature_policy;
+                log::info!("new signature policy: {}", SignaturePolicy::ALL[signature_policy]);
+            }
             SettingsMessage::Submitted => {
                 log::info!("setting change submited");
                 let mut config = Config::load();
+                let config_handler = config.0.unwrap();
                 config.1.set_installation_dir(
-                    &config.0.unwrap(),
+                    &config_handler,
                     PathBuf::from(&self.installation_dir_value),
                 );
+                config.1.set_signature_policy(
+                    &config_handler,
+                    SignaturePolicy::ALL[self.signature_policy],
+                );
             }
         }
         Command::none()